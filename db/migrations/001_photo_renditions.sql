@@ -0,0 +1,15 @@
+-- 写真のリサイズ版（レンディション）
+CREATE TABLE photo_renditions (
+    id SERIAL PRIMARY KEY,
+    photo_id INTEGER NOT NULL REFERENCES photos(id) ON DELETE CASCADE,
+    size INTEGER NOT NULL,
+    format TEXT NOT NULL,
+    image_path TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    size_in_bytes BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (photo_id, size, format)
+);
+
+CREATE INDEX photo_renditions_photo_id_idx ON photo_renditions (photo_id);
@@ -0,0 +1,20 @@
+-- 写真から抽出した撮影情報（EXIF/XMP）
+-- taken_at はタイムゾーン不明の場合 UTC として保存する
+CREATE TABLE photo_metadata (
+    photo_id INTEGER PRIMARY KEY REFERENCES photos(id) ON DELETE CASCADE,
+    camera_make TEXT,
+    camera_model TEXT,
+    lens_model TEXT,
+    focal_length DOUBLE PRECISION,
+    aperture DOUBLE PRECISION,
+    exposure_time TEXT,
+    iso INTEGER,
+    taken_at TIMESTAMPTZ,
+    timezone_offset_minutes INTEGER,
+    latitude DOUBLE PRECISION,
+    longitude DOUBLE PRECISION,
+    altitude DOUBLE PRECISION,
+    extracted_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX photo_metadata_taken_at_idx ON photo_metadata (taken_at);
@@ -0,0 +1,15 @@
+-- 位置情報・メタデータの削除設定
+-- mode: none / location / all, target: original（保存する元画像） / renditions（配信用のみ）
+ALTER TABLE users
+    ADD COLUMN metadata_strip_mode TEXT NOT NULL DEFAULT 'none'
+        CHECK (metadata_strip_mode IN ('none', 'location', 'all')),
+    ADD COLUMN metadata_strip_target TEXT NOT NULL DEFAULT 'original'
+        CHECK (metadata_strip_target IN ('original', 'renditions'));
+
+-- アップロード時点の設定を写真ごとに保持する
+ALTER TABLE photos
+    ADD COLUMN metadata_strip_mode TEXT NOT NULL DEFAULT 'none'
+        CHECK (metadata_strip_mode IN ('none', 'location', 'all')),
+    ADD COLUMN metadata_strip_target TEXT NOT NULL DEFAULT 'original'
+        CHECK (metadata_strip_target IN ('original', 'renditions')),
+    ADD COLUMN metadata_stripped_at TIMESTAMPTZ;
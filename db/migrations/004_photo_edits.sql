@@ -0,0 +1,18 @@
+-- 非破壊編集（回転・反転・トリミング・明るさ/コントラスト）
+-- operation は EditOperation を JSON にしたもの
+CREATE TABLE photo_edits (
+    id SERIAL PRIMARY KEY,
+    photo_id INTEGER NOT NULL REFERENCES photos(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    operation TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (photo_id, position)
+);
+
+-- width/height は編集後のサイズ、original_* は元画像のサイズ
+ALTER TABLE photos
+    ADD COLUMN original_width INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN original_height INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN edit_version INTEGER NOT NULL DEFAULT 0;
+
+UPDATE photos SET original_width = width, original_height = height;
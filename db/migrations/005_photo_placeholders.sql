@@ -0,0 +1,6 @@
+-- 読み込み中に表示するプレースホルダー（編集後の画像から計算）
+ALTER TABLE photos
+    ADD COLUMN blurhash TEXT,
+    ADD COLUMN lqip TEXT,
+    ADD COLUMN dominant_color TEXT,
+    ADD COLUMN average_color TEXT;
@@ -0,0 +1,15 @@
+-- 知覚ハッシュ（pHash, 64bit）
+ALTER TABLE photos ADD COLUMN phash BIGINT;
+
+-- ハミング距離検索用のインデックス（multi-index hashing）
+-- 64bit を 8bit ずつ 8 区間に分けて保存する。距離が 7 以下なら少なくとも 1 区間は完全一致するため、
+-- 一致する区間を持つ写真だけを候補として距離を計算すればよい
+CREATE TABLE photo_hash_bands (
+    photo_id INTEGER NOT NULL REFERENCES photos(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL,
+    band SMALLINT NOT NULL,
+    value SMALLINT NOT NULL,
+    PRIMARY KEY (photo_id, band)
+);
+
+CREATE INDEX photo_hash_bands_lookup_idx ON photo_hash_bands (user_id, band, value);
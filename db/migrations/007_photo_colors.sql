@@ -0,0 +1,12 @@
+-- 写真ごとの代表色パレット（編集後の画像から計算）。色検索のため CIELAB の値も保存する
+CREATE TABLE photo_colors (
+    photo_id INTEGER NOT NULL REFERENCES photos(id) ON DELETE CASCADE,
+    position SMALLINT NOT NULL,
+    color TEXT NOT NULL,
+    l REAL NOT NULL,
+    a REAL NOT NULL,
+    b REAL NOT NULL,
+    -- 画像に占める割合（0〜1）
+    weight REAL NOT NULL,
+    PRIMARY KEY (photo_id, position)
+);
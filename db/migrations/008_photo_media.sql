@@ -0,0 +1,9 @@
+-- 動画・アニメーション画像への対応
+-- media_type: image / animated / video
+ALTER TABLE photos
+    ADD COLUMN media_type TEXT NOT NULL DEFAULT 'image',
+    ADD COLUMN duration_seconds DOUBLE PRECISION,
+    ADD COLUMN codec TEXT,
+    ADD COLUMN frame_rate DOUBLE PRECISION,
+    -- 先頭数秒の音声なしMP4
+    ADD COLUMN preview_path TEXT;
@@ -0,0 +1,26 @@
+-- バックグラウンドジョブのキュー
+-- status: pending / running / completed / dead（再試行の上限に達したもの）
+CREATE TABLE jobs (
+    id BIGSERIAL PRIMARY KEY,
+    job_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending'
+        CHECK (status IN ('pending', 'running', 'completed', 'dead')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    locked_at TIMESTAMPTZ,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
+
+-- 取得待ちのジョブを種類ごとに実行予定順で引く
+CREATE INDEX jobs_pending_idx ON jobs (job_type, run_at) WHERE status = 'pending';
+CREATE INDEX jobs_running_idx ON jobs (job_type, locked_at) WHERE status = 'running';
+CREATE INDEX jobs_status_idx ON jobs (status, updated_at);
+
+-- ジョブの管理画面などにアクセスできるユーザー
+ALTER TABLE users
+    ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT false;
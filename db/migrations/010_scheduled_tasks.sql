@@ -0,0 +1,16 @@
+-- 定期実行タスクの実行履歴
+-- 同じ予定時刻の実行は1回だけ記録され、複数のサーバーで重複して実行されない
+-- status: running / succeeded / failed
+CREATE TABLE scheduled_task_runs (
+    id BIGSERIAL PRIMARY KEY,
+    task_name TEXT NOT NULL,
+    scheduled_for TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL DEFAULT 'running'
+        CHECK (status IN ('running', 'succeeded', 'failed')),
+    detail TEXT,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ,
+    UNIQUE (task_name, scheduled_for)
+);
+
+CREATE INDEX scheduled_task_runs_started_at_idx ON scheduled_task_runs (task_name, started_at DESC);
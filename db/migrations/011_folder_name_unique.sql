@@ -0,0 +1,25 @@
+-- パスでフォルダーを一意に特定できるよう、同じ親フォルダーの下では名前を重複させない
+
+-- パスの区切り文字と区別できないため、名前に含まれる「/」は置き換える
+UPDATE folders SET name = replace(name, '/', '_') WHERE name LIKE '%/%';
+
+-- 既存の重複は後から作られたフォルダーの名前にIDを付けて解消する
+-- 付け替えた名前が既存のフォルダー（例:「旅行 (12)」）と重なることがあるため、重複がなくなるまで繰り返す
+DO $$
+BEGIN
+    LOOP
+        UPDATE folders f
+        SET name = f.name || ' (' || f.id || ')'
+        WHERE EXISTS (
+            SELECT 1
+            FROM folders other
+            WHERE other.parent_id = f.parent_id
+            AND other.name = f.name
+            AND other.id < f.id
+        );
+        EXIT WHEN NOT FOUND;
+    END LOOP;
+END
+$$;
+
+CREATE UNIQUE INDEX folders_parent_id_name_key ON folders (parent_id, name);
@@ -0,0 +1,11 @@
+-- フォルダーの表紙写真と、フォルダー・写真の手動の並び順
+-- cover_photo_id が NULL の場合は配下で一番新しい写真を表紙にする
+ALTER TABLE folders
+    ADD COLUMN cover_photo_id INTEGER REFERENCES photos(id) ON DELETE SET NULL,
+    ADD COLUMN sort_position INTEGER;
+
+-- sort_position が NULL のものは並べ替えたものの後ろに並ぶ
+ALTER TABLE photos ADD COLUMN sort_position INTEGER;
+
+CREATE INDEX folders_parent_id_sort_position_idx ON folders (parent_id, sort_position);
+CREATE INDEX photos_folder_id_sort_position_idx ON photos (folder_id, sort_position);
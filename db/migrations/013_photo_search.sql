@@ -0,0 +1,120 @@
+-- 写真の名前・説明・フォルダー名・タグの全文検索
+-- 日本語は単語に区切れないため、ひらがな・カタカナ・漢字の並びは1文字と2文字ずつ（uni-gram・bi-gram）に分けて索引する
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- 全角英数字・半角カナをそろえて小文字にする
+CREATE FUNCTION search_normalize(input TEXT) RETURNS TEXT
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT lower(normalize(COALESCE(input, ''), NFKC))
+$$;
+
+CREATE FUNCTION search_tokens(input TEXT) RETURNS TEXT[]
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT COALESCE(ARRAY_AGG(DISTINCT token), '{}')
+    FROM regexp_split_to_table(search_normalize(input), '[^[:alnum:]]+') AS word,
+        regexp_matches(word, '[぀-ヿ㐀-鿿]+|[^぀-ヿ㐀-鿿]+', 'g') AS chunk,
+        unnest(
+            CASE
+                WHEN chunk[1] !~ '^[぀-ヿ㐀-鿿]' OR char_length(chunk[1]) = 1 THEN ARRAY[chunk[1]]
+                ELSE ARRAY(
+                    SELECT substr(chunk[1], i, n)
+                    FROM generate_series(1, char_length(chunk[1])) AS i, generate_series(1, 2) AS n
+                    WHERE i + n - 1 <= char_length(chunk[1])
+                )
+            END
+        ) AS token
+    WHERE word <> ''
+$$;
+
+-- 重みは位置を持つ語にしか付けられないため、語に連番の位置を振る
+CREATE FUNCTION search_vector(input TEXT, weight "char") RETURNS tsvector
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT setweight(COALESCE(string_agg(quote_literal(token) || ':' || least(position, 16383), ' '), '')::tsvector, weight)
+    FROM unnest(search_tokens(input)) WITH ORDINALITY AS t(token, position)
+$$;
+
+-- 検索語のすべての語を含む（前方一致）条件にする
+CREATE FUNCTION search_query(input TEXT) RETURNS tsquery
+LANGUAGE sql IMMUTABLE AS $$
+    SELECT array_to_string(ARRAY(SELECT quote_literal(token) || ':*' FROM unnest(search_tokens(input)) AS token), ' & ')::tsquery
+$$;
+
+-- search_text は部分一致・あいまい検索用、search_vector は全文検索用
+-- 重みは名前 A、フォルダー名・タグ B、説明 C
+ALTER TABLE photos
+    ADD COLUMN search_text TEXT NOT NULL DEFAULT '',
+    ADD COLUMN search_vector tsvector NOT NULL DEFAULT ''::tsvector;
+
+CREATE FUNCTION photos_search_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+DECLARE
+    folder_name TEXT;
+    tag_names TEXT;
+BEGIN
+    SELECT name INTO folder_name FROM folders WHERE id = NEW.folder_id;
+    SELECT string_agg(t.tag, ' ') INTO tag_names
+    FROM photo_tag_relations ptr
+    JOIN tags t ON t.id = ptr.tag_id
+    WHERE ptr.photo_id = NEW.id;
+
+    NEW.search_text := search_normalize(concat_ws(' ', NEW.name, NEW.description, folder_name, tag_names));
+    NEW.search_vector :=
+        search_vector(NEW.name, 'A')
+        || search_vector(concat_ws(' ', folder_name, tag_names), 'B')
+        || search_vector(NEW.description, 'C');
+    RETURN NEW;
+END;
+$$;
+
+CREATE TRIGGER photos_search_update
+    BEFORE INSERT OR UPDATE OF name, description, folder_id ON photos
+    FOR EACH ROW EXECUTE FUNCTION photos_search_update();
+
+-- フォルダー名・タグが変わったら関係する写真の検索用の列を作り直す
+CREATE FUNCTION folders_search_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    UPDATE photos SET folder_id = folder_id WHERE folder_id = NEW.id;
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER folders_search_update
+    AFTER UPDATE OF name ON folders
+    FOR EACH ROW WHEN (OLD.name IS DISTINCT FROM NEW.name)
+    EXECUTE FUNCTION folders_search_update();
+
+CREATE FUNCTION photo_tag_relations_search_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE photos SET folder_id = folder_id WHERE id = OLD.photo_id;
+    ELSE
+        UPDATE photos SET folder_id = folder_id WHERE id = NEW.photo_id;
+    END IF;
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER photo_tag_relations_search_update
+    AFTER INSERT OR DELETE ON photo_tag_relations
+    FOR EACH ROW EXECUTE FUNCTION photo_tag_relations_search_update();
+
+CREATE FUNCTION tags_search_update() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    UPDATE photos SET folder_id = folder_id
+    WHERE id IN (SELECT photo_id FROM photo_tag_relations WHERE tag_id = NEW.id);
+    RETURN NULL;
+END;
+$$;
+
+CREATE TRIGGER tags_search_update
+    AFTER UPDATE OF tag ON tags
+    FOR EACH ROW WHEN (OLD.tag IS DISTINCT FROM NEW.tag)
+    EXECUTE FUNCTION tags_search_update();
+
+UPDATE photos SET folder_id = folder_id;
+
+CREATE INDEX photos_search_vector_idx ON photos USING GIN (search_vector);
+CREATE INDEX photos_search_text_trgm_idx ON photos USING GIN (search_text gin_trgm_ops);
@@ -0,0 +1,19 @@
+-- スマートフォルダー（保存した検索条件）。開くたびに条件で写真を検索する
+-- folder_id は検索範囲（配下のフォルダーを含む）。NULL の場合はすべてのフォルダー
+-- 検索範囲のフォルダーが完全に削除された場合は scope_deleted を立て、すべてのフォルダーを検索しないようにする
+CREATE TABLE smart_folders (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    tags TEXT,
+    q TEXT,
+    uploaded_from TIMESTAMPTZ,
+    uploaded_to TIMESTAMPTZ,
+    taken_from TIMESTAMPTZ,
+    taken_to TIMESTAMPTZ,
+    folder_id INTEGER REFERENCES folders(id) ON DELETE SET NULL,
+    scope_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (user_id, name)
+);
@@ -0,0 +1,29 @@
+-- ゴミ箱。削除した写真・フォルダーには trash_id を付けて一覧・検索から除外し、保持期間を過ぎたら完全に削除する
+-- フォルダーを削除した場合は、配下のフォルダーと写真にも同じ trash_id を付けてまとめて復元・削除する
+CREATE TABLE trash_entries (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- photo_id と folder_id のどちらか一方。写真・フォルダーを完全に削除すると消える
+    photo_id INTEGER REFERENCES photos(id) ON DELETE CASCADE,
+    folder_id INTEGER REFERENCES folders(id) ON DELETE CASCADE,
+    -- 削除したときの親フォルダー
+    original_parent_id INTEGER REFERENCES folders(id) ON DELETE SET NULL,
+    -- ルートフォルダーから親フォルダーまでの名前（ルートフォルダーは含まない）
+    -- 復元するときに親フォルダーが無くなっていれば、この名前でフォルダーを作り直す
+    original_path TEXT[] NOT NULL DEFAULT '{}',
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CHECK ((photo_id IS NULL) <> (folder_id IS NULL))
+);
+
+CREATE INDEX trash_entries_user_id_deleted_at_idx ON trash_entries (user_id, deleted_at);
+CREATE INDEX trash_entries_deleted_at_idx ON trash_entries (deleted_at);
+
+ALTER TABLE photos ADD COLUMN trash_id INTEGER REFERENCES trash_entries(id);
+ALTER TABLE folders ADD COLUMN trash_id INTEGER REFERENCES trash_entries(id);
+
+CREATE INDEX photos_trash_id_idx ON photos (trash_id) WHERE trash_id IS NOT NULL;
+CREATE INDEX folders_trash_id_idx ON folders (trash_id) WHERE trash_id IS NOT NULL;
+
+-- ゴミ箱のフォルダーは名前の重複の対象にしない
+DROP INDEX folders_parent_id_name_key;
+CREATE UNIQUE INDEX folders_parent_id_name_key ON folders (parent_id, name) WHERE trash_id IS NULL;
@@ -0,0 +1,25 @@
+-- アルバム。フォルダーとは別に、複数のフォルダーの写真をまとめる（写真の所属フォルダーは変わらない）
+-- cover_photo_id が NULL の場合はアルバムの先頭の写真を表紙にする
+CREATE TABLE albums (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    description TEXT,
+    cover_photo_id INTEGER REFERENCES photos(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (user_id, name)
+);
+
+-- 写真を完全に削除するとアルバムからも外れる。ゴミ箱にある間は一覧に出さず、元に戻すとアルバムにも戻る
+CREATE TABLE album_photos (
+    album_id INTEGER NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+    photo_id INTEGER NOT NULL REFERENCES photos(id) ON DELETE CASCADE,
+    -- アルバム内の並び順（小さい順）
+    position INTEGER NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (album_id, photo_id)
+);
+
+CREATE INDEX album_photos_album_id_position_idx ON album_photos (album_id, position, photo_id);
+CREATE INDEX album_photos_photo_id_idx ON album_photos (photo_id);
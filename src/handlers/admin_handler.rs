@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::message;
+use crate::models::job::{JobListQuery, JobResponse, JobStats};
+use crate::models::scheduled_task::{ScheduledTaskResponse, ScheduledTaskRun, ScheduledTaskRunQuery};
+use crate::models::user::Claims;
+use crate::utils::cron::CronSchedule;
+use crate::workers::job::JobType;
+use crate::workers::queue;
+use crate::workers::scheduler::ScheduledTask;
+
+const DEFAULT_JOB_LIMIT: i64 = 50;
+const MAX_JOB_LIMIT: i64 = 200;
+const JOB_STATUSES: [&str; 4] = ["pending", "running", "completed", "dead"];
+const DEFAULT_RUN_LIMIT: i64 = 50;
+const MAX_RUN_LIMIT: i64 = 200;
+
+pub(crate) async fn require_admin(req: &HttpRequest, db: &PgPool) -> Result<Claims, HttpResponse> {
+    let claims = extract_user_from_jwt(req)?;
+
+    let is_admin = sqlx::query_scalar!(
+        "SELECT is_admin FROM users WHERE id = $1",
+        claims.user_id,
+    )
+    .fetch_optional(db)
+    .await;
+
+    match is_admin {
+        Ok(Some(true)) => Ok(claims),
+        Ok(_) => Err(HttpResponse::Forbidden().body("管理者のみ利用できます")),
+        Err(e) => {
+            eprintln!("ユーザー取得失敗: {:?}", e);
+            Err(HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message()))
+        }
+    }
+}
+
+#[get("/admin/jobs")]
+pub async fn get_jobs(
+    req: HttpRequest,
+    query: web::Query<JobListQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(resp) = require_admin(&req, db.get_ref()).await {
+        return resp;
+    }
+
+    if let Some(status) = query.status.as_deref() {
+        if !JOB_STATUSES.contains(&status) {
+            return HttpResponse::BadRequest().body(format!("不正な status です: {}", status));
+        }
+    }
+
+    if let Some(job_type) = query.job_type.as_deref() {
+        if JobType::parse(job_type).is_none() {
+            return HttpResponse::BadRequest().body(format!("不正な job_type です: {}", job_type));
+        }
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_JOB_LIMIT).clamp(1, MAX_JOB_LIMIT);
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            job_type,
+            payload::TEXT AS "payload!",
+            status,
+            attempts,
+            max_attempts,
+            run_at,
+            last_error,
+            created_at,
+            updated_at,
+            completed_at
+        FROM
+            jobs
+        WHERE
+            ($1::TEXT IS NULL OR status = $1) AND
+            ($2::TEXT IS NULL OR job_type = $2) AND
+            ($3::BIGINT IS NULL OR id < $3)
+        ORDER BY
+            id DESC
+        LIMIT $4
+        "#,
+        query.status.as_deref(),
+        query.job_type.as_deref(),
+        query.before_id,
+        limit,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let jobs: Vec<JobResponse> = rows
+                .into_iter()
+                .map(|row| JobResponse {
+                    id: row.id,
+                    job_type: row.job_type,
+                    payload: serde_json::from_str(&row.payload).unwrap_or(serde_json::Value::Null),
+                    status: row.status,
+                    attempts: row.attempts,
+                    max_attempts: row.max_attempts,
+                    run_at: row.run_at,
+                    last_error: row.last_error,
+                    created_at: row.created_at,
+                    updated_at: row.updated_at,
+                    completed_at: row.completed_at,
+                })
+                .collect();
+
+            HttpResponse::Ok().json(serde_json::json!({ "data": jobs }))
+        }
+        Err(e) => {
+            eprintln!("ジョブ一覧の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
+
+// 種類・状態ごとの件数
+#[get("/admin/jobs/stats")]
+pub async fn get_job_stats(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(resp) = require_admin(&req, db.get_ref()).await {
+        return resp;
+    }
+
+    let rows = sqlx::query_as!(
+        JobStats,
+        r#"
+        SELECT
+            job_type,
+            status,
+            COUNT(*) AS "count!"
+        FROM
+            jobs
+        GROUP BY
+            job_type, status
+        ORDER BY
+            job_type, status
+        "#
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match rows {
+        Ok(stats) => HttpResponse::Ok().json(serde_json::json!({ "data": stats })),
+        Err(e) => {
+            eprintln!("ジョブ集計の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
+
+// 再試行の上限に達したジョブを再実行する
+#[post("/admin/jobs/{id}/retry")]
+pub async fn retry_job(
+    req: HttpRequest,
+    path: web::Path<i64>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(resp) = require_admin(&req, db.get_ref()).await {
+        return resp;
+    }
+
+    match queue::retry_dead(db.get_ref(), path.into_inner()).await {
+        Ok(true) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "ジョブを再実行待ちにしました",
+        })),
+        Ok(false) => HttpResponse::NotFound().body("再実行できるジョブが見つかりません"),
+        Err(e) => {
+            eprintln!("ジョブの再実行登録失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
+
+// 定期タスクごとのスケジュールと直近の実行結果
+#[get("/admin/scheduled-tasks")]
+pub async fn get_scheduled_tasks(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(resp) = require_admin(&req, db.get_ref()).await {
+        return resp;
+    }
+
+    let rows = sqlx::query_as!(
+        ScheduledTaskRun,
+        "
+        SELECT DISTINCT ON (task_name)
+            id,
+            task_name,
+            scheduled_for,
+            status,
+            detail,
+            started_at,
+            finished_at
+        FROM
+            scheduled_task_runs
+        ORDER BY
+            task_name, started_at DESC
+        "
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    let mut last_runs: HashMap<String, ScheduledTaskRun> = match rows {
+        Ok(rows) => rows.into_iter().map(|run| (run.task_name.clone(), run)).collect(),
+        Err(e) => {
+            eprintln!("実行履歴の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let tasks: Vec<ScheduledTaskResponse> = ScheduledTask::ALL
+        .into_iter()
+        .map(|task| {
+            let schedule = task.schedule_expression();
+            let next_run_at = schedule
+                .as_deref()
+                .and_then(|expression| CronSchedule::parse(expression).ok())
+                .and_then(|schedule| schedule.next_after(now));
+
+            ScheduledTaskResponse {
+                task_name: task.name().to_string(),
+                schedule,
+                next_run_at,
+                last_run: last_runs.remove(task.name()),
+            }
+        })
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({ "data": tasks }))
+}
+
+#[get("/admin/scheduled-tasks/runs")]
+pub async fn get_scheduled_task_runs(
+    req: HttpRequest,
+    query: web::Query<ScheduledTaskRunQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if let Err(resp) = require_admin(&req, db.get_ref()).await {
+        return resp;
+    }
+
+    if let Some(task_name) = query.task_name.as_deref() {
+        if ScheduledTask::parse(task_name).is_none() {
+            return HttpResponse::BadRequest().body(format!("不正な task_name です: {}", task_name));
+        }
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_RUN_LIMIT).clamp(1, MAX_RUN_LIMIT);
+
+    let rows = sqlx::query_as!(
+        ScheduledTaskRun,
+        "
+        SELECT
+            id,
+            task_name,
+            scheduled_for,
+            status,
+            detail,
+            started_at,
+            finished_at
+        FROM
+            scheduled_task_runs
+        WHERE
+            $1::TEXT IS NULL OR task_name = $1
+        ORDER BY
+            started_at DESC
+        LIMIT $2
+        ",
+        query.task_name.as_deref(),
+        limit,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match rows {
+        Ok(runs) => HttpResponse::Ok().json(serde_json::json!({ "data": runs })),
+        Err(e) => {
+            eprintln!("実行履歴の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
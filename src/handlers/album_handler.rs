@@ -0,0 +1,727 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::handlers::files_handler::{fetch_cover_thumbnails, load_photos, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use crate::handlers::folder_handler::{has_duplicate_ids, is_unique_violation};
+use crate::message;
+use crate::models::album::{
+    Album, AlbumContents, AlbumCoverRequest, AlbumCreateRequest, AlbumDeleteRequest, AlbumOrderRequest,
+    AlbumPhotosQuery, AlbumPhotosRequest, AlbumUpdateRequest,
+};
+use crate::models::photo::PhotoPage;
+use crate::utils::pagination::PageCursor;
+
+// アルバムの写真は並び順を変えられないため、カーソルの並び順は常にこの値
+const ALBUM_SORT: &str = "position";
+
+fn album_name_conflict() -> HttpResponse {
+    HttpResponse::Conflict().body("同じ名前のアルバムが既に存在します")
+}
+
+fn album_not_found() -> HttpResponse {
+    HttpResponse::NotFound().body("アルバムが存在しないか、権限がありません")
+}
+
+fn validate_album_name(name: &str) -> Result<(), HttpResponse> {
+    if name.trim().is_empty() {
+        return Err(HttpResponse::BadRequest().body("アルバム名を入力してください"));
+    }
+    Ok(())
+}
+
+fn validate_photo_ids(photo_ids: &[i32]) -> Result<(), HttpResponse> {
+    if photo_ids.is_empty() {
+        return Err(HttpResponse::BadRequest().body("写真IDが指定されていません"));
+    }
+    if has_duplicate_ids(photo_ids) {
+        return Err(HttpResponse::BadRequest().body("写真IDが重複しています"));
+    }
+    Ok(())
+}
+
+// 作成日の新しい順に返す。album_id を指定した場合はその1件だけ
+pub(crate) async fn fetch_albums(
+    db: &PgPool,
+    user_id: i32,
+    album_id: Option<i32>,
+) -> Result<Vec<Album>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            a.id,
+            a.name,
+            a.description,
+            COALESCE(a.cover_photo_id, first_photo.photo_id) AS "cover_photo_id?",
+            counts.photo_count AS "photo_count!",
+            a.created_at,
+            a.updated_at
+        FROM
+            albums a
+            LEFT JOIN LATERAL (
+                SELECT COUNT(*) AS photo_count
+                FROM album_photos ap
+                INNER JOIN photos p ON p.id = ap.photo_id
+                WHERE ap.album_id = a.id AND p.trash_id IS NULL
+            ) counts ON TRUE
+            LEFT JOIN LATERAL (
+                SELECT ap.photo_id
+                FROM album_photos ap
+                INNER JOIN photos p ON p.id = ap.photo_id
+                WHERE ap.album_id = a.id AND p.trash_id IS NULL
+                ORDER BY ap.position, ap.photo_id
+                LIMIT 1
+            ) first_photo ON TRUE
+        WHERE
+            a.user_id = $1
+            AND ($2::INTEGER IS NULL OR a.id = $2)
+        ORDER BY
+            a.created_at DESC,
+            a.id DESC
+        "#,
+        user_id,
+        album_id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let cover_ids: Vec<i32> = rows.iter().filter_map(|row| row.cover_photo_id).collect();
+    let thumbnails = fetch_cover_thumbnails(db, &cover_ids).await;
+
+    Ok(rows.into_iter().map(|row| Album {
+        id: row.id,
+        name: row.name,
+        description: row.description,
+        photo_count: row.photo_count,
+        cover_photo_id: row.cover_photo_id,
+        cover_thumbnail: row.cover_photo_id.and_then(|id| thumbnails.get(&id).cloned()),
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+    }).collect())
+}
+
+// 並び順の更新が重ならないよう、アルバムの行をロックする。アルバムが無い場合は false
+async fn lock_album(tx: &mut Transaction<'_, Postgres>, user_id: i32, album_id: i32) -> Result<bool, sqlx::Error> {
+    let album = sqlx::query_scalar!(
+        "SELECT id FROM albums WHERE id = $1 AND user_id = $2 FOR UPDATE",
+        album_id,
+        user_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(album.is_some())
+}
+
+// ゴミ箱にない自分の写真だけが指定されている
+async fn owns_photos(tx: &mut Transaction<'_, Postgres>, user_id: i32, photo_ids: &[i32]) -> Result<bool, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM photos WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL"#,
+        photo_ids,
+        user_id,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(count == photo_ids.len() as i64)
+}
+
+// 写真を指定した順でアルバムの末尾に追加し、追加した枚数を返す。既にアルバムにある写真はそのままにする
+async fn append_album_photos(
+    tx: &mut Transaction<'_, Postgres>,
+    album_id: i32,
+    photo_ids: &[i32],
+) -> Result<u64, sqlx::Error> {
+    let added = sqlx::query!(
+        "
+        INSERT INTO album_photos (album_id, photo_id, position)
+        SELECT
+            $1,
+            o.id,
+            (SELECT COALESCE(MAX(position), 0) FROM album_photos WHERE album_id = $1) + o.n::INTEGER
+        FROM
+            UNNEST($2::INTEGER[]) WITH ORDINALITY AS o(id, n)
+        ON CONFLICT (album_id, photo_id) DO NOTHING
+        ",
+        album_id,
+        photo_ids,
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    touch_album(tx, album_id).await?;
+
+    Ok(added)
+}
+
+async fn touch_album(tx: &mut Transaction<'_, Postgres>, album_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE albums SET updated_at = now() WHERE id = $1", album_id)
+        .execute(&mut **tx)
+        .await
+        .map(|_| ())
+}
+
+#[get("/albums")]
+pub async fn get_albums(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    match fetch_albums(db.get_ref(), claims.user_id, None).await {
+        Ok(albums) => HttpResponse::Ok().json(serde_json::json!({ "data": albums })),
+        Err(e) => {
+            eprintln!("アルバム一覧の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("Error fetching albums")
+        }
+    }
+}
+
+// アルバムの情報と、アルバム内の並び順で写真を1ページ分返す
+#[get("/albums/{album_id}")]
+pub async fn get_album_contents(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<AlbumPhotosQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return HttpResponse::BadRequest().body(format!("limit は1〜{}で指定してください", MAX_PAGE_SIZE));
+    }
+
+    let cursor = match query.cursor.as_deref().map(PageCursor::decode).transpose() {
+        Ok(Some(cursor)) if cursor.sort != ALBUM_SORT => return HttpResponse::BadRequest().body("カーソルと並び順が一致しません"),
+        Ok(cursor) => cursor,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let cursor_key = match cursor.map(|c| i32::try_from(c.num_key).map(|position| (position, c.id))).transpose() {
+        Ok(key) => key,
+        Err(_) => return HttpResponse::BadRequest().body("カーソルが不正です"),
+    };
+
+    let album = match fetch_albums(db.get_ref(), claims.user_id, Some(path.into_inner())).await {
+        Ok(mut rows) if !rows.is_empty() => rows.remove(0),
+        Ok(_) => return album_not_found(),
+        Err(e) => {
+            eprintln!("アルバムの取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching album");
+        }
+    };
+
+    let rows = sqlx::query!(
+        "
+        SELECT
+            ap.photo_id,
+            ap.position
+        FROM
+            album_photos ap
+            INNER JOIN photos p ON p.id = ap.photo_id
+        WHERE
+            ap.album_id = $1
+            AND p.trash_id IS NULL
+            AND ($2::INTEGER IS NULL OR (ap.position, ap.photo_id) > ($2, $3))
+        ORDER BY
+            ap.position,
+            ap.photo_id
+        LIMIT $4
+        ",
+        album.id,
+        cursor_key.map(|(position, _)| position),
+        cursor_key.map(|(_, id)| id),
+        limit + 1,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    let mut keys: Vec<(i32, i32)> = match rows {
+        Ok(rows) => rows.into_iter().map(|row| (row.photo_id, row.position)).collect(),
+        Err(e) => {
+            eprintln!("アルバムの写真の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching photos");
+        }
+    };
+
+    let has_more = keys.len() as i64 > limit;
+    keys.truncate(limit as usize);
+
+    let next_cursor = match keys.last() {
+        Some(&(id, position)) if has_more => Some(PageCursor {
+            sort: ALBUM_SORT.to_string(),
+            descending: false,
+            num_key: position as i64,
+            text_key: String::new(),
+            id,
+        }.encode()),
+        _ => None,
+    };
+
+    let photo_ids: Vec<i32> = keys.into_iter().map(|(id, _)| id).collect();
+    let data = match load_photos(db.get_ref(), &photo_ids).await {
+        Ok(photos) => photos,
+        Err(e) => {
+            eprintln!("写真の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching photos");
+        }
+    };
+
+    let total_count = album.photo_count;
+    HttpResponse::Ok().json(AlbumContents {
+        album,
+        photos: PhotoPage { data, next_cursor, total_count },
+    })
+}
+
+#[post("/albums")]
+pub async fn create_album(
+    req: HttpRequest,
+    payload: web::Json<AlbumCreateRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Err(resp) = validate_album_name(&payload.name) {
+        return resp;
+    }
+    if !payload.photo_ids.is_empty() {
+        if let Err(resp) = validate_photo_ids(&payload.photo_ids) {
+            return resp;
+        }
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if !payload.photo_ids.is_empty() {
+        match owns_photos(&mut tx, claims.user_id, &payload.photo_ids).await {
+            Ok(true) => {}
+            Ok(false) => return HttpResponse::NotFound().body("写真が存在しないか、権限がありません"),
+            Err(e) => {
+                eprintln!("写真確認失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+            }
+        }
+    }
+
+    let album_id = sqlx::query_scalar!(
+        "INSERT INTO albums (user_id, name, description) VALUES ($1, $2, NULLIF($3, '')) RETURNING id",
+        claims.user_id,
+        payload.name.trim(),
+        payload.description.as_deref().map(str::trim),
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    let result = match album_id {
+        Ok(album_id) if payload.photo_ids.is_empty() => Ok(album_id),
+        Ok(album_id) => append_album_photos(&mut tx, album_id, &payload.photo_ids).await.map(|_| album_id),
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(album_id) => tx.commit().await.map(|_| album_id),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(id) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "アルバムを作成しました。",
+            "id": id,
+        })),
+        Err(e) if is_unique_violation(&e) => album_name_conflict(),
+        Err(e) => {
+            eprintln!("アルバム作成エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの作成に失敗しました")
+        }
+    }
+}
+
+// 名前・説明の変更
+#[put("/albums")]
+pub async fn update_album(
+    req: HttpRequest,
+    payload: web::Json<AlbumUpdateRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Some(name) = payload.name.as_deref() {
+        if let Err(resp) = validate_album_name(name) {
+            return resp;
+        }
+    }
+
+    let result = sqlx::query_scalar!(
+        "UPDATE albums
+        SET
+            name = COALESCE($3, name),
+            description = CASE WHEN $4 THEN NULLIF($5, '') ELSE description END,
+            updated_at = now()
+        WHERE
+            id = $1 AND user_id = $2
+        RETURNING
+            id",
+        payload.id,
+        claims.user_id,
+        payload.name.as_deref().map(str::trim),
+        payload.description.is_some(),
+        payload.description.as_deref().map(str::trim),
+    )
+    .fetch_optional(db.get_ref())
+    .await;
+
+    match result {
+        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Updated(message::FileType::Album).message(),
+        })),
+        Ok(None) => album_not_found(),
+        Err(e) if is_unique_violation(&e) => album_name_conflict(),
+        Err(e) => {
+            eprintln!("アルバム更新エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの更新に失敗しました")
+        }
+    }
+}
+
+// アルバムを削除しても写真は削除しない
+#[delete("/albums")]
+pub async fn delete_album(
+    req: HttpRequest,
+    payload: web::Json<AlbumDeleteRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if payload.ids.is_empty() {
+        return HttpResponse::BadRequest().body("削除するアルバムIDが指定されていません");
+    }
+
+    let result = sqlx::query_scalar!(
+        "DELETE FROM albums WHERE id = ANY($1) AND user_id = $2 RETURNING id",
+        &payload.ids,
+        claims.user_id,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match result {
+        Ok(ids) if ids.is_empty() => album_not_found(),
+        Ok(ids) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Deleted(message::FileType::Album).message(),
+            "deleted_ids": ids,
+        })),
+        Err(e) => {
+            eprintln!("アルバム削除エラー: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::DeleteFailed(message::FileType::Album).message())
+        }
+    }
+}
+
+// 写真をアルバムの末尾に追加する。写真のフォルダーは変わらない
+#[post("/albums/photos")]
+pub async fn add_album_photos(
+    req: HttpRequest,
+    payload: web::Json<AlbumPhotosRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Err(resp) = validate_photo_ids(&payload.photo_ids) {
+        return resp;
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    match lock_album(&mut tx, claims.user_id, payload.album_id).await {
+        Ok(true) => {}
+        Ok(false) => return album_not_found(),
+        Err(e) => {
+            eprintln!("アルバム確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    match owns_photos(&mut tx, claims.user_id, &payload.photo_ids).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::NotFound().body("写真が存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let result = match append_album_photos(&mut tx, payload.album_id, &payload.photo_ids).await {
+        Ok(added) => tx.commit().await.map(|_| added),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(added) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}枚の写真をアルバムに追加しました", added),
+            "added_count": added,
+        })),
+        Err(e) => {
+            eprintln!("アルバムへの追加失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの更新に失敗しました")
+        }
+    }
+}
+
+// 写真をアルバムから外す。写真そのものは削除しない
+#[delete("/albums/photos")]
+pub async fn remove_album_photos(
+    req: HttpRequest,
+    payload: web::Json<AlbumPhotosRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Err(resp) = validate_photo_ids(&payload.photo_ids) {
+        return resp;
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    match lock_album(&mut tx, claims.user_id, payload.album_id).await {
+        Ok(true) => {}
+        Ok(false) => return album_not_found(),
+        Err(e) => {
+            eprintln!("アルバム確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let removed = sqlx::query!(
+        "DELETE FROM album_photos WHERE album_id = $1 AND photo_id = ANY($2)",
+        payload.album_id,
+        &payload.photo_ids,
+    )
+    .execute(&mut *tx)
+    .await
+    .map(|r| r.rows_affected());
+
+    // 外した写真が表紙だった場合は先頭の写真に戻す
+    let result = match removed {
+        Ok(removed) => sqlx::query!(
+            "UPDATE albums SET cover_photo_id = NULL WHERE id = $1 AND cover_photo_id = ANY($2)",
+            payload.album_id,
+            &payload.photo_ids,
+        )
+        .execute(&mut *tx)
+        .await
+        .map(|_| removed),
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(removed) => touch_album(&mut tx, payload.album_id).await.map(|_| removed),
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(removed) => tx.commit().await.map(|_| removed),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(removed) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}枚の写真をアルバムから外しました", removed),
+            "removed_count": removed,
+        })),
+        Err(e) => {
+            eprintln!("アルバムから外す処理の失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの更新に失敗しました")
+        }
+    }
+}
+
+// アルバム内の写真を指定した順に並べる
+#[put("/albums/order")]
+pub async fn reorder_album_photos(
+    req: HttpRequest,
+    payload: web::Json<AlbumOrderRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Err(resp) = validate_photo_ids(&payload.ids) {
+        return resp;
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    match lock_album(&mut tx, claims.user_id, payload.album_id).await {
+        Ok(true) => {}
+        Ok(false) => return album_not_found(),
+        Err(e) => {
+            eprintln!("アルバム確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let in_album = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM album_photos WHERE album_id = $1 AND photo_id = ANY($2)"#,
+        payload.album_id,
+        &payload.ids,
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match in_album {
+        Ok(count) if count == payload.ids.len() as i64 => {}
+        Ok(_) => return HttpResponse::BadRequest().body("アルバムにない写真が含まれています"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    // 指定した写真を先頭に、残りの写真を今の順番のままその後ろに並べ直す
+    let result = sqlx::query!(
+        "
+        UPDATE album_photos ap
+        SET position = r.position
+        FROM (
+            SELECT
+                a.photo_id,
+                ROW_NUMBER() OVER (ORDER BY o.n NULLS LAST, a.position, a.photo_id)::INTEGER AS position
+            FROM
+                album_photos a
+                LEFT JOIN UNNEST($2::INTEGER[]) WITH ORDINALITY AS o(id, n) ON o.id = a.photo_id
+            WHERE
+                a.album_id = $1
+        ) r
+        WHERE ap.album_id = $1 AND ap.photo_id = r.photo_id
+        ",
+        payload.album_id,
+        &payload.ids,
+    )
+    .execute(&mut *tx)
+    .await;
+
+    let result = match result {
+        Ok(_) => touch_album(&mut tx, payload.album_id).await,
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(_) => tx.commit().await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}枚の写真を並べ替えました", payload.ids.len()),
+        })),
+        Err(e) => {
+            eprintln!("アルバムの並べ替え失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの更新に失敗しました")
+        }
+    }
+}
+
+// 表紙の写真を指定する。アルバムにある写真のみ指定できる
+#[put("/albums/cover")]
+pub async fn set_album_cover(
+    req: HttpRequest,
+    payload: web::Json<AlbumCoverRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Some(photo_id) = payload.photo_id {
+        let in_album = sqlx::query_scalar!(
+            "SELECT ap.photo_id
+            FROM album_photos ap
+            INNER JOIN albums a ON a.id = ap.album_id
+            INNER JOIN photos p ON p.id = ap.photo_id
+            WHERE ap.album_id = $1 AND ap.photo_id = $2 AND a.user_id = $3 AND p.trash_id IS NULL",
+            payload.album_id,
+            photo_id,
+            claims.user_id,
+        )
+        .fetch_optional(db.get_ref())
+        .await;
+
+        match in_album {
+            Ok(Some(_)) => {}
+            Ok(None) => return HttpResponse::BadRequest().body("アルバムにある写真を指定してください"),
+            Err(e) => {
+                eprintln!("写真確認失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+            }
+        }
+    }
+
+    let result = sqlx::query_scalar!(
+        "UPDATE albums SET cover_photo_id = $1, updated_at = now() WHERE id = $2 AND user_id = $3 RETURNING id",
+        payload.photo_id,
+        payload.album_id,
+        claims.user_id,
+    )
+    .fetch_optional(db.get_ref())
+    .await;
+
+    match result {
+        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Updated(message::FileType::Album).message(),
+        })),
+        Ok(None) => album_not_found(),
+        Err(e) => {
+            eprintln!("表紙の更新失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("アルバムの更新に失敗しました")
+        }
+    }
+}
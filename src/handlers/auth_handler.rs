@@ -3,7 +3,7 @@ use actix_web_httpauth::extractors::bearer::BearerAuth;
 use jsonwebtoken::{decode, Algorithm, DecodingKey, TokenData, Validation};
 use crate::models::user::Claims;
 
-const SECRET: &[u8] = b"secret";
+pub(crate) const SECRET: &[u8] = b"secret";
 
 pub async fn validate_jwt(
     req: ServiceRequest,
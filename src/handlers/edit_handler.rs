@@ -0,0 +1,254 @@
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::message;
+use crate::models::edit::{EditAppendRequest, EditStackResponse};
+use crate::utils::photo_edit::{edited_dimensions, parse_edits};
+use crate::workers::job::Job;
+use crate::workers::queue;
+
+// 編集の変更後に呼ぶ。編集後のサイズを写真に反映し、バージョンを進める
+async fn refresh_edit_stack(
+    tx: &mut Transaction<'_, Postgres>,
+    photo_id: i32,
+) -> Result<EditStackResponse, sqlx::Error> {
+    let operations = sqlx::query_scalar!(
+        "SELECT operation FROM photo_edits WHERE photo_id = $1 ORDER BY position",
+        photo_id
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let edits = parse_edits(operations);
+
+    let photo = sqlx::query!(
+        "SELECT original_width, original_height, width, height FROM photos WHERE id = $1",
+        photo_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    // 元画像のサイズが未確定（後処理待ち）の場合は後処理側で計算する
+    let (width, height) = if photo.original_width > 0 && photo.original_height > 0 {
+        let (w, h) = edited_dimensions(photo.original_width as u32, photo.original_height as u32, &edits);
+        (w as i32, h as i32)
+    } else {
+        (photo.width, photo.height)
+    };
+
+    sqlx::query!(
+        "UPDATE photos
+        SET width = $1, height = $2, edit_version = edit_version + 1
+        WHERE id = $3",
+        width,
+        height,
+        photo_id,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(EditStackResponse {
+        photo_id,
+        edits,
+        width,
+        height,
+        original_width: photo.original_width,
+        original_height: photo.original_height,
+    })
+}
+
+async fn lock_own_photo(
+    tx: &mut Transaction<'_, Postgres>,
+    photo_id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query_scalar!(
+        "SELECT id FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL FOR UPDATE",
+        photo_id,
+        user_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(row.is_some())
+}
+
+enum EditChange {
+    Append(String),
+    Undo,
+    Reset,
+}
+
+async fn change_edit_stack(
+    req: HttpRequest,
+    photo_id: i32,
+    db_pool: &PgPool,
+    change: EditChange,
+) -> HttpResponse {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message()),
+    };
+
+    match lock_own_photo(&mut tx, photo_id, claims.user_id).await {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let result = match change {
+        EditChange::Append(operation) => sqlx::query!(
+            "INSERT INTO photo_edits (photo_id, position, operation)
+            SELECT $1, COALESCE(MAX(position), 0) + 1, $2
+            FROM photo_edits
+            WHERE photo_id = $1",
+            photo_id,
+            operation,
+        )
+        .execute(&mut *tx)
+        .await,
+        EditChange::Undo => sqlx::query!(
+            "DELETE FROM photo_edits
+            WHERE photo_id = $1
+            AND position = (SELECT MAX(position) FROM photo_edits WHERE photo_id = $1)",
+            photo_id,
+        )
+        .execute(&mut *tx)
+        .await,
+        EditChange::Reset => sqlx::query!(
+            "DELETE FROM photo_edits WHERE photo_id = $1",
+            photo_id,
+        )
+        .execute(&mut *tx)
+        .await,
+    };
+
+    if let Err(e) = result {
+        eprintln!("編集の更新失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body("編集の更新に失敗しました");
+    }
+
+    let stack = match refresh_edit_stack(&mut tx, photo_id).await {
+        Ok(stack) => stack,
+        Err(e) => {
+            eprintln!("編集後サイズの更新失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("編集の更新に失敗しました");
+        }
+    };
+
+    // 元画像はそのままで、レンディションを編集後の内容で作り直す
+    if let Err(e) = queue::enqueue(&mut *tx, &Job::ProcessPhoto { photo_id }).await {
+        eprintln!("ジョブの登録失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("トランザクションコミット失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    HttpResponse::Ok().json(stack)
+}
+
+#[get("/photos/{id}/edits")]
+pub async fn get_photo_edits(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_id = path.into_inner();
+
+    let photo = sqlx::query!(
+        "SELECT width, height, original_width, original_height
+        FROM photos
+        WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    let photo = match photo {
+        Ok(Some(p)) => p,
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let operations = sqlx::query_scalar!(
+        "SELECT operation FROM photo_edits WHERE photo_id = $1 ORDER BY position",
+        photo_id
+    )
+    .fetch_all(db_pool.get_ref())
+    .await;
+
+    match operations {
+        Ok(operations) => HttpResponse::Ok().json(EditStackResponse {
+            photo_id,
+            edits: parse_edits(operations),
+            width: photo.width,
+            height: photo.height,
+            original_width: photo.original_width,
+            original_height: photo.original_height,
+        }),
+        Err(e) => {
+            eprintln!("編集取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
+
+#[post("/photos/{id}/edits")]
+pub async fn append_photo_edit(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<PgPool>,
+    payload: web::Json<EditAppendRequest>,
+) -> impl Responder {
+    if let Err(e) = payload.operation.validate() {
+        return HttpResponse::BadRequest().json(serde_json::json!({ "message": e }));
+    }
+
+    let operation = match serde_json::to_string(&payload.operation) {
+        Ok(o) => o,
+        Err(_) => return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message()),
+    };
+
+    change_edit_stack(req, path.into_inner(), db_pool.get_ref(), EditChange::Append(operation)).await
+}
+
+// 直前の編集を取り消す
+#[delete("/photos/{id}/edits/last")]
+pub async fn undo_photo_edit(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    change_edit_stack(req, path.into_inner(), db_pool.get_ref(), EditChange::Undo).await
+}
+
+// すべての編集を取り消して元画像の状態に戻す
+#[delete("/photos/{id}/edits")]
+pub async fn reset_photo_edits(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<PgPool>,
+) -> impl Responder {
+    change_edit_stack(req, path.into_inner(), db_pool.get_ref(), EditChange::Reset).await
+}
@@ -1,247 +1,405 @@
 use std::collections::HashMap;
 use serde::Serialize;
-use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
-use sqlx::PgPool;
-use crate::models::{tag::TagResponse, Breadcrumb, Folder, Photo};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::{PgPool, Postgres, Transaction};
+use crate::models::{photo::{PhotoListQuery, PhotoPage, SearchHighlight}, smart_folder::SmartFolder, tag::TagResponse, Breadcrumb, Folder, Photo, PhotoMetadata, Rendition};
+use crate::utils::metadata::{format_utc_offset, utc_offset};
 use crate::handlers::auth_handler::extract_user_from_jwt;
-use bigdecimal::ToPrimitive;
+use crate::handlers::smart_folder_handler::fetch_smart_folders;
+use crate::handlers::folder_handler::{lock_folder_tree, subtree_folder_ids, validate_folder_name};
+use crate::utils::pagination::PageCursor;
+use crate::utils::tag_query;
+use crate::utils::search_highlight::{highlight, SNIPPET_LENGTH};
+
+pub(crate) const DEFAULT_PAGE_SIZE: i64 = 100;
+pub(crate) const MAX_PAGE_SIZE: i64 = 500;
 
 #[derive(Serialize, Debug)]
-struct FolderContents {
+pub(crate) struct FolderContents {
     folder: Folder,
     photos: Vec<Photo>,
+    // 写真の次のページ。子フォルダーとパンくずリストは毎回すべて返す
+    next_cursor: Option<String>,
+    total_count: i64,
     child_folders: Vec<Folder>,
     breadcrumbs: Vec<Breadcrumb>,
+    // ルートフォルダーを開いた場合のみ。子フォルダーと並べて表示する
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    smart_folders: Vec<SmartFolder>,
+    // スマートフォルダーを開いた場合のみ。folder は検索結果をまとめた仮のフォルダーになる
+    #[serde(skip_serializing_if = "Option::is_none")]
+    smart_folder: Option<SmartFolder>,
 }
 
 #[get("/files/{folder_id}")]
 pub async fn get_folder_contents(
     req: HttpRequest,
     path: web::Path<i32>,
+    query: web::Query<PhotoListQuery>,
+    db: web::Data<PgPool>
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let params = match photo_list_params(&query) {
+        Ok(p) => p,
+        Err(resp) => return resp,
+    };
+
+    match load_folder_contents(db.get_ref(), claims.user_id, path.into_inner(), &query, &params).await {
+        Ok(contents) => HttpResponse::Ok().json(contents),
+        Err(resp) => resp,
+    }
+}
+
+// "/2024/trips/kyoto" のような名前のパスでフォルダーを開く（ルートフォルダーからの相対パス）
+#[get("/files/path/{path:.*}")]
+pub async fn get_folder_contents_by_path(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PhotoListQuery>,
+    db: web::Data<PgPool>
+) -> impl Responder {
+    folder_contents_by_path(req, path.into_inner(), query.into_inner(), db.get_ref(), false).await
+}
+
+// 途中のフォルダーが無ければ作成してから開く（mkdir -p と同じ）
+#[post("/files/path/{path:.*}")]
+pub async fn create_folder_by_path(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PhotoListQuery>,
     db: web::Data<PgPool>
 ) -> impl Responder {
+    folder_contents_by_path(req, path.into_inner(), query.into_inner(), db.get_ref(), true).await
+}
+
+async fn folder_contents_by_path(
+    req: HttpRequest,
+    path: String,
+    query: PhotoListQuery,
+    db: &PgPool,
+    create: bool,
+) -> HttpResponse {
     let claims = match extract_user_from_jwt(&req) {
         Ok(c) => c,
         Err(resp) => return resp,
     };
 
-    let folder_id = path.into_inner();
+    let params = match photo_list_params(&query) {
+        Ok(p) => p,
+        Err(resp) => return resp,
+    };
+
+    let names = split_folder_path(&path);
+    if create {
+        if let Some(resp) = names.iter().find_map(|name| validate_folder_name(name).err()) {
+            return resp;
+        }
+    }
+
+    let folder_id = match resolve_folder_path(db, claims.user_id, claims.root_folder, &names, create).await {
+        Ok(Some(id)) => id,
+        Ok(None) => return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("フォルダのパス解決失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error resolving folder path");
+        }
+    };
+
+    match load_folder_contents(db, claims.user_id, folder_id, &query, &params).await {
+        Ok(contents) => HttpResponse::Ok().json(contents),
+        Err(resp) => resp,
+    }
+}
+
+// 空の要素（先頭・末尾や連続した「/」）は無視する
+fn split_folder_path(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// ルートフォルダーから名前をたどり、最後のフォルダーのIDを返す。create の場合は足りないフォルダーを作成する
+async fn resolve_folder_path(
+    db: &PgPool,
+    user_id: i32,
+    root_folder: i32,
+    names: &[String],
+    create: bool,
+) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    if create {
+        lock_folder_tree(&mut tx, user_id).await?;
+    }
+
+    let folder_id = walk_folder_path(&mut tx, user_id, root_folder, names, create).await?;
+    tx.commit().await?;
+
+    Ok(folder_id)
+}
+
+// resolve_folder_path のトランザクション内の処理。ゴミ箱のフォルダーはたどらない
+// create の場合は呼び出し側でフォルダー構成をロックしておく
+pub(crate) async fn walk_folder_path(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    root_folder: i32,
+    names: &[String],
+    create: bool,
+) -> Result<Option<i32>, sqlx::Error> {
+    // たどれた一番深いフォルダー
+    let deepest = sqlx::query!(
+        r#"
+        WITH RECURSIVE walk AS (
+            SELECT id, 0 AS depth
+            FROM folders
+            WHERE id = $1 AND user_id = $2 AND trash_id IS NULL
+            UNION ALL
+            SELECT f.id, w.depth + 1
+            FROM walk w
+            INNER JOIN folders f ON f.parent_id = w.id AND f.name = ($3::TEXT[])[w.depth + 1] AND f.trash_id IS NULL
+            WHERE w.depth < cardinality($3::TEXT[])
+        )
+        SELECT id AS "id!", depth AS "depth!"
+        FROM walk
+        ORDER BY depth DESC
+        LIMIT 1
+        "#,
+        root_folder,
+        user_id,
+        names,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let Some(deepest) = deepest else {
+        return Ok(None);
+    };
+
+    let mut folder_id = deepest.id;
+    let found = deepest.depth as usize;
+
+    if found == names.len() {
+        return Ok(Some(folder_id));
+    }
+    if !create {
+        return Ok(None);
+    }
+
+    for name in &names[found..] {
+        folder_id = sqlx::query_scalar!(
+            "INSERT INTO folders (user_id, name, parent_id) VALUES ($1, $2, $3) RETURNING id",
+            user_id,
+            name,
+            folder_id,
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+    }
+
+    Ok(Some(folder_id))
+}
 
+// フォルダー本体・子フォルダー（配下の写真の集計つき）・写真・パンくずリストをまとめて取得する
+async fn load_folder_contents(
+    db: &PgPool,
+    user_id: i32,
+    folder_id: i32,
+    query: &PhotoListQuery,
+    params: &PhotoListParams<'_>,
+) -> Result<FolderContents, HttpResponse> {
     let folder_rows = sqlx::query!(
         "SELECT
             id,
             user_id,
             name,
             description,
-            parent_id
+            parent_id,
+            cover_photo_id,
+            sort_position
         FROM
             folders
         WHERE
             id = $1 AND
-            user_id = $2",
+            user_id = $2 AND
+            trash_id IS NULL",
         folder_id,
-        claims.user_id,
+        user_id,
     )
-    .fetch_all(db.get_ref())
+    .fetch_all(db)
     .await;
 
-    let folder = match folder_rows {
+    let mut folder = match folder_rows {
         Ok(rows) if !rows.is_empty() => Folder {
-            id: rows[0].id,
+            id: Some(rows[0].id),
             user_id: rows[0].user_id,
             name: rows[0].name.clone(),
             description: rows[0].description.clone(),
             parent_id: rows[0].parent_id,
             total_photo_count: None,
             total_photo_size: None,
+            cover_photo_id: rows[0].cover_photo_id,
+            cover_thumbnail: None,
+            sort_position: rows[0].sort_position,
         },
-        Ok(_) => return HttpResponse::NotFound().body("Folder not found"),
-        Err(_) => return HttpResponse::InternalServerError().body("Error fetching folder"),
+        Ok(_) => return Err(HttpResponse::NotFound().body("Folder not found")),
+        Err(_) => return Err(HttpResponse::InternalServerError().body("Error fetching folder")),
     };
 
-    // 子フォルダー
+    // 子フォルダーと、その配下すべての写真枚数・合計サイズを1回のクエリで集計する
+    // 配下のフォルダーにはそれぞれが属する子フォルダーのIDを top_id として持たせる
     let child_folder_rows = sqlx::query!(
-        "SELECT
-            id,
-            user_id,
-            name,
-            description,
-            parent_id
+        r#"
+        WITH RECURSIVE subtree AS (
+            SELECT id, id AS top_id
+            FROM folders
+            WHERE parent_id = $1 AND user_id = $2 AND trash_id IS NULL
+            UNION ALL
+            SELECT f.id, s.top_id
+            FROM folders f
+            INNER JOIN subtree s ON f.parent_id = s.id
+            WHERE f.trash_id IS NULL
+        ),
+        tops AS (
+            SELECT id, top_id FROM subtree
+            UNION ALL
+            SELECT $1, $1
+        )
+        SELECT
+            f.id,
+            f.user_id,
+            f.name,
+            f.description,
+            f.parent_id,
+            f.cover_photo_id,
+            f.sort_position,
+            COUNT(p.id) AS "total_photo_count!",
+            COALESCE(SUM(p.size_in_bytes), 0)::BIGINT AS "total_photo_size!",
+            MAX(p.uploaded_at) AS newest_uploaded_at,
+            (ARRAY_AGG(p.id ORDER BY p.uploaded_at DESC, p.id DESC) FILTER (WHERE p.id IS NOT NULL))[1] AS newest_photo_id
         FROM
-            folders
-        WHERE
-            parent_id = $1 AND
-            user_id = $2",
+            tops t
+        INNER JOIN
+            folders f ON f.id = t.top_id
+        LEFT JOIN
+            photos p ON p.folder_id = t.id AND p.user_id = $2 AND p.trash_id IS NULL
+        GROUP BY
+            f.id
+        ORDER BY
+            f.sort_position NULLS LAST,
+            f.name,
+            f.id
+        "#,
         folder_id,
-        claims.user_id,
+        user_id,
     )
-    .fetch_all(db.get_ref())
+    .fetch_all(db)
     .await;
 
+    let child_folder_rows = match child_folder_rows {
+        Ok(rows) => rows,
+        Err(_) => return Err(HttpResponse::InternalServerError().body("Error counting photos")),
+    };
+
     let mut child_folders: Vec<Folder> = Vec::new();
+    let mut total_photo_count = 0;
+    let mut total_photo_size = 0;
+    let mut newest = None;
 
-    for row in child_folder_rows.unwrap_or_default() {
-        let folder_ids_result = sqlx::query!(
-            "
-            WITH RECURSIVE all_folders AS (
-                SELECT id FROM folders WHERE id = $1
-                UNION ALL
-                SELECT f.id
-                FROM folders f
-                INNER JOIN all_folders af ON f.parent_id = af.id
-            )
-            SELECT id FROM all_folders
-            ",
-            row.id
-        )
-        .fetch_all(db.get_ref())
-        .await;
+    for row in child_folder_rows {
+        total_photo_count += row.total_photo_count as usize;
+        total_photo_size += row.total_photo_size;
 
-        let folder_ids: Vec<i32> = match folder_ids_result {
-            Ok(rows) => rows.into_iter().map(|r| r.id.unwrap()).collect(),
-            Err(_) => return HttpResponse::InternalServerError().body("Error fetching folder hierarchy"),
-        };
-
-        // フォルダID群に属する写真枚数を取得
-        let photo_count_row = sqlx::query!(
-            "
-            SELECT COUNT(*) as count
-            FROM photos
-            WHERE folder_id = ANY($1) AND user_id = $2
-            ",
-            &folder_ids,
-            claims.user_id
-        )
-        .fetch_one(db.get_ref())
-        .await;
-
-        let total_photo_count = match photo_count_row {
-            Ok(row) => row.count.unwrap_or(0) as usize,
-            Err(_) => return HttpResponse::InternalServerError().body("Error counting photos"),
-        };
-
-        let photo_size_sum_row = sqlx::query!(
-            "
-            SELECT COALESCE(SUM(size_in_bytes), 0) as sum_size
-            FROM photos
-            WHERE folder_id = ANY($1) AND user_id = $2
-            ",
-            &folder_ids,
-            claims.user_id
-        )
-        .fetch_one(db.get_ref())
-        .await;
+        if let (Some(uploaded_at), Some(photo_id)) = (row.newest_uploaded_at, row.newest_photo_id) {
+            if newest.is_none_or(|(newest_at, _)| uploaded_at > newest_at) {
+                newest = Some((uploaded_at, photo_id));
+            }
+        }
 
-        // total_photo_size を i64 に変換
-        let total_photo_size = match photo_size_sum_row {
-            Ok(row) => row.sum_size.and_then(|bd| bd.to_i64()).unwrap_or(0),
-            Err(_) => return HttpResponse::InternalServerError().body("Error summing photo sizes"),
-        };
+        // 現在のフォルダー直下の写真は合計にだけ含める
+        if row.id == folder_id {
+            continue;
+        }
 
         child_folders.push(Folder {
-            id: row.id,
+            id: Some(row.id),
             user_id: row.user_id,
-            name: row.name.clone(),
-            description: row.description.clone(),
+            name: row.name,
+            description: row.description,
             parent_id: row.parent_id,
-            total_photo_count: Some(total_photo_count),
-            total_photo_size: Some(total_photo_size),
+            total_photo_count: Some(row.total_photo_count as usize),
+            total_photo_size: Some(row.total_photo_size),
+            cover_photo_id: row.cover_photo_id.or(row.newest_photo_id),
+            cover_thumbnail: None,
+            sort_position: row.sort_position,
         });
     }
 
-    // 写真データ
-    let photo_rows = sqlx::query!(
-        "SELECT
-            photos.id,
-            photos.user_id,
-            photos.name,
-            photos.description,
-            photos.image_path,
-            photos.uploaded_at,
-            photos.size_in_bytes,
-            photos.width,
-            photos.height,
-            folders.name AS folder_name
-        FROM
-            photos
-        LEFT JOIN
-            folders ON photos.folder_id = folders.id
-        WHERE
-            photos.folder_id = $1 AND
-            photos.user_id = $2",
-        folder_id,
-        claims.user_id,
-    )
-    .fetch_all(db.get_ref())
-    .await;
+    folder.total_photo_count = Some(total_photo_count);
+    folder.total_photo_size = Some(total_photo_size);
+    folder.cover_photo_id = folder.cover_photo_id.or(newest.map(|(_, photo_id)| photo_id));
 
-    let rows = match photo_rows {
-        Ok(rows) => rows,
-        Err(_) => return HttpResponse::InternalServerError().body("Error fetching photos"),
-    };
+    let cover_ids: Vec<i32> = std::iter::once(&folder)
+        .chain(child_folders.iter())
+        .filter_map(|f| f.cover_photo_id)
+        .collect();
+    let thumbnails = fetch_cover_thumbnails(db, &cover_ids).await;
 
-    let photo_ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    for f in std::iter::once(&mut folder).chain(child_folders.iter_mut()) {
+        f.cover_thumbnail = f.cover_photo_id.and_then(|id| thumbnails.get(&id).cloned());
+    }
 
-    // photo_tags テーブルと tags テーブルを結合して取得
-    let photo_tag_rows = sqlx::query!(
-        "
-        SELECT
-            photo_tag_relations.photo_id,
-            tags.id AS tag_id,
-            tags.tag
-        FROM
-            photo_tag_relations
-        INNER JOIN
-            tags ON photo_tag_relations.tag_id = tags.id
-        WHERE
-            photo_tag_relations.photo_id = ANY($1)
-        ",
-        &photo_ids
-    )
-    .fetch_all(db.get_ref())
-    .await;
+    // 写真データ
+    let page = fetch_photo_page(db, user_id, Some(&[folder_id]), query, params).await?;
 
-    let mut tag_map: HashMap<i32, Vec<TagResponse>> = HashMap::new();
+    // パンくずリスト
+    let breadcrumbs = match fetch_breadcrumbs(db, folder_id).await {
+        Ok(breadcrumbs) => breadcrumbs,
+        Err(_) => return Err(HttpResponse::InternalServerError().body("Error fetching breadcrumbs")),
+    };
 
-    if let Ok(tag_rows) = photo_tag_rows {
-        for tag_row in tag_rows {
-            tag_map
-                .entry(tag_row.photo_id)
-                .or_default()
-                .push(TagResponse {
-                    id: tag_row.tag_id,
-                    tag: tag_row.tag,
-                });
+    let smart_folders = if folder.parent_id.is_none() {
+        match fetch_smart_folders(db, user_id, None).await {
+            Ok(smart_folders) => smart_folders,
+            Err(e) => {
+                eprintln!("スマートフォルダー一覧の取得失敗: {:?}", e);
+                return Err(HttpResponse::InternalServerError().body("Error fetching smart folders"));
+            }
         }
-    }
-
+    } else {
+        Vec::new()
+    };
 
-    let photos: Vec<Photo> = rows.into_iter().map(|row| Photo {
-        id: row.id,
-        user_id: row.user_id,
-        name: row.name,
-        description: row.description,
-        image_path: row.image_path,
-        uploaded_at: row.uploaded_at,
-        folder_id: folder_id,
-        size_in_bytes: row.size_in_bytes,
-        folder_name: row.folder_name,
-        tags: tag_map.remove(&row.id).unwrap_or_default(),
-        width: row.width,
-        height: row.height,
-    }).collect();
+    Ok(FolderContents {
+        folder,
+        photos: page.data,
+        next_cursor: page.next_cursor,
+        total_count: page.total_count,
+        child_folders,
+        breadcrumbs,
+        smart_folders,
+        smart_folder: None,
+    })
+}
 
-    // パンくずリスト
-    let breadcrumb_rows = sqlx::query!(
+// ルートフォルダーから folder_id までのパンくずリスト
+// フォルダーを移動すると ID の大小と階層が一致しなくなるため、たどった深さで並べる
+pub(crate) async fn fetch_breadcrumbs(db: &PgPool, folder_id: i32) -> Result<Vec<Breadcrumb>, sqlx::Error> {
+    let rows = sqlx::query!(
         "
         WITH RECURSIVE breadcrumb AS (
-            SELECT id, name, parent_id
+            SELECT id, name, parent_id, 0 AS depth
             FROM folders
             WHERE id = $1
 
             UNION ALL
 
-            SELECT f.id, f.name, f.parent_id
+            SELECT f.id, f.name, f.parent_id, b.depth + 1
             FROM folders f
             JOIN breadcrumb b ON f.id = b.parent_id
         )
@@ -250,32 +408,107 @@ pub async fn get_folder_contents(
             name
         FROM
             breadcrumb
-        ORDER BY parent_id NULLS FIRST;
+        ORDER BY depth DESC;
         ",
         folder_id
     )
-    .fetch_all(db.get_ref())
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| Breadcrumb {
+        id: row.id,
+        name: row.name,
+    }).collect())
+}
+
+// スマートフォルダーの条件で検索する。パンくずリストはルートフォルダーとスマートフォルダー（id は null）
+pub(crate) async fn load_smart_folder_contents(
+    db: &PgPool,
+    user_id: i32,
+    root_folder_id: i32,
+    smart_folder: SmartFolder,
+    query: PhotoListQuery,
+) -> Result<FolderContents, HttpResponse> {
+    let criteria = smart_folder.criteria.clone();
+    let query = PhotoListQuery {
+        tags: criteria.tags,
+        q: criteria.q,
+        uploaded_from: criteria.uploaded_from,
+        uploaded_to: criteria.uploaded_to,
+        taken_from: criteria.taken_from,
+        taken_to: criteria.taken_to,
+        folder_id: criteria.folder_id,
+        ..query
+    };
+    let params = photo_list_params(&query)?;
+
+    let folder_ids = match query.folder_id {
+        // 検索範囲のフォルダーが削除された場合は、すべてのフォルダーに広げず何も表示しない
+        _ if smart_folder.scope_deleted => Some(Vec::new()),
+        Some(folder_id) => match subtree_folder_ids(db, &[folder_id]).await {
+            Ok(ids) => Some(ids),
+            Err(_) => return Err(HttpResponse::InternalServerError().body("Error fetching folder hierarchy")),
+        },
+        None => None,
+    };
+
+    let page = fetch_photo_page(db, user_id, folder_ids.as_deref(), &query, &params).await?;
+
+    let root_name = sqlx::query_scalar!(
+        "SELECT name FROM folders WHERE id = $1 AND user_id = $2",
+        root_folder_id,
+        user_id,
+    )
+    .fetch_optional(db)
     .await;
 
-    let breadcrumbs: Vec<Breadcrumb> = match breadcrumb_rows {
-        Ok(rows) => rows.into_iter().map(|row| Breadcrumb {
-            id: row.id,
-            name: row.name,
-        }).collect(),
-        Err(_) => return HttpResponse::InternalServerError().body("Error fetching breadcrumbs"),
+    let root_name = match root_name {
+        Ok(name) => name,
+        Err(_) => return Err(HttpResponse::InternalServerError().body("Error fetching breadcrumbs")),
+    };
+
+    // 表紙は検索結果の先頭の写真
+    let cover_photo_id = page.data.first().map(|photo| photo.id);
+    let cover_thumbnail = match cover_photo_id {
+        Some(id) => fetch_cover_thumbnails(db, &[id]).await.remove(&id),
+        None => None,
     };
 
-    HttpResponse::Ok().json(FolderContents {
+    // スマートフォルダーの ID は smart_folder.id で返す。実在のフォルダーの ID と区別するため folder.id は null
+    let folder = Folder {
+        id: None,
+        user_id: Some(user_id),
+        name: smart_folder.name.clone(),
+        description: None,
+        parent_id: Some(root_folder_id),
+        total_photo_count: Some(page.total_count as usize),
+        total_photo_size: None,
+        cover_photo_id,
+        cover_thumbnail,
+        sort_position: None,
+    };
+
+    let breadcrumbs = vec![
+        Breadcrumb { id: Some(root_folder_id), name: root_name },
+        Breadcrumb { id: None, name: Some(smart_folder.name.clone()) },
+    ];
+
+    Ok(FolderContents {
         folder,
-        photos,
-        child_folders,
+        photos: page.data,
+        next_cursor: page.next_cursor,
+        total_count: page.total_count,
+        child_folders: Vec::new(),
         breadcrumbs,
+        smart_folders: Vec::new(),
+        smart_folder: Some(smart_folder),
     })
 }
 
 #[get("/search")]
 pub async fn get_all_photos(
     req: HttpRequest,
+    query: web::Query<PhotoListQuery>,
     db: web::Data<PgPool>
 ) -> impl Responder {
     let claims = match extract_user_from_jwt(&req) {
@@ -283,7 +516,284 @@ pub async fn get_all_photos(
         Err(resp) => return resp,
     };
 
-    let photo_rows = sqlx::query!(
+    let params = match photo_list_params(&query) {
+        Ok(p) => p,
+        Err(resp) => return resp,
+    };
+
+    // フォルダーを指定した場合は配下のフォルダーも含める
+    let folder_ids = match query.folder_id {
+        Some(folder_id) => match subtree_folder_ids(db.get_ref(), &[folder_id]).await {
+            Ok(ids) => Some(ids),
+            Err(_) => return HttpResponse::InternalServerError().body("Error fetching folder hierarchy"),
+        },
+        None => None,
+    };
+
+    match fetch_photo_page(db.get_ref(), claims.user_id, folder_ids.as_deref(), &query, &params).await {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(resp) => resp,
+    }
+}
+
+struct PhotoListParams<'a> {
+    sort: &'a str,
+    descending: bool,
+    limit: i64,
+    cursor: Option<PageCursor>,
+    search_query: Option<&'a str>,
+    tag_clauses: Option<String>,
+}
+
+// 並び順と昇順・降順。relevance は全文検索の検索語がある場合のみ
+pub(crate) fn photo_sort_order<'a>(
+    sort: Option<&'a str>,
+    order: Option<&str>,
+    has_search_query: bool,
+) -> Result<(&'a str, bool), HttpResponse> {
+    let sort = match sort {
+        None if has_search_query => "relevance",
+        None | Some("uploaded_at") => "uploaded_at",
+        Some(sort @ ("taken_at" | "name" | "size" | "manual")) => sort,
+        Some("relevance") if has_search_query => "relevance",
+        Some("relevance") => return Err(HttpResponse::BadRequest().body("relevance で並べるには q を指定してください")),
+        Some(other) => return Err(HttpResponse::BadRequest().body(format!("不正な並び順です: {}", other))),
+    };
+
+    let descending = match order {
+        None => !matches!(sort, "name" | "manual"),
+        Some("desc") => true,
+        Some("asc") => false,
+        Some(other) => return Err(HttpResponse::BadRequest().body(format!("不正な並び順です: {}", other))),
+    };
+
+    Ok((sort, descending))
+}
+
+fn photo_list_params(query: &PhotoListQuery) -> Result<PhotoListParams<'_>, HttpResponse> {
+    let search_query = query.q.as_deref().map(str::trim);
+    if search_query == Some("") {
+        return Err(HttpResponse::BadRequest().body("q が空です"));
+    }
+
+    let (sort, descending) = photo_sort_order(query.sort.as_deref(), query.order.as_deref(), search_query.is_some())?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(HttpResponse::BadRequest().body(format!("limit は1〜{}で指定してください", MAX_PAGE_SIZE)));
+    }
+
+    let cursor = match query.cursor.as_deref() {
+        Some(value) => {
+            let cursor = PageCursor::decode(value).map_err(|e| HttpResponse::BadRequest().body(e))?;
+            if cursor.sort != sort || cursor.descending != descending {
+                return Err(HttpResponse::BadRequest().body("カーソルと並び順が一致しません"));
+            }
+            Some(cursor)
+        }
+        None => None,
+    };
+
+    let tag_clauses = query
+        .tags
+        .as_deref()
+        .map(tag_query::compile)
+        .transpose()
+        .map_err(|e| HttpResponse::BadRequest().body(e))?;
+
+    Ok(PhotoListParams { sort, descending, limit, cursor, search_query, tag_clauses })
+}
+
+// 絞り込み・並び替えた写真を1ページ分取得する。folder_ids が None の場合はすべてのフォルダーが対象
+// どの並び順でも (数値キー, 文字列キー, ID) の組で並べ、前のページの最後の行より後ろから取得する
+async fn fetch_photo_page(
+    db: &PgPool,
+    user_id: i32,
+    folder_ids: Option<&[i32]>,
+    query: &PhotoListQuery,
+    params: &PhotoListParams<'_>,
+) -> Result<PhotoPage, HttpResponse> {
+    let cursor = params.cursor.as_ref();
+
+    let rows = sqlx::query!(
+        r#"
+        WITH filtered AS (
+            SELECT
+                photos.id,
+                CASE $3
+                    WHEN 'uploaded_at' THEN (EXTRACT(EPOCH FROM photos.uploaded_at) * 1000000)::BIGINT
+                    WHEN 'taken_at' THEN (EXTRACT(EPOCH FROM COALESCE(photo_metadata.taken_at, photos.uploaded_at)) * 1000000)::BIGINT
+                    WHEN 'size' THEN photos.size_in_bytes
+                    WHEN 'manual' THEN COALESCE(photos.sort_position, 2147483647)::BIGINT
+                    -- 全文検索の順位と、あいまい一致の類似度の和
+                    WHEN 'relevance' THEN ((
+                        ts_rank(photos.search_vector, search_query($20))
+                        + word_similarity(search_normalize($20), photos.search_text)
+                    ) * 1000000)::BIGINT
+                    ELSE 0::BIGINT
+                END AS num_key,
+                CASE WHEN $3 = 'name' THEN photos.name ELSE '' END AS text_key
+            FROM
+                photos
+            LEFT JOIN
+                photo_metadata ON photo_metadata.photo_id = photos.id
+            LEFT JOIN LATERAL (
+                SELECT COALESCE(ARRAY_AGG(lower(t.tag)), '{}') AS names
+                FROM photo_tag_relations ptr
+                JOIN tags t ON ptr.tag_id = t.id
+                WHERE ptr.photo_id = photos.id
+            ) photo_tags ON $21::TEXT IS NOT NULL
+            WHERE
+                photos.user_id = $1
+                AND photos.trash_id IS NULL
+                AND ($2::INTEGER[] IS NULL OR photos.folder_id = ANY($2))
+                AND ($9::TIMESTAMPTZ IS NULL OR photos.uploaded_at >= $9)
+                AND ($10::TIMESTAMPTZ IS NULL OR photos.uploaded_at < $10)
+                AND ($11::TIMESTAMPTZ IS NULL OR photo_metadata.taken_at >= $11)
+                AND ($12::TIMESTAMPTZ IS NULL OR photo_metadata.taken_at < $12)
+                AND ($13::BIGINT IS NULL OR photos.size_in_bytes >= $13)
+                AND ($14::BIGINT IS NULL OR photos.size_in_bytes <= $14)
+                AND ($15::INTEGER IS NULL OR photos.width >= $15)
+                AND ($16::INTEGER IS NULL OR photos.width <= $16)
+                AND ($17::INTEGER IS NULL OR photos.height >= $17)
+                AND ($18::INTEGER IS NULL OR photos.height <= $18)
+                AND ($19::BOOLEAN IS NULL OR EXISTS (
+                    SELECT 1 FROM photo_tag_relations ptr WHERE ptr.photo_id = photos.id
+                ) = $19)
+                AND ($20::TEXT IS NULL
+                    OR photos.search_vector @@ search_query($20)
+                    OR photos.search_text LIKE '%' || replace(replace(replace(search_normalize($20), '\', '\\'), '%', '\%'), '_', '\_') || '%'
+                    OR search_normalize($20) <% photos.search_text)
+                AND ($21::TEXT IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM jsonb_array_elements($21::TEXT::JSONB) clause
+                    WHERE photo_tags.names @> ARRAY(SELECT jsonb_array_elements_text(clause->'all'))
+                    AND NOT photo_tags.names && ARRAY(SELECT jsonb_array_elements_text(clause->'none'))
+                ))
+        ),
+        page AS (
+            SELECT id, num_key, text_key
+            FROM filtered
+            WHERE
+                $8::INTEGER IS NULL
+                OR ($4 AND (num_key, text_key, id) < ($6::BIGINT, $7::TEXT, $8::INTEGER))
+                OR (NOT $4 AND (num_key, text_key, id) > ($6::BIGINT, $7::TEXT, $8::INTEGER))
+            ORDER BY
+                CASE WHEN $4 THEN num_key END DESC,
+                CASE WHEN $4 THEN text_key END DESC,
+                CASE WHEN $4 THEN id END DESC,
+                num_key, text_key, id
+            LIMIT $5
+        )
+        SELECT
+            page.id AS "id?",
+            page.num_key AS "num_key?",
+            page.text_key AS "text_key?",
+            counted.total AS "total!"
+        FROM
+            (SELECT COUNT(*) AS total FROM filtered) counted
+        LEFT JOIN
+            page ON TRUE
+        ORDER BY
+            CASE WHEN $4 THEN page.num_key END DESC,
+            CASE WHEN $4 THEN page.text_key END DESC,
+            CASE WHEN $4 THEN page.id END DESC,
+            page.num_key, page.text_key, page.id
+        "#,
+        user_id,
+        folder_ids,
+        params.sort,
+        params.descending,
+        // 次のページがあるか判定するため1件多く取得する
+        params.limit + 1,
+        cursor.map(|c| c.num_key),
+        cursor.map(|c| c.text_key.as_str()),
+        cursor.map(|c| c.id),
+        query.uploaded_from,
+        query.uploaded_to,
+        query.taken_from,
+        query.taken_to,
+        query.min_size,
+        query.max_size,
+        query.min_width,
+        query.max_width,
+        query.min_height,
+        query.max_height,
+        query.tagged,
+        params.search_query,
+        params.tag_clauses.as_deref(),
+    )
+    .fetch_all(db)
+    .await;
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("写真一覧の取得失敗: {:?}", e);
+            return Err(HttpResponse::InternalServerError().body("Error fetching photos"));
+        }
+    };
+
+    let total_count = rows.first().map_or(0, |row| row.total);
+    let mut keys: Vec<(i32, i64, String)> = rows
+        .into_iter()
+        .filter_map(|row| Some((row.id?, row.num_key?, row.text_key?)))
+        .collect();
+
+    let has_more = keys.len() as i64 > params.limit;
+    keys.truncate(params.limit as usize);
+
+    let next_cursor = match keys.last() {
+        Some((id, num_key, text_key)) if has_more => Some(PageCursor {
+            sort: params.sort.to_string(),
+            descending: params.descending,
+            num_key: *num_key,
+            text_key: text_key.clone(),
+            id: *id,
+        }.encode()),
+        _ => None,
+    };
+
+    let photo_ids: Vec<i32> = keys.into_iter().map(|(id, _, _)| id).collect();
+    let mut data = match load_photos(db, &photo_ids).await {
+        Ok(photos) => photos,
+        Err(e) => {
+            eprintln!("写真の取得失敗: {:?}", e);
+            return Err(HttpResponse::InternalServerError().body("Error fetching photos"));
+        }
+    };
+
+    if let Some(search_query) = params.search_query {
+        for photo in &mut data {
+            photo.highlights = search_highlights(photo, search_query);
+        }
+    }
+
+    Ok(PhotoPage { data, next_cursor, total_count })
+}
+
+// あいまい一致だけの場合など、検索語がそのまま含まれない項目は抜粋を返さない
+fn search_highlights(photo: &Photo, search_query: &str) -> Vec<SearchHighlight> {
+    let tags = photo.tags.iter().map(|t| t.tag.as_str()).collect::<Vec<_>>().join(" ");
+    let fields = [
+        ("name", Some(photo.name.as_str())),
+        ("description", photo.description.as_deref()),
+        ("folder_name", Some(photo.folder_name.as_str())),
+        ("tags", Some(tags.as_str())),
+    ];
+
+    fields
+        .into_iter()
+        .filter_map(|(field, text)| {
+            let snippet = highlight(text?, search_query, SNIPPET_LENGTH)?;
+            Some(SearchHighlight { field, snippet })
+        })
+        .collect()
+}
+
+// タグ・レンディション・メタデータを含めて写真を取得する。photo_ids の順番で返す
+pub(crate) async fn load_photos(db: &PgPool, photo_ids: &[i32]) -> Result<Vec<Photo>, sqlx::Error> {
+    let rows = sqlx::query!(
         "SELECT
             photos.id,
             photos.user_id,
@@ -295,24 +805,26 @@ pub async fn get_all_photos(
             photos.size_in_bytes,
             photos.width,
             photos.height,
+            photos.blurhash,
+            photos.lqip,
+            photos.dominant_color,
+            photos.average_color,
+            photos.media_type,
+            photos.duration_seconds,
+            photos.codec,
+            photos.frame_rate,
+            photos.preview_path,
             folders.name AS folder_name
         FROM
             photos
-        LEFT JOIN
+        INNER JOIN
             folders ON photos.folder_id = folders.id
         WHERE
-            photos.user_id = $1",
-        claims.user_id,
+            photos.id = ANY($1)",
+        photo_ids
     )
-    .fetch_all(db.get_ref())
-    .await;
-
-    let rows = match photo_rows {
-        Ok(rows) => rows,
-        Err(_) => return HttpResponse::InternalServerError().body("Error fetching photos"),
-    };
-
-    let photo_ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    .fetch_all(db)
+    .await?;
 
     let photo_tag_rows = sqlx::query!(
         "SELECT
@@ -325,9 +837,9 @@ pub async fn get_all_photos(
             tags t ON ptr.tag_id = t.id
         WHERE
             ptr.photo_id = ANY($1)",
-        &photo_ids
+        photo_ids
     )
-    .fetch_all(db.get_ref())
+    .fetch_all(db)
     .await;
 
     let mut tag_map: HashMap<i32, Vec<TagResponse>> = HashMap::new();
@@ -344,7 +856,10 @@ pub async fn get_all_photos(
         }
     }
 
-    let photos: Vec<Photo> = rows.into_iter().map(|row| Photo {
+    let mut rendition_map = fetch_rendition_map(db, photo_ids).await;
+    let mut metadata_map = fetch_metadata_map(db, photo_ids).await;
+
+    let mut photo_map: HashMap<i32, Photo> = rows.into_iter().map(|row| (row.id, Photo {
         id: row.id,
         user_id: row.user_id,
         name: row.name,
@@ -355,9 +870,178 @@ pub async fn get_all_photos(
         folder_name: row.folder_name,
         size_in_bytes: row.size_in_bytes,
         tags: tag_map.remove(&row.id).unwrap_or_default(),
+        renditions: rendition_map.remove(&row.id).unwrap_or_default(),
+        metadata: metadata_map.remove(&row.id),
+        blurhash: row.blurhash,
+        lqip: row.lqip,
+        dominant_color: row.dominant_color,
+        average_color: row.average_color,
+        media_type: row.media_type,
+        duration_seconds: row.duration_seconds,
+        codec: row.codec,
+        frame_rate: row.frame_rate,
+        preview_path: row.preview_path,
         width: row.width,
         height: row.height,
-    }).collect();
+        highlights: Vec::new(),
+    })).collect();
+
+    Ok(photo_ids.iter().filter_map(|id| photo_map.remove(id)).collect())
+}
+
+// 表紙の写真ID → サムネイルのURL（一番小さいレンディション。未生成の静止画は元画像）
+pub(crate) async fn fetch_cover_thumbnails(
+    db: &PgPool,
+    photo_ids: &[i32],
+) -> HashMap<i32, String> {
+    let rows = sqlx::query!(
+        "SELECT id, image_path, media_type FROM photos WHERE id = ANY($1)",
+        photo_ids
+    )
+    .fetch_all(db)
+    .await;
+
+    let mut rendition_map = fetch_rendition_map(db, photo_ids).await;
+
+    match rows {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|row| {
+                let rendition = rendition_map
+                    .remove(&row.id)
+                    .and_then(|renditions| renditions.into_iter().next())
+                    .map(|r| r.image_path);
+                let original = (row.media_type == "image").then_some(row.image_path);
+
+                rendition.or(original).map(|path| (row.id, path))
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("表紙の取得失敗: {:?}", e);
+            HashMap::new()
+        }
+    }
+}
+
+// 写真ID → レンディション一覧（小さい順）
+pub(crate) async fn fetch_rendition_map(
+    db: &PgPool,
+    photo_ids: &[i32],
+) -> HashMap<i32, Vec<Rendition>> {
+    let rows = sqlx::query!(
+        "
+        SELECT
+            photo_id,
+            size,
+            format,
+            image_path,
+            width,
+            height
+        FROM
+            photo_renditions
+        WHERE
+            photo_id = ANY($1)
+        ORDER BY
+            size, format
+        ",
+        photo_ids
+    )
+    .fetch_all(db)
+    .await;
+
+    let mut rendition_map: HashMap<i32, Vec<Rendition>> = HashMap::new();
+
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                rendition_map
+                    .entry(row.photo_id)
+                    .or_default()
+                    .push(Rendition {
+                        size: row.size,
+                        format: row.format,
+                        image_path: row.image_path,
+                        width: row.width,
+                        height: row.height,
+                    });
+            }
+        }
+        Err(e) => eprintln!("レンディション取得失敗: {:?}", e),
+    }
+
+    rendition_map
+}
+
+pub(crate) async fn fetch_metadata_map(
+    db: &PgPool,
+    photo_ids: &[i32],
+) -> HashMap<i32, PhotoMetadata> {
+    let rows = sqlx::query!(
+        "
+        SELECT
+            photo_id,
+            camera_make,
+            camera_model,
+            lens_model,
+            focal_length,
+            aperture,
+            exposure_time,
+            iso,
+            taken_at,
+            timezone_offset_minutes,
+            latitude,
+            longitude,
+            altitude
+        FROM
+            photo_metadata
+        WHERE
+            photo_id = ANY($1)
+        ",
+        photo_ids
+    )
+    .fetch_all(db)
+    .await;
+
+    let mut metadata_map: HashMap<i32, PhotoMetadata> = HashMap::new();
+
+    match rows {
+        Ok(rows) => {
+            for row in rows {
+                let offset = row.timezone_offset_minutes.and_then(utc_offset);
 
-    HttpResponse::Ok().json(photos)
+                metadata_map.insert(row.photo_id, PhotoMetadata {
+                    camera_make: row.camera_make,
+                    camera_model: row.camera_model,
+                    lens_model: row.lens_model,
+                    focal_length: row.focal_length,
+                    aperture: row.aperture,
+                    exposure_time: row.exposure_time,
+                    iso: row.iso,
+                    taken_at: row.taken_at.map(|t| match offset {
+                        Some(offset) => t.to_offset(offset),
+                        None => t,
+                    }),
+                    timezone_offset: row.timezone_offset_minutes.map(format_utc_offset),
+                    latitude: row.latitude,
+                    longitude: row.longitude,
+                    altitude: row.altitude,
+                });
+            }
+        }
+        Err(e) => eprintln!("メタデータ取得失敗: {:?}", e),
+    }
+
+    metadata_map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_folder_path() {
+        assert_eq!(split_folder_path("/2024/trips//kyoto/"), vec!["2024", "trips", "kyoto"]);
+        assert!(split_folder_path("").is_empty());
+        assert!(split_folder_path("/").is_empty());
+    }
 }
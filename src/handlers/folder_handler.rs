@@ -1,7 +1,154 @@
-use actix_web::{post, put, delete, web::{self}, HttpRequest, HttpResponse, Responder};
+use std::collections::{HashMap, HashSet};
+
+use actix_web::{get, post, put, delete, web::{self}, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
 use serde::Deserialize;
-use crate::{handlers::{auth_handler::extract_user_from_jwt, s3_handler::delete_image_from_s3}, models::folder::{FolderDeleteRequest, FolderUpdateRequest}, utils::s3::create_s3_client};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, Postgres, Transaction};
+use crate::{handlers::auth_handler::extract_user_from_jwt, models::folder::{FolderCopyRequest, FolderCoverRequest, FolderDeleteRequest, FolderMoveRequest, FolderOrderRequest, FolderTreeNode, FolderTreeQuery, FolderUpdateRequest}};
+use crate::handlers::trash_handler::trash_folders;
+use crate::handlers::photo_handler::{copy_photos, discard_copied_objects};
 use crate::message;
+use crate::utils::s3::create_s3_client;
+
+// pg_advisory_xact_lock(クラス, ユーザーID) のクラス部分
+const FOLDER_TREE_LOCK_CLASS: i32 = 0x464F_4C44;
+
+// 指定したフォルダーとその配下すべてのフォルダーID
+pub(crate) async fn subtree_folder_ids<'e, E: PgExecutor<'e>>(
+    executor: E,
+    root_ids: &[i32],
+) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE all_folders AS (
+            SELECT id FROM folders WHERE id = ANY($1)
+            UNION
+            SELECT f.id
+            FROM folders f
+            INNER JOIN all_folders af ON f.parent_id = af.id
+            WHERE f.trash_id IS NULL
+        )
+        SELECT id AS "id!" FROM all_folders
+        "#,
+        root_ids,
+    )
+    .fetch_all(executor)
+    .await
+}
+
+// フォルダー構成を変更する処理を、ユーザーごとに1つずつ実行する（同時に移動して循環するのを防ぐ）
+pub(crate) async fn lock_folder_tree(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "SELECT pg_advisory_xact_lock($1, $2)",
+        FOLDER_TREE_LOCK_CLASS,
+        user_id,
+    )
+    .execute(&mut **tx)
+    .await
+    .map(|_| ())
+}
+
+// 並べ替えで指定されたIDに重複が無いか
+pub(crate) fn has_duplicate_ids(ids: &[i32]) -> bool {
+    let mut seen = HashSet::new();
+    !ids.iter().all(|id| seen.insert(id))
+}
+
+// 同じ親フォルダーの下に同名のフォルダーがある
+pub(crate) fn is_unique_violation(e: &sqlx::Error) -> bool {
+    e.as_database_error().and_then(|d| d.code()).as_deref() == Some("23505")
+}
+
+fn folder_name_conflict() -> HttpResponse {
+    HttpResponse::Conflict().body("同じ名前のフォルダーが既に存在します")
+}
+
+// パスの区切り文字と区別できないため「/」は使えない
+pub(crate) fn validate_folder_name(name: &str) -> Result<(), HttpResponse> {
+    if name.trim().is_empty() {
+        return Err(HttpResponse::BadRequest().body("フォルダー名を入力してください"));
+    }
+    if name.contains('/') {
+        return Err(HttpResponse::BadRequest().body("フォルダー名に「/」は使用できません"));
+    }
+    Ok(())
+}
+
+// 複製先・復元先に同名のフォルダーがある場合は「名前 (2)」のように番号を付ける
+pub(crate) async fn available_folder_name(
+    tx: &mut Transaction<'_, Postgres>,
+    parent_id: i32,
+    name: &str,
+) -> Result<String, sqlx::Error> {
+    let siblings: HashSet<String> = sqlx::query_scalar!(
+        "SELECT name FROM folders WHERE parent_id = $1 AND trash_id IS NULL",
+        parent_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .collect();
+
+    let candidate = (1..)
+        .map(|n| if n == 1 { name.to_string() } else { format!("{} ({})", name, n) })
+        .find(|candidate| !siblings.contains(candidate))
+        .unwrap_or_else(|| name.to_string());
+
+    Ok(candidate)
+}
+
+struct FolderTreeRow {
+    id: i32,
+    name: String,
+    parent_id: Option<i32>,
+    depth: i32,
+    photo_count: i64,
+    photo_size: i64,
+}
+
+// 深さ順に並んだ行から入れ子の木を組み立てる。合計は max_depth より深いフォルダーも含めて集計する
+fn build_folder_tree(rows: Vec<FolderTreeRow>, root_id: i32, max_depth: Option<i32>) -> Option<FolderTreeNode> {
+    let mut children: HashMap<i32, Vec<FolderTreeRow>> = HashMap::new();
+    let mut root = None;
+
+    for row in rows {
+        match row.parent_id {
+            _ if row.id == root_id => root = Some(row),
+            Some(parent_id) => children.entry(parent_id).or_default().push(row),
+            None => {}
+        }
+    }
+
+    fn build(row: FolderTreeRow, children: &mut HashMap<i32, Vec<FolderTreeRow>>, max_depth: Option<i32>) -> FolderTreeNode {
+        let mut node = FolderTreeNode {
+            id: row.id,
+            name: row.name,
+            parent_id: row.parent_id,
+            depth: row.depth,
+            photo_count: row.photo_count,
+            total_photo_count: row.photo_count,
+            total_photo_size: row.photo_size,
+            children: Vec::new(),
+        };
+
+        for child in children.remove(&row.id).unwrap_or_default() {
+            let child = build(child, children, max_depth);
+            node.total_photo_count += child.total_photo_count;
+            node.total_photo_size += child.total_photo_size;
+            if max_depth.is_none_or(|max| child.depth <= max) {
+                node.children.push(child);
+            }
+        }
+
+        node
+    }
+
+    root.map(|root| build(root, &mut children, max_depth))
+}
 
 #[derive(Debug, Deserialize)]
 pub struct FolderCreateRequest {
@@ -21,30 +168,58 @@ async fn create_folder(
         Err(resp) => return resp,
     };
 
+    if let Err(resp) = validate_folder_name(&payload.name) {
+        return resp;
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    // ゴミ箱に移す処理と重ならないようロックする
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダ構成のロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    // 親フォルダーの指定が無い場合はルートフォルダーに作る。ゴミ箱のフォルダーや他のユーザーのフォルダーには作れない
     let result = sqlx::query!(
         "
         INSERT INTO folders
             (user_id, name, description, parent_id)
-        VALUES
-            ($1, $2, $3, $4)
+        SELECT
+            $1, $2, $3, $4
+        WHERE
+            EXISTS (SELECT 1 FROM folders WHERE id = $4 AND user_id = $1 AND trash_id IS NULL)
         RETURNING
             id
         ",
         claims.user_id,
         payload.name,
         payload.description,
-        payload.parent_id,
+        payload.parent_id.unwrap_or(claims.root_folder),
     )
-    .fetch_one(db_pool.get_ref())
+    .fetch_optional(&mut *tx)
     .await;
 
+    let result = match result {
+        Ok(Some(record)) => tx.commit().await.map(|_| Some(record)),
+        other => other,
+    };
+
     match result {
-        Ok(record) => HttpResponse::Ok().json({
+        Ok(None) => HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません"),
+        Ok(Some(record)) => HttpResponse::Ok().json({
             serde_json::json!({
                 "message": message::AppSuccess::CreatedFolder.message(),
                 "id": record.id
             })
         }),
+        Err(e) if is_unique_violation(&e) => folder_name_conflict(),
         Err(e) => {
             eprintln!("フォルダ作成エラー: {:?}", e);
             HttpResponse::InternalServerError().body("")
@@ -68,7 +243,7 @@ pub async fn update_folder(
             id
         FROM
             folders
-        WHERE id = $1 AND user_id = $2",
+        WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
         payload.folder_id,
         claims.user_id,
     )
@@ -79,6 +254,10 @@ pub async fn update_folder(
         return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません");
     };
 
+    if let Err(resp) = validate_folder_name(&payload.name) {
+        return resp;
+    }
+
     let result = sqlx::query!(
         "
         UPDATE folders
@@ -103,6 +282,7 @@ pub async fn update_folder(
                 "description": record.description,
             }
         })),
+        Err(e) if is_unique_violation(&e) => folder_name_conflict(),
         Err(e) => {
             eprintln!("フォルダ更新エラー: {:?}", e);
             HttpResponse::InternalServerError().body("フォルダの更新に失敗しました")
@@ -110,110 +290,813 @@ pub async fn update_folder(
     }
 }
 
-#[delete("/folders")]
-pub async fn delete_folder(
+// サイドバーや移動先の選択用に、フォルダー階層全体を返す
+#[get("/folders/tree")]
+pub async fn get_folder_tree(
     db_pool: web::Data<sqlx::PgPool>,
-    payload: web::Json<FolderDeleteRequest>,
+    query: web::Query<FolderTreeQuery>,
     req: HttpRequest,
 ) -> impl Responder {
-    let (s3_client, bucket_name, _) = create_s3_client();
-
     let claims = match extract_user_from_jwt(&req) {
         Ok(c) => c,
         Err(resp) => return resp,
     };
 
-    let folder_ids = &payload.ids;
+    let root_id = query.root.unwrap_or(claims.root_folder);
 
-    let mut tx = match db_pool.begin().await {
-        Ok(tx) => tx,
+    if query.max_depth.is_some_and(|d| d < 0) {
+        return HttpResponse::BadRequest().body("max_depth は0以上で指定してください");
+    }
+
+    let rows = sqlx::query_as!(
+        FolderTreeRow,
+        r#"
+        WITH RECURSIVE tree AS (
+            SELECT id, name, parent_id, sort_position, 0 AS depth
+            FROM folders
+            WHERE id = $1 AND user_id = $2 AND trash_id IS NULL
+            UNION ALL
+            SELECT f.id, f.name, f.parent_id, f.sort_position, t.depth + 1
+            FROM folders f
+            INNER JOIN tree t ON f.parent_id = t.id
+            WHERE f.trash_id IS NULL
+        )
+        SELECT
+            t.id AS "id!",
+            t.name AS "name!",
+            t.parent_id,
+            t.depth AS "depth!",
+            COUNT(p.id) AS "photo_count!",
+            COALESCE(SUM(p.size_in_bytes), 0)::BIGINT AS "photo_size!"
+        FROM
+            tree t
+        LEFT JOIN
+            photos p ON p.folder_id = t.id AND p.trash_id IS NULL
+        GROUP BY
+            t.id, t.name, t.parent_id, t.sort_position, t.depth
+        ORDER BY
+            t.depth, t.sort_position NULLS LAST, t.name, t.id
+        "#,
+        root_id,
+        claims.user_id,
+    )
+    .fetch_all(db_pool.get_ref())
+    .await;
+
+    let tree = match rows {
+        Ok(rows) => build_folder_tree(rows, root_id, query.max_depth),
         Err(e) => {
-            eprintln!("トランザクション開始エラー: {:?}", e);
-            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+            eprintln!("フォルダ階層の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
         }
     };
 
-    for &folder_id in folder_ids {
-        let folder_check = sqlx::query_scalar!(
-            "SELECT
-                id
-            FROM
-                folders
-            WHERE id = $1 AND user_id = $2",
-            folder_id,
+    let Some(tree) = tree else {
+        return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません");
+    };
+
+    let body = match serde_json::to_string(&tree) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("フォルダ階層のシリアライズ失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    // 内容が変わらなければ同じ値になるよう、レスポンス本文から作る
+    let hash: String = Sha256::digest(&body).iter().take(16).map(|b| format!("{:02x}", b)).collect();
+    let etag = format!("\"{}\"", hash);
+
+    let not_modified = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"))
+        .unwrap_or(false);
+
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, "private, no-cache"))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .insert_header((ETAG, etag))
+        .insert_header((CACHE_CONTROL, "private, no-cache"))
+        .content_type("application/json")
+        .body(body)
+}
+
+// 表紙の写真を指定する。配下のフォルダーにある写真も指定できる
+#[put("/folders/cover")]
+pub async fn set_folder_cover(
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<FolderCoverRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let folder_check = sqlx::query_scalar!(
+        "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        payload.folder_id,
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    let Some(_) = folder_check.ok().flatten() else {
+        return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません");
+    };
+
+    if let Some(photo_id) = payload.photo_id {
+        let photo_folder = sqlx::query_scalar!(
+            "SELECT folder_id FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+            photo_id,
             claims.user_id,
         )
-        .fetch_optional(&mut *tx)
+        .fetch_optional(db_pool.get_ref())
         .await;
 
-        match folder_check {
-            Ok(Some(_)) => {}
-            Ok(None) => {
-                return HttpResponse::NotFound()
-                    .body(format!("フォルダID {} が存在しないか、権限がありません", folder_id));
-            }
+        let photo_folder = match photo_folder {
+            Ok(Some(folder_id)) => folder_id,
+            Ok(None) => return HttpResponse::NotFound().body("写真が存在しないか、権限がありません"),
             Err(e) => {
-                eprintln!("フォルダ確認失敗: {:?}", e);
-                return HttpResponse::InternalServerError()
-                    .body(message::AppError::InternalServerError.message());
+                eprintln!("写真確認失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
             }
-        }
+        };
 
-        let photos = match sqlx::query!(
-            "SELECT
-                id,
-                image_path
-            FROM
-                photos
-            WHERE folder_id = $1",
-            folder_id
-        )
-        .fetch_all(&mut *tx)
-        .await
-        {
-            Ok(p) => p,
+        match subtree_folder_ids(db_pool.get_ref(), &[payload.folder_id]).await {
+            Ok(ids) if ids.contains(&photo_folder) => {}
+            Ok(_) => return HttpResponse::BadRequest().body("フォルダーの配下にある写真を指定してください"),
             Err(e) => {
-                eprintln!("写真取得失敗: {:?}", e);
+                eprintln!("フォルダ階層の取得失敗: {:?}", e);
                 return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
             }
-        };
+        }
+    }
 
-        for photo in photos {
-            if let Err(e) = delete_image_from_s3(&s3_client, &bucket_name, &photo.image_path).await {
-                eprintln!("S3画像削除失敗: {}", e);
-                return HttpResponse::InternalServerError()
-                    .body(format!("S3画像削除失敗: {}", e));
-            }
+    let result = sqlx::query!(
+        "UPDATE folders SET cover_photo_id = $1 WHERE id = $2 AND user_id = $3",
+        payload.photo_id,
+        payload.folder_id,
+        claims.user_id,
+    )
+    .execute(db_pool.get_ref())
+    .await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Updated(message::FileType::Folder).message(),
+        })),
+        Err(e) => {
+            eprintln!("表紙の更新失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("フォルダの更新に失敗しました")
+        }
+    }
+}
 
-            let result = sqlx::query!("DELETE FROM photos WHERE id = $1", photo.id)
-                .execute(&mut *tx)
-                .await;
+// 子フォルダーを指定した順に並べる
+#[put("/folders/order")]
+pub async fn reorder_folders(
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<FolderOrderRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
 
-            if let Err(e) = result {
-                eprintln!("DBからの画像削除失敗: {:?}", e);
-                return HttpResponse::InternalServerError().body("写真の削除に失敗しました");
-            }
+    if has_duplicate_ids(&payload.ids) {
+        return HttpResponse::BadRequest().body("フォルダーIDが重複しています");
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
         }
+    };
 
-        let delete_result = sqlx::query!(
-            "DELETE FROM folders WHERE id = $1 AND user_id = $2",
-            folder_id,
-            claims.user_id,
+    let children = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM folders WHERE id = ANY($1) AND parent_id = $2 AND user_id = $3 AND trash_id IS NULL"#,
+        &payload.ids,
+        payload.parent_id,
+        claims.user_id,
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match children {
+        Ok(count) if count == payload.ids.len() as i64 => {}
+        Ok(_) => return HttpResponse::BadRequest().body("指定したフォルダーの直下にないフォルダーが含まれています"),
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let cleared = sqlx::query!(
+        "UPDATE folders SET sort_position = NULL WHERE parent_id = $1 AND user_id = $2 AND NOT (id = ANY($3))",
+        payload.parent_id,
+        claims.user_id,
+        &payload.ids,
+    )
+    .execute(&mut *tx)
+    .await;
+
+    let result = match cleared {
+        Ok(_) => sqlx::query!(
+            "
+            UPDATE folders f
+            SET sort_position = o.position::INTEGER
+            FROM UNNEST($1::INTEGER[]) WITH ORDINALITY AS o(id, position)
+            WHERE f.id = o.id
+            ",
+            &payload.ids,
         )
         .execute(&mut *tx)
-        .await;
+        .await,
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(_) => tx.commit().await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Updated(message::FileType::Folder).message(),
+        })),
+        Err(e) => {
+            eprintln!("フォルダーの並べ替え失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("フォルダの更新に失敗しました")
+        }
+    }
+}
 
-        if let Err(e) = delete_result {
-            eprintln!("フォルダ削除失敗: {:?}", e);
+// フォルダーを配下のフォルダー・写真ごと別のフォルダーの下に移動する
+#[put("/folders/move")]
+pub async fn move_folder(
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<FolderMoveRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut folder_ids = payload.ids.clone();
+    folder_ids.sort_unstable();
+    folder_ids.dedup();
+
+    if folder_ids.is_empty() {
+        return HttpResponse::BadRequest().body("移動するフォルダーIDが指定されていません");
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダーのロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    let target = sqlx::query_scalar!(
+        "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        payload.parent_id,
+        claims.user_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await;
+
+    match target {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("移動先のフォルダーが存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let folders = sqlx::query!(
+        "SELECT id, parent_id FROM folders WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL",
+        &folder_ids,
+        claims.user_id,
+    )
+    .fetch_all(&mut *tx)
+    .await;
+
+    let folders = match folders {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    if folders.len() != folder_ids.len() {
+        return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません");
+    }
+
+    if folders.iter().any(|f| f.id == claims.root_folder || f.parent_id.is_none()) {
+        return HttpResponse::BadRequest().body("ルートフォルダーは移動できません");
+    }
+
+    // 自分自身や配下のフォルダーの下には移動できない
+    let subtree = match subtree_folder_ids(&mut *tx, &folder_ids).await {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("フォルダ階層の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    if subtree.contains(&payload.parent_id) {
+        return HttpResponse::BadRequest().body("フォルダーを自身の配下に移動することはできません");
+    }
+
+    // 手動の並び順は移動前の親フォルダーでのものなので解除する
+    let result = sqlx::query!(
+        "UPDATE folders SET parent_id = $1, sort_position = NULL WHERE id = ANY($2) AND user_id = $3",
+        payload.parent_id,
+        &folder_ids,
+        claims.user_id,
+    )
+    .execute(&mut *tx)
+    .await;
+
+    if let Err(e) = result {
+        if is_unique_violation(&e) {
+            return folder_name_conflict();
+        }
+        eprintln!("フォルダー移動失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body("フォルダーの移動に失敗しました");
+    }
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("トランザクションコミット失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": format!("{}件のフォルダーを移動しました", folder_ids.len()),
+    }))
+}
+
+// フォルダーを配下のフォルダー・写真ごと複製する
+#[post("/folders/copy")]
+pub async fn copy_folder(
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<FolderCopyRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut folder_ids = payload.ids.clone();
+    folder_ids.sort_unstable();
+    folder_ids.dedup();
+
+    if folder_ids.is_empty() {
+        return HttpResponse::BadRequest().body("複製するフォルダーIDが指定されていません");
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダーのロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    let target = sqlx::query_scalar!(
+        "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        payload.parent_id,
+        claims.user_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await;
+
+    match target {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("複製先のフォルダーが存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let owned = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM folders WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL"#,
+        &folder_ids,
+        claims.user_id,
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match owned {
+        Ok(count) if count == folder_ids.len() as i64 => {}
+        Ok(_) => return HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    // 親から順に作成できるよう浅い順に並べる。複製先が複製元の配下でも、作成前の状態だけを複製する
+    let subtree = match sqlx::query!(
+        r#"
+        WITH RECURSIVE all_folders AS (
+            SELECT id, parent_id, name, description, 0 AS depth, id AS root_id
+            FROM folders
+            WHERE id = ANY($1)
+            UNION ALL
+            SELECT f.id, f.parent_id, f.name, f.description, af.depth + 1, af.root_id
+            FROM folders f
+            INNER JOIN all_folders af ON f.parent_id = af.id
+            WHERE f.trash_id IS NULL
+        )
+        SELECT
+            id AS "id!",
+            parent_id,
+            name AS "name!",
+            description,
+            depth AS "depth!",
+            root_id AS "root_id!"
+        FROM all_folders
+        ORDER BY depth, id
+        "#,
+        &folder_ids,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("フォルダ階層の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    // 指定したフォルダー同士が親子関係にある場合は、上位のフォルダーの複製に含める
+    let nested: HashSet<i32> = subtree.iter().filter(|f| f.depth > 0).map(|f| f.id).collect();
+    let mut folder_map: HashMap<i32, i32> = HashMap::new();
+    for folder in &subtree {
+        if folder_map.contains_key(&folder.id) {
+            continue;
+        }
+
+        let parent_id = match folder.depth {
+            0 if nested.contains(&folder.id) => continue,
+            0 => payload.parent_id,
+            _ => match folder.parent_id.and_then(|id| folder_map.get(&id)) {
+                Some(&id) => id,
+                None => continue,
+            },
+        };
+
+        let name = match folder.depth {
+            0 => available_folder_name(&mut tx, parent_id, &folder.name).await,
+            _ => Ok(folder.name.clone()),
+        };
+
+        let created = match name {
+            Ok(name) => sqlx::query_scalar!(
+                "INSERT INTO folders (user_id, name, description, parent_id) VALUES ($1, $2, $3, $4) RETURNING id",
+                claims.user_id,
+                name,
+                folder.description,
+                parent_id,
+            )
+            .fetch_one(&mut *tx)
+            .await,
+            Err(e) => Err(e),
+        };
+
+        match created {
+            Ok(id) => {
+                folder_map.insert(folder.id, id);
+            }
+            Err(e) => {
+                eprintln!("フォルダーの複製失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body("フォルダーの複製に失敗しました");
+            }
+        }
+    }
+
+    let source_folder_ids: Vec<i32> = folder_map.keys().copied().collect();
+    let photos = match sqlx::query!(
+        "SELECT id, folder_id FROM photos WHERE folder_id = ANY($1) AND trash_id IS NULL ORDER BY id",
+        &source_folder_ids,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let targets: Vec<(i32, i32)> = photos
+        .iter()
+        .filter_map(|p| folder_map.get(&p.folder_id).map(|&folder_id| (p.id, folder_id)))
+        .collect();
+
+    let (client, bucket_name, region) = create_s3_client();
+    let mut copied_urls = Vec::new();
+
+    let result = copy_photos(&mut tx, &client, &bucket_name, &region, claims.user_id, &targets, &mut copied_urls).await;
+    let result = match result {
+        Ok(photo_map) => tx.commit().await.map(|_| photo_map).map_err(|e| format!("トランザクションコミット失敗: {:?}", e)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(photo_map) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}件のフォルダーを複製しました", folder_map.len()),
+            "folders": folder_map,
+            "photos": photo_map,
+        })),
+        Err(e) => {
+            eprintln!("フォルダーの複製失敗: {}", e);
+            discard_copied_objects(db_pool.get_ref(), copied_urls).await;
+            HttpResponse::InternalServerError().body("フォルダーの複製に失敗しました")
+        }
+    }
+}
+
+// 配下のフォルダー・写真ごとゴミ箱に移す。完全な削除は /trash で行う
+#[delete("/folders")]
+pub async fn delete_folder(
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<FolderDeleteRequest>,
+    req: HttpRequest,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut folder_ids = payload.ids.clone();
+    folder_ids.sort_unstable();
+    folder_ids.dedup();
+
+    if folder_ids.is_empty() {
+        return HttpResponse::BadRequest().body("削除するフォルダーIDが指定されていません");
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダーのロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    let folders = match sqlx::query!(
+        "SELECT id, parent_id FROM folders WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL",
+        &folder_ids,
+        claims.user_id,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    {
+        Ok(f) => f,
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    if let Some(&missing) = folder_ids.iter().find(|&&id| !folders.iter().any(|f| f.id == id)) {
+        return HttpResponse::NotFound()
+            .body(format!("フォルダID {} が存在しないか、権限がありません", missing));
+    }
+
+    if folders.iter().any(|f| f.id == claims.root_folder || f.parent_id.is_none()) {
+        return HttpResponse::BadRequest().body("ルートフォルダーは削除できません");
+    }
+
+    let (deleted_folders, deleted_photos) = match trash_folders(&mut tx, claims.user_id, &folder_ids).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            eprintln!("フォルダーをゴミ箱に移せませんでした: {:?}", e);
             return HttpResponse::InternalServerError()
                 .body(message::AppError::DeleteFailed(message::FileType::Folder).message());
         }
-    }
+    };
 
     if let Err(e) = tx.commit().await {
         eprintln!("トランザクションコミット失敗: {:?}", e);
         return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
     }
 
-    HttpResponse::Ok().json(serde_json::json!({ "message": "フォルダーを削除しました" }))
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "フォルダーをゴミ箱に移しました",
+        "deleted_folders": deleted_folders,
+        "deleted_photos": deleted_photos,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: i32, parent_id: Option<i32>, depth: i32, photo_count: i64) -> FolderTreeRow {
+        FolderTreeRow {
+            id,
+            name: format!("folder{}", id),
+            parent_id,
+            depth,
+            photo_count,
+            photo_size: photo_count * 100,
+        }
+    }
+
+    #[test]
+    fn test_build_folder_tree() {
+        let rows = vec![
+            row(1, None, 0, 1),
+            row(2, Some(1), 1, 2),
+            row(3, Some(1), 1, 0),
+            row(4, Some(2), 2, 5),
+        ];
+
+        let tree = build_folder_tree(rows, 1, None).unwrap();
+        assert_eq!(tree.total_photo_count, 8);
+        assert_eq!(tree.total_photo_size, 800);
+        assert_eq!(tree.children.iter().map(|c| c.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(tree.children[0].children[0].id, 4);
+    }
+
+    #[test]
+    fn test_build_folder_tree_max_depth() {
+        let rows = vec![
+            row(2, Some(1), 0, 2),
+            row(4, Some(2), 1, 5),
+        ];
+
+        let tree = build_folder_tree(rows, 2, Some(0)).unwrap();
+        assert!(tree.children.is_empty());
+        assert_eq!(tree.photo_count, 2);
+        assert_eq!(tree.total_photo_count, 7);
+
+        assert!(build_folder_tree(Vec::new(), 2, None).is_none());
+    }
+
+    // 共有のテスト用DBを汚さないよう、使い捨てのユーザーで試して最後に丸ごと消す
+    async fn create_test_user(pool: &sqlx::PgPool) -> (i32, i32) {
+        let user_id = sqlx::query_scalar!(
+            "INSERT INTO users (name, email, password_hash) VALUES ('test', $1, '') RETURNING id",
+            format!("{}@example.com", uuid::Uuid::new_v4()),
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        let root_folder = sqlx::query_scalar!(
+            "INSERT INTO folders (user_id, name) VALUES ($1, 'root') RETURNING id",
+            user_id,
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap();
+
+        sqlx::query!("UPDATE users SET root_folder = $1 WHERE id = $2", root_folder, user_id)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        (user_id, root_folder)
+    }
+
+    async fn delete_test_user(pool: &sqlx::PgPool, user_id: i32) {
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query!("UPDATE users SET root_folder = NULL WHERE id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        sqlx::query!("UPDATE folders SET trash_id = NULL WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        sqlx::query!("DELETE FROM photos WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        sqlx::query!("DELETE FROM folders WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        // ゴミ箱の記録などはユーザーと一緒に消える
+        sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    // ゴミ箱のフォルダーの中には写真もフォルダーも作れない
+    #[actix_web::test]
+    async fn test_reject_trashed_target_folder() {
+        use actix_web::{test, App};
+        use jsonwebtoken::{encode, EncodingKey, Header};
+        use crate::handlers::auth_handler::SECRET;
+        use crate::handlers::photo_handler::upload_photo;
+        use crate::models::user::Claims;
+
+        dotenvy::from_filename(".env.test").ok();
+
+        let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+        let pool = sqlx::PgPool::connect(&db_url).await.expect("Failed to connect to DB");
+
+        let (user_id, root_folder) = create_test_user(&pool).await;
+        let folder_id = sqlx::query_scalar!(
+            "INSERT INTO folders (user_id, name, parent_id) VALUES ($1, 'trashed', $2) RETURNING id",
+            user_id,
+            root_folder,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let mut tx = pool.begin().await.unwrap();
+        trash_folders(&mut tx, user_id, &[folder_id]).await.unwrap();
+        tx.commit().await.unwrap();
+
+        let claims = Claims {
+            user_id,
+            root_folder,
+            exp: (chrono::Utc::now() + chrono::Duration::minutes(10)).timestamp() as usize,
+        };
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(SECRET)).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .service(create_folder)
+                .service(upload_photo),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/folders")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(serde_json::json!({ "name": "child", "parent_id": folder_id }))
+            .to_request();
+        let folder_status = test::call_service(&app, req).await.status();
+
+        let req = test::TestRequest::post()
+            .uri("/photos")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(serde_json::json!({
+                "folder_id": folder_id,
+                "image_path": "/images/trashed.jpg",
+                "size_in_bytes": 1,
+            }))
+            .to_request();
+        let photo_status = test::call_service(&app, req).await.status();
+
+        let children = sqlx::query_scalar!(
+            r#"SELECT (SELECT COUNT(*) FROM folders WHERE parent_id = $1) + (SELECT COUNT(*) FROM photos WHERE folder_id = $1) AS "count!""#,
+            folder_id,
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        delete_test_user(&pool, user_id).await;
+
+        assert_eq!(folder_status, actix_web::http::StatusCode::NOT_FOUND);
+        assert_eq!(photo_status, actix_web::http::StatusCode::NOT_FOUND);
+        assert_eq!(children, 0);
+    }
 }
@@ -0,0 +1,254 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::message;
+use crate::models::photo::{ImageTransformQuery, ImageUrlRequest};
+use crate::utils::image_processing::decode_image;
+use crate::utils::media::still_image;
+use crate::utils::image_transform::TransformParams;
+use crate::utils::photo_edit::{apply_edits, parse_edits};
+use crate::utils::s3::{create_s3_client, download_object, object_key_from_url, upload_object};
+use crate::utils::signed_url;
+
+pub(crate) const DEFAULT_URL_EXPIRES_IN: i64 = 60 * 60;
+pub(crate) const MAX_URL_EXPIRES_IN: i64 = 60 * 60 * 24 * 7;
+const CACHE_CONTROL_VALUE: &str = "private, max-age=3600";
+
+fn signing_message(photo_id: i32, user_id: i32, exp: i64, params: &TransformParams) -> String {
+    format!("{}:{}:{}:{}", photo_id, user_id, exp, params.canonical())
+}
+
+// 変換後画像のキャッシュキー。削除時に前方一致でまとめて消せるよう写真IDを先頭に置く
+pub fn derived_image_prefix(photo_id: i32) -> String {
+    format!("derived-{}-", photo_id)
+}
+
+// 変換用の署名付きURLを発行する（画像タグから直接参照できるよう、取得側はJWT不要）
+#[post("/photos/{id}/image-url")]
+pub async fn create_image_url(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<ImageUrlRequest>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_id = path.into_inner();
+
+    let params = match TransformParams::parse(
+        payload.w,
+        payload.h,
+        payload.fit.as_deref(),
+        payload.fmt.as_deref(),
+        payload.q,
+    ) {
+        Ok(p) => p,
+        Err(e) => return HttpResponse::BadRequest().json(serde_json::json!({ "message": e })),
+    };
+
+    let expires_in = payload.expires_in.unwrap_or(DEFAULT_URL_EXPIRES_IN);
+    if !(1..=MAX_URL_EXPIRES_IN).contains(&expires_in) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "message": format!("expires_in は 1〜{} 秒の範囲で指定してください", MAX_URL_EXPIRES_IN)
+        }));
+    }
+
+    let photo_check = sqlx::query_scalar!(
+        "SELECT id FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    match photo_check {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let exp = Utc::now().timestamp() + expires_in;
+
+    let sig = match signed_url::sign(&signing_message(photo_id, claims.user_id, exp, &params)) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("署名失敗: {}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "url": format!(
+            "/photos/{}/image?{}&uid={}&exp={}&sig={}",
+            photo_id,
+            params.canonical(),
+            claims.user_id,
+            exp,
+            sig,
+        ),
+        "expires_at": exp,
+    }))
+}
+
+#[get("/photos/{id}/image")]
+pub async fn get_transformed_image(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<ImageTransformQuery>,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let photo_id = path.into_inner();
+
+    let params = match TransformParams::parse(
+        query.w,
+        query.h,
+        query.fit.as_deref(),
+        query.fmt.as_deref(),
+        query.q,
+    ) {
+        Ok(p) => p,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    if query.exp < Utc::now().timestamp() {
+        return HttpResponse::Forbidden().body("URLの有効期限が切れています");
+    }
+
+    match signed_url::verify(&signing_message(photo_id, query.uid, query.exp, &params), &query.sig) {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Forbidden().body("署名が不正です"),
+        Err(e) => {
+            eprintln!("署名検証失敗: {}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let photo = sqlx::query!(
+        "SELECT
+            image_path,
+            metadata_stripped_at,
+            edit_version
+        FROM
+            photos
+        WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        query.uid,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    let photo = match photo {
+        Ok(Some(p)) => p,
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    // 元画像の差し替え（メタデータ削除など）や編集でも別の値になるようにする
+    let digest = Sha256::digest(format!(
+        "{}|{:?}|{}|{}",
+        photo.image_path,
+        photo.metadata_stripped_at,
+        photo.edit_version,
+        params.canonical(),
+    ));
+    let hash: String = digest.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+    let etag = format!("\"{}\"", hash);
+
+    let not_modified = req
+        .headers()
+        .get(IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"))
+        .unwrap_or(false);
+
+    if not_modified {
+        return HttpResponse::NotModified()
+            .insert_header((ETAG, etag))
+            .insert_header((CACHE_CONTROL, CACHE_CONTROL_VALUE))
+            .finish();
+    }
+
+    let (client, bucket_name, _) = create_s3_client();
+    let cache_key = format!("{}{}.{}", derived_image_prefix(photo_id), hash, params.format.extension());
+
+    let body = match download_object(&client, &bucket_name, &cache_key).await {
+        Ok(cached) => cached,
+        Err(_) => {
+            let original_key = match object_key_from_url(&photo.image_path) {
+                Ok(k) => k,
+                Err(e) => return HttpResponse::InternalServerError().body(e),
+            };
+
+            let operations = sqlx::query_scalar!(
+                "SELECT operation FROM photo_edits WHERE photo_id = $1 ORDER BY position",
+                photo_id
+            )
+            .fetch_all(db_pool.get_ref())
+            .await;
+
+            let edits = match operations {
+                Ok(operations) => parse_edits(operations),
+                Err(e) => {
+                    eprintln!("編集内容の取得失敗: {:?}", e);
+                    return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+                }
+            };
+
+            // 動画は代表フレームを変換する
+            let original = match download_object(&client, &bucket_name, original_key).await {
+                Ok(data) => still_image(original_key, data).await,
+                Err(e) => Err(e),
+            };
+
+            let original = match original {
+                Ok(data) => data,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return HttpResponse::InternalServerError().body("画像の取得に失敗しました");
+                }
+            };
+
+            let transform_params = params.clone();
+            let transformed = tokio::task::spawn_blocking(move || {
+                let img = apply_edits(&decode_image(&original)?, &edits);
+                transform_params.encode(&transform_params.apply(&img))
+            })
+            .await
+            .map_err(|e| format!("画像処理タスクの実行に失敗: {:?}", e))
+            .and_then(|result| result);
+
+            let bytes = match transformed {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    eprintln!("画像変換失敗: {}", e);
+                    return HttpResponse::InternalServerError().body("画像の変換に失敗しました");
+                }
+            };
+
+            // キャッシュへの保存に失敗しても変換結果は返す
+            if let Err(e) = upload_object(&client, &bucket_name, &cache_key, params.format.content_type(), bytes.clone()).await {
+                eprintln!("変換画像のキャッシュ保存失敗: {}", e);
+            }
+
+            bytes
+        }
+    };
+
+    HttpResponse::Ok()
+        .content_type(params.format.content_type())
+        .insert_header((ETAG, etag))
+        .insert_header((CACHE_CONTROL, CACHE_CONTROL_VALUE))
+        .body(body)
+}
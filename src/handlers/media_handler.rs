@@ -0,0 +1,226 @@
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{ACCEPT_RANGES, CACHE_CONTROL, CONTENT_RANGE, CONTENT_TYPE, ETAG, RANGE};
+use actix_web::http::StatusCode;
+use chrono::Utc;
+
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::handlers::image_handler::{DEFAULT_URL_EXPIRES_IN, MAX_URL_EXPIRES_IN};
+use crate::message;
+use crate::models::photo::{MediaStreamQuery, MediaUrlRequest};
+use crate::utils::s3::{create_s3_client, object_key_from_url};
+use crate::utils::signed_url;
+
+const CACHE_CONTROL_VALUE: &str = "private, max-age=3600";
+
+// 画像変換のURLの署名と区別するため先頭に "stream" を付ける
+fn signing_message(photo_id: i32, user_id: i32, exp: i64, variant: &str) -> String {
+    format!("stream:{}:{}:{}:{}", photo_id, user_id, exp, variant)
+}
+
+fn parse_variant(variant: Option<&str>) -> Result<&'static str, HttpResponse> {
+    match variant {
+        None | Some("original") => Ok("original"),
+        Some("preview") => Ok("preview"),
+        Some(other) => Err(HttpResponse::BadRequest().body(format!("不正な variant です: {}", other))),
+    }
+}
+
+// 単一範囲の "bytes=start-end" 形式のみS3にそのまま渡す。複数範囲は全体を返す
+fn single_byte_range(value: &str) -> Option<&str> {
+    let range = value.strip_prefix("bytes=")?;
+    let (start, end) = range.split_once('-')?;
+
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let valid = !(start.is_empty() && end.is_empty()) && is_digits(start) && is_digits(end);
+
+    valid.then_some(value)
+}
+
+// 再生用の署名付きURLを発行する（video タグから直接参照できるよう、取得側はJWT不要）
+#[post("/photos/{id}/stream-url")]
+pub async fn create_stream_url(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<MediaUrlRequest>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_id = path.into_inner();
+
+    let variant = match parse_variant(payload.variant.as_deref()) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    let expires_in = payload.expires_in.unwrap_or(DEFAULT_URL_EXPIRES_IN);
+    if !(1..=MAX_URL_EXPIRES_IN).contains(&expires_in) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "message": format!("expires_in は 1〜{} 秒の範囲で指定してください", MAX_URL_EXPIRES_IN)
+        }));
+    }
+
+    let photo = sqlx::query!(
+        "SELECT preview_path FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    match photo {
+        Ok(Some(photo)) if variant == "preview" && photo.preview_path.is_none() => {
+            return HttpResponse::NotFound().body("プレビューがありません");
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let exp = Utc::now().timestamp() + expires_in;
+
+    let sig = match signed_url::sign(&signing_message(photo_id, claims.user_id, exp, variant)) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("署名失敗: {}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "url": format!(
+            "/photos/{}/stream?variant={}&uid={}&exp={}&sig={}",
+            photo_id,
+            variant,
+            claims.user_id,
+            exp,
+            sig,
+        ),
+        "expires_at": exp,
+    }))
+}
+
+// 動画の再生用。Range リクエストに対応してS3から中継する
+#[get("/photos/{id}/stream")]
+pub async fn stream_media(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<MediaStreamQuery>,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let photo_id = path.into_inner();
+
+    let variant = match parse_variant(query.variant.as_deref()) {
+        Ok(v) => v,
+        Err(resp) => return resp,
+    };
+
+    if query.exp < Utc::now().timestamp() {
+        return HttpResponse::Forbidden().body("URLの有効期限が切れています");
+    }
+
+    match signed_url::verify(&signing_message(photo_id, query.uid, query.exp, variant), &query.sig) {
+        Ok(true) => {}
+        Ok(false) => return HttpResponse::Forbidden().body("署名が不正です"),
+        Err(e) => {
+            eprintln!("署名検証失敗: {}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let photo = sqlx::query!(
+        "SELECT image_path, preview_path FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        query.uid,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    let source = match photo {
+        Ok(Some(photo)) if variant == "preview" => match photo.preview_path {
+            Some(preview_path) => preview_path,
+            None => return HttpResponse::NotFound().body("プレビューがありません"),
+        },
+        Ok(Some(photo)) => photo.image_path,
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let key = match object_key_from_url(&source) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let range = req
+        .headers()
+        .get(RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(single_byte_range);
+
+    let (client, bucket_name, _) = create_s3_client();
+
+    let output = match client
+        .get_object()
+        .bucket(&bucket_name)
+        .key(key)
+        .set_range(range.map(str::to_string))
+        .send()
+        .await
+    {
+        Ok(output) => output,
+        Err(e) => {
+            if e.raw_response().map(|r| r.http().status().as_u16()) == Some(416) {
+                return HttpResponse::RangeNotSatisfiable().body("指定された範囲が不正です");
+            }
+
+            eprintln!("S3取得失敗: {} ({:?})", key, e);
+            return HttpResponse::InternalServerError().body("メディアの取得に失敗しました");
+        }
+    };
+
+    let status = if output.content_range().is_some() { StatusCode::PARTIAL_CONTENT } else { StatusCode::OK };
+
+    let mut response = HttpResponse::build(status);
+    response
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .insert_header((CACHE_CONTROL, CACHE_CONTROL_VALUE))
+        .insert_header((CONTENT_TYPE, output.content_type().unwrap_or("application/octet-stream")))
+        // ストリーミングのボディは chunked で送られ、手動の Content-Length は捨てられるため長さを明示する
+        .no_chunking(output.content_length().max(0) as u64);
+
+    if let Some(content_range) = output.content_range() {
+        response.insert_header((CONTENT_RANGE, content_range));
+    }
+    if let Some(e_tag) = output.e_tag() {
+        response.insert_header((ETAG, e_tag));
+    }
+
+    response.streaming(output.body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_byte_range() {
+        assert_eq!(single_byte_range("bytes=0-1023"), Some("bytes=0-1023"));
+        assert_eq!(single_byte_range("bytes=1024-"), Some("bytes=1024-"));
+        assert_eq!(single_byte_range("bytes=-500"), Some("bytes=-500"));
+        assert_eq!(single_byte_range("bytes=0-1,5-9"), None);
+        assert_eq!(single_byte_range("bytes=-"), None);
+        assert_eq!(single_byte_range("items=0-1"), None);
+    }
+}
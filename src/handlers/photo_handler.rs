@@ -1,9 +1,23 @@
 use std::collections::HashMap;
 
 use actix_web::{get, delete, post, put, web, HttpRequest, HttpResponse, Responder};
+use aws_sdk_s3::Client;
 use serde::Serialize;
-use crate::{handlers::{auth_handler::extract_user_from_jwt, s3_handler::delete_image_from_s3}, models::{photo::{PhotoDeleteRequest, PhotoMoveRequest, PhotoResponse, PhotoSearchRequest, PhotoUpdateRequest, PhotoUploadRequest, PhotoWrapper, TagAddRequest}, tag::AddTagRequest, Tag}, utils::s3::create_s3_client};
+use sqlx::{Postgres, Transaction};
+use crate::{handlers::auth_handler::extract_user_from_jwt, models::{photo::{PhotoCopyRequest, PhotoDetail, PhotoDetailQuery, PhotoDeleteRequest, PhotoMoveRequest, PhotoOrderRequest, PhotoResponse, PhotoSearchRequest, PhotoUpdateRequest, PhotoUploadRequest, PhotoWrapper, TagAddRequest}, tag::AddTagRequest, Tag}};
 use crate::message;
+use crate::handlers::files_handler::{fetch_breadcrumbs, load_photos, photo_sort_order};
+use crate::handlers::folder_handler::has_duplicate_ids;
+use crate::handlers::trash_handler::trash_photos;
+use crate::utils::color::{parse_hex_color, rgb_to_lab};
+use crate::utils::media::preview_key;
+use crate::utils::s3::{copied_object_key, copy_object, create_s3_client, object_key_from_url, public_url};
+use crate::utils::tag_query;
+use crate::workers::job::Job;
+use crate::workers::queue;
+
+const DEFAULT_COLOR_TOLERANCE: f64 = 20.0;
+const MAX_COLOR_TOLERANCE: f64 = 100.0;
 
 #[derive(Debug, Serialize)]
 struct PhotoWithTags {
@@ -11,34 +25,209 @@ struct PhotoWithTags {
     tags: Vec<Tag>,
 }
 
+// 他のユーザーの写真も、存在しない写真と同じく 404 を返す
+#[get("/photos/{id}")]
+pub async fn get_photo(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<PhotoDetailQuery>,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_id = path.into_inner();
+    let (sort, descending) = match photo_sort_order(query.sort.as_deref(), query.order.as_deref(), false) {
+        Ok(sort_order) => sort_order,
+        Err(resp) => return resp,
+    };
+
+    let folder_id = match sqlx::query_scalar!(
+        "SELECT folder_id FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await
+    {
+        Ok(Some(folder_id)) => folder_id,
+        Ok(None) => return HttpResponse::NotFound().body("写真が存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("写真の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching photo");
+        }
+    };
+
+    let photo = match load_photos(db_pool.get_ref(), &[photo_id]).await {
+        Ok(mut photos) if !photos.is_empty() => photos.remove(0),
+        Ok(_) => return HttpResponse::NotFound().body("写真が存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("写真の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching photo");
+        }
+    };
+
+    let breadcrumbs = match fetch_breadcrumbs(db_pool.get_ref(), folder_id).await {
+        Ok(breadcrumbs) => breadcrumbs,
+        Err(e) => {
+            eprintln!("パンくずリストの取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching breadcrumbs");
+        }
+    };
+
+    // 写真一覧と同じ (数値キー, 文字列キー, ID) の並びで、直前・直後の写真を探す
+    let neighbors = sqlx::query!(
+        r#"
+        WITH keyed AS (
+            SELECT
+                photos.id,
+                CASE $3
+                    WHEN 'uploaded_at' THEN (EXTRACT(EPOCH FROM photos.uploaded_at) * 1000000)::BIGINT
+                    WHEN 'taken_at' THEN (EXTRACT(EPOCH FROM COALESCE(photo_metadata.taken_at, photos.uploaded_at)) * 1000000)::BIGINT
+                    WHEN 'size' THEN photos.size_in_bytes
+                    WHEN 'manual' THEN COALESCE(photos.sort_position, 2147483647)::BIGINT
+                    ELSE 0::BIGINT
+                END AS num_key,
+                CASE WHEN $3 = 'name' THEN photos.name ELSE '' END AS text_key
+            FROM
+                photos
+            LEFT JOIN
+                photo_metadata ON photo_metadata.photo_id = photos.id
+            WHERE
+                photos.folder_id = $1
+                AND photos.user_id = $2
+                AND photos.trash_id IS NULL
+        ),
+        current AS (
+            SELECT num_key, text_key, id FROM keyed WHERE id = $4
+        )
+        SELECT
+            (
+                SELECT k.id
+                FROM keyed k, current c
+                WHERE (k.num_key, k.text_key, k.id) < (c.num_key, c.text_key, c.id)
+                ORDER BY k.num_key DESC, k.text_key DESC, k.id DESC
+                LIMIT 1
+            ) AS before_id,
+            (
+                SELECT k.id
+                FROM keyed k, current c
+                WHERE (k.num_key, k.text_key, k.id) > (c.num_key, c.text_key, c.id)
+                ORDER BY k.num_key, k.text_key, k.id
+                LIMIT 1
+            ) AS after_id
+        "#,
+        folder_id,
+        claims.user_id,
+        sort,
+        photo_id,
+    )
+    .fetch_one(db_pool.get_ref())
+    .await;
+
+    let (previous_id, next_id) = match neighbors {
+        Ok(row) if descending => (row.after_id, row.before_id),
+        Ok(row) => (row.before_id, row.after_id),
+        Err(e) => {
+            eprintln!("前後の写真の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching photo");
+        }
+    };
+
+    HttpResponse::Ok().json(PhotoDetail {
+        photo,
+        breadcrumbs,
+        previous_id,
+        next_id,
+    })
+}
+
 #[get("/photos/search")]
 pub async fn search_photos(
     req: HttpRequest,
     db_pool: web::Data<sqlx::PgPool>,
-    payload: web::Json<PhotoSearchRequest>,
+    payload: web::Query<PhotoSearchRequest>,
 ) -> impl Responder {
     let claims = match extract_user_from_jwt(&req) {
         Ok(c) => c,
         Err(resp) => return resp,
     };
 
-    let tag_list: Vec<String> = payload
-        .tags
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .collect();
+    // 検索式を「all をすべて含み none を含まない」条件の OR に展開して SQL に渡す
+    let tag_clauses_json = match payload.tags.as_deref().map(tag_query::compile).transpose() {
+        Ok(json) => json,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    let target_color = match payload.color.as_deref().map(parse_hex_color).transpose() {
+        Ok(color) => color.map(rgb_to_lab),
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+
+    if tag_clauses_json.is_none() && target_color.is_none() {
+        return HttpResponse::BadRequest().body("tags か color のどちらかを指定してください");
+    }
 
+    let tolerance = payload.tolerance.unwrap_or(DEFAULT_COLOR_TOLERANCE);
+    if !(0.0..=MAX_COLOR_TOLERANCE).contains(&tolerance) {
+        return HttpResponse::BadRequest().body(format!("tolerance は 0〜{} の範囲で指定してください", MAX_COLOR_TOLERANCE));
+    }
+
+    // 色の指定がある場合は、パレット中で最も近い色との色差が tolerance 以内の写真を近い順に返す
     let rows = sqlx::query!(
-        "
-        SELECT DISTINCT p.*
+        r#"
+        SELECT
+            p.id,
+            p.name,
+            p.description,
+            p.image_path,
+            p.folder_id,
+            p.width,
+            p.height,
+            p.blurhash,
+            p.lqip,
+            p.dominant_color,
+            p.average_color,
+            p.media_type,
+            nearest.distance AS "distance?"
         FROM photos p
-        JOIN photo_tag_relations ptr ON p.id = ptr.photo_id
-        JOIN tags t ON ptr.tag_id = t.id
+        LEFT JOIN LATERAL (
+            SELECT
+                sqrt(power(pc.l - $3, 2) + power(pc.a - $4, 2) + power(pc.b - $5, 2)) AS distance,
+                pc.weight
+            FROM photo_colors pc
+            WHERE pc.photo_id = p.id
+            ORDER BY distance, pc.weight DESC
+            LIMIT 1
+        ) nearest ON $3::REAL IS NOT NULL
+        LEFT JOIN LATERAL (
+            SELECT COALESCE(ARRAY_AGG(lower(t.tag)), '{}') AS names
+            FROM photo_tag_relations ptr
+            JOIN tags t ON ptr.tag_id = t.id
+            WHERE ptr.photo_id = p.id
+        ) photo_tags ON $2::TEXT IS NOT NULL
         WHERE p.user_id = $1
-        AND t.tag = ANY($2)
-        ",
+        AND p.trash_id IS NULL
+        AND (
+            $2::TEXT IS NULL OR
+            EXISTS (
+                SELECT 1
+                FROM jsonb_array_elements($2::TEXT::JSONB) clause
+                WHERE photo_tags.names @> ARRAY(SELECT jsonb_array_elements_text(clause->'all'))
+                AND NOT photo_tags.names && ARRAY(SELECT jsonb_array_elements_text(clause->'none'))
+            )
+        )
+        AND ($3::REAL IS NULL OR nearest.distance <= $6)
+        ORDER BY nearest.distance NULLS LAST, nearest.weight DESC NULLS LAST, p.id
+        "#,
         claims.user_id,
-        &tag_list
+        tag_clauses_json,
+        target_color.map(|c| c.l),
+        target_color.map(|c| c.a),
+        target_color.map(|c| c.b),
+        tolerance,
     )
     .fetch_all(db_pool.get_ref())
     .await;
@@ -55,6 +244,12 @@ pub async fn search_photos(
                     folder_id: row.folder_id,
                     width: row.width,
                     height: row.height,
+                    blurhash: row.blurhash,
+                    lqip: row.lqip,
+                    dominant_color: row.dominant_color,
+                    average_color: row.average_color,
+                    media_type: row.media_type,
+                    color_distance: row.distance,
                 })
                 .collect();
 
@@ -77,6 +272,13 @@ pub async fn upload_photo(
         Err(resp) => return resp,
     };
 
+    let mut tx = match db_pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message()),
+    };
+
+    // メタデータ削除の指定が無ければユーザー設定を使う
+    // 保存先の指定が無い場合はルートフォルダー。ゴミ箱にない自分のフォルダーのみ指定でき、ゴミ箱に移す処理と重ならないようフォルダーの行をロックする
     let result = sqlx::query!(
         "
         INSERT INTO photos
@@ -86,24 +288,56 @@ pub async fn upload_photo(
                 folder_id,
                 description,
                 image_path,
-                size_in_bytes)
-        VALUES
-            ($1, $2, $3, $4, $5, $6)
+                size_in_bytes,
+                metadata_strip_mode,
+                metadata_strip_target)
+        SELECT
+            $1, $2, $3, $4, $5, $6,
+            COALESCE($7, users.metadata_strip_mode),
+            COALESCE($8, users.metadata_strip_target)
+        FROM
+            users
+        WHERE
+            users.id = $1
+            AND EXISTS (SELECT 1 FROM folders WHERE id = $3 AND user_id = $1 AND trash_id IS NULL FOR SHARE)
+        RETURNING
+            id
         ",
         claims.user_id,
         payload.name.as_deref(),
-        payload.folder_id,
+        payload.folder_id.unwrap_or(claims.root_folder),
         payload.description.as_deref(),
         payload.image_path,
         payload.size_in_bytes,
+        payload.metadata_strip_mode.map(|m| m.as_str()),
+        payload.metadata_strip_target.map(|t| t.as_str()),
     )
-    .execute(db_pool.get_ref())
+    .fetch_optional(&mut *tx)
     .await;
 
-    match result {
-        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
-            "message": message::AppSuccess::UploadedPhoto.message(),
+    // サムネイル等の生成はバックグラウンドで行う。写真の登録と同じトランザクションで積む
+    let result = match result {
+        Ok(None) => return HttpResponse::NotFound().json(serde_json::json!({
+            "message": "フォルダが存在しないか、権限がありません"
         })),
+        Ok(Some(record)) => queue::enqueue(&mut *tx, &Job::ProcessPhoto { photo_id: record.id })
+            .await
+            .map(|_| record),
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(record) => tx.commit().await.map(|_| record),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(record) => {
+            HttpResponse::Ok().json(serde_json::json!({
+                "message": message::AppSuccess::UploadedPhoto.message(),
+                "id": record.id,
+            }))
+        }
         Err(e) => {
             println!("error: {:?}", e);
             HttpResponse::InternalServerError().json(serde_json::json!({
@@ -129,7 +363,7 @@ pub async fn update_photo(
         UPDATE photos
         SET name = COALESCE($1, name),
             description = COALESCE($2, description)
-        WHERE id = $3 AND user_id = $4
+        WHERE id = $3 AND user_id = $4 AND trash_id IS NULL
         RETURNING id, name, description
         ",
         payload.name.as_deref(),
@@ -213,7 +447,7 @@ pub async fn add_tag_to_photo(
 
     // 写真の所有者チェック
     let photo_rows = match sqlx::query!(
-        "SELECT id FROM photos WHERE user_id = $1 AND id = ANY($2)",
+        "SELECT id FROM photos WHERE user_id = $1 AND id = ANY($2) AND trash_id IS NULL",
         claims.user_id,
         &payload.photo_ids
     )
@@ -343,8 +577,9 @@ pub async fn move_photo(
     let result = sqlx::query!(
         "
         UPDATE photos
-        SET folder_id = $1
-        WHERE id = ANY($2) AND user_id = $3
+        SET folder_id = $1, sort_position = NULL
+        WHERE id = ANY($2) AND user_id = $3 AND trash_id IS NULL
+        AND EXISTS (SELECT 1 FROM folders WHERE id = $1 AND user_id = $3 AND trash_id IS NULL FOR SHARE)
         ",
         payload.folder_id,
         &payload.ids,
@@ -374,6 +609,332 @@ pub async fn move_photo(
     }
 }
 
+// 写真をS3のオブジェクトごと複製し、元のID → 複製後のIDの対応を返す
+// S3へのコピーはトランザクション外で確定するため、コピーしたURLを copied_urls に積み、失敗時は呼び出し側で削除する
+pub(crate) async fn copy_photos(
+    tx: &mut Transaction<'_, Postgres>,
+    client: &Client,
+    bucket_name: &str,
+    region: &str,
+    user_id: i32,
+    targets: &[(i32, i32)],
+    copied_urls: &mut Vec<String>,
+) -> Result<HashMap<i32, i32>, String> {
+    let mut id_map = HashMap::new();
+
+    for &(photo_id, folder_id) in targets {
+        let photo = sqlx::query!(
+            "SELECT image_path, preview_path FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+            photo_id,
+            user_id,
+        )
+        .fetch_optional(&mut **tx)
+        .await
+        .map_err(|e| format!("写真取得失敗: {:?}", e))?
+        .ok_or_else(|| format!("写真ID {} が存在しないか、権限がありません", photo_id))?;
+
+        let source_key = object_key_from_url(&photo.image_path)?;
+        let target_key = copied_object_key(source_key);
+        copy_object(client, bucket_name, source_key, &target_key).await?;
+        let image_path = public_url(bucket_name, region, &target_key);
+        copied_urls.push(image_path.clone());
+
+        let preview_path = match &photo.preview_path {
+            Some(preview_path) => {
+                let source_key = object_key_from_url(preview_path)?;
+                let target_key = preview_key(&target_key);
+                copy_object(client, bucket_name, source_key, &target_key).await?;
+                let url = public_url(bucket_name, region, &target_key);
+                copied_urls.push(url.clone());
+                Some(url)
+            }
+            None => None,
+        };
+
+        let new_id = sqlx::query_scalar!(
+            "
+            INSERT INTO photos
+                (
+                    user_id,
+                    name,
+                    folder_id,
+                    description,
+                    image_path,
+                    size_in_bytes,
+                    width,
+                    height,
+                    metadata_strip_mode,
+                    metadata_strip_target,
+                    metadata_stripped_at,
+                    original_width,
+                    original_height,
+                    edit_version,
+                    media_type,
+                    duration_seconds,
+                    codec,
+                    frame_rate,
+                    preview_path)
+            SELECT
+                user_id, name, $2, description, $3, size_in_bytes, width, height,
+                metadata_strip_mode, metadata_strip_target, metadata_stripped_at,
+                original_width, original_height, edit_version,
+                media_type, duration_seconds, codec, frame_rate, $4
+            FROM
+                photos
+            WHERE
+                id = $1
+            RETURNING
+                id
+            ",
+            photo_id,
+            folder_id,
+            image_path,
+            preview_path,
+        )
+        .fetch_one(&mut **tx)
+        .await
+        .map_err(|e| format!("写真の複製失敗: {:?}", e))?;
+
+        // 元画像からメタデータを削除済みの場合は再抽出できないため、抽出済みの内容を引き継ぐ
+        sqlx::query!(
+            "
+            INSERT INTO photo_metadata
+                (photo_id, camera_make, camera_model, lens_model, focal_length, aperture, exposure_time,
+                 iso, taken_at, timezone_offset_minutes, latitude, longitude, altitude)
+            SELECT
+                $2, camera_make, camera_model, lens_model, focal_length, aperture, exposure_time,
+                iso, taken_at, timezone_offset_minutes, latitude, longitude, altitude
+            FROM photo_metadata
+            WHERE photo_id = $1
+            ",
+            photo_id,
+            new_id,
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| format!("メタデータの複製失敗: {:?}", e))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO photo_edits (photo_id, position, operation)
+            SELECT $2, position, operation
+            FROM photo_edits
+            WHERE photo_id = $1
+            ",
+            photo_id,
+            new_id,
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| format!("編集内容の複製失敗: {:?}", e))?;
+
+        sqlx::query!(
+            "
+            INSERT INTO photo_tag_relations (photo_id, tag_id)
+            SELECT $2, tag_id
+            FROM photo_tag_relations
+            WHERE photo_id = $1
+            ",
+            photo_id,
+            new_id,
+        )
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| format!("タグの複製失敗: {:?}", e))?;
+
+        // レンディション・プレースホルダー・ハッシュ等は新しいキーで作り直す
+        queue::enqueue(&mut **tx, &Job::ProcessPhoto { photo_id: new_id })
+            .await
+            .map_err(|e| format!("ジョブの登録失敗: {:?}", e))?;
+
+        id_map.insert(photo_id, new_id);
+    }
+
+    Ok(id_map)
+}
+
+// 複製に失敗した場合、S3にコピー済みのオブジェクトを削除する
+pub(crate) async fn discard_copied_objects(db_pool: &sqlx::PgPool, copied_urls: Vec<String>) {
+    if copied_urls.is_empty() {
+        return;
+    }
+
+    let job = Job::DeleteObjects { image_paths: copied_urls, prefixes: Vec::new() };
+    if let Err(e) = queue::enqueue(db_pool, &job).await {
+        eprintln!("ジョブの登録失敗: {:?}", e);
+    }
+}
+
+#[post("/photos/copy")]
+pub async fn copy_photo(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<PhotoCopyRequest>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut photo_ids = payload.ids.clone();
+    photo_ids.sort_unstable();
+    photo_ids.dedup();
+
+    if photo_ids.is_empty() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "message": "複製する写真IDが指定されていません"
+        }));
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message()),
+    };
+
+    // 複製中に複製先がゴミ箱に移されないよう、フォルダーの行をロックする
+    let folder = sqlx::query_scalar!(
+        "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL FOR SHARE",
+        payload.folder_id,
+        claims.user_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await;
+
+    match folder {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("複製先のフォルダーが存在しないか、権限がありません"),
+        Err(e) => {
+            eprintln!("フォルダ確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let owned = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM photos WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL"#,
+        &photo_ids,
+        claims.user_id,
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match owned {
+        Ok(count) if count == photo_ids.len() as i64 => {}
+        Ok(_) => return HttpResponse::NotFound().json(serde_json::json!({
+            "message": "対象の写真が見つからない、または複製権限がありません"
+        })),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let (client, bucket_name, region) = create_s3_client();
+    let targets: Vec<(i32, i32)> = photo_ids.iter().map(|&id| (id, payload.folder_id)).collect();
+    let mut copied_urls = Vec::new();
+
+    let result = copy_photos(&mut tx, &client, &bucket_name, &region, claims.user_id, &targets, &mut copied_urls).await;
+    let result = match result {
+        Ok(id_map) => tx.commit().await.map(|_| id_map).map_err(|e| format!("トランザクションコミット失敗: {:?}", e)),
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(id_map) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}枚の写真を複製しました", id_map.len()),
+            "photos": id_map,
+        })),
+        Err(e) => {
+            eprintln!("写真の複製失敗: {}", e);
+            discard_copied_objects(db_pool.get_ref(), copied_urls).await;
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "message": "写真の複製に失敗しました"
+            }))
+        }
+    }
+}
+
+// フォルダー内の写真を指定した順に並べる（sort=manual で取得した場合の順番）
+#[put("/photos/order")]
+pub async fn reorder_photos(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<PhotoOrderRequest>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if has_duplicate_ids(&payload.ids) {
+        return HttpResponse::BadRequest().body("写真IDが重複しています");
+    }
+
+    let mut tx = match db_pool.begin().await {
+        Ok(t) => t,
+        Err(_) => return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message()),
+    };
+
+    let in_folder = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) AS "count!" FROM photos WHERE id = ANY($1) AND folder_id = $2 AND user_id = $3 AND trash_id IS NULL"#,
+        &payload.ids,
+        payload.folder_id,
+        claims.user_id,
+    )
+    .fetch_one(&mut *tx)
+    .await;
+
+    match in_folder {
+        Ok(count) if count == payload.ids.len() as i64 => {}
+        Ok(_) => return HttpResponse::BadRequest().body("指定したフォルダーにない写真が含まれています"),
+        Err(e) => {
+            eprintln!("写真確認失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    }
+
+    let cleared = sqlx::query!(
+        "UPDATE photos SET sort_position = NULL WHERE folder_id = $1 AND user_id = $2 AND NOT (id = ANY($3))",
+        payload.folder_id,
+        claims.user_id,
+        &payload.ids,
+    )
+    .execute(&mut *tx)
+    .await;
+
+    let result = match cleared {
+        Ok(_) => sqlx::query!(
+            "
+            UPDATE photos p
+            SET sort_position = o.position::INTEGER
+            FROM UNNEST($1::INTEGER[]) WITH ORDINALITY AS o(id, position)
+            WHERE p.id = o.id
+            ",
+            &payload.ids,
+        )
+        .execute(&mut *tx)
+        .await,
+        Err(e) => Err(e),
+    };
+
+    let result = match result {
+        Ok(_) => tx.commit().await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": format!("{}枚の写真を並べ替えました", payload.ids.len()),
+        })),
+        Err(e) => {
+            eprintln!("写真の並べ替え失敗: {:?}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "message": "写真の並べ替えに失敗しました"
+            }))
+        }
+    }
+}
+
+// ゴミ箱に移す。完全な削除は /trash で行う
 #[delete("/photos")]
 pub async fn delete_photo(
     req: HttpRequest,
@@ -396,89 +957,155 @@ pub async fn delete_photo(
         Err(_) => return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message()),
     };
 
-    // S3削除対象の画像URLを取得
-    let rows = sqlx::query!(
-        "
-        SELECT image_path
-        FROM photos
-        WHERE id = ANY($1) AND user_id = $2
-        ",
-        &photo_ids[..],
-        claims.user_id
-    )
-    .fetch_all(&mut *tx)
-    .await;
+    let trashed = match trash_photos(&mut tx, claims.user_id, photo_ids).await {
+        Ok(count) => count,
+        Err(e) => {
+            eprintln!("写真をゴミ箱に移せませんでした: {:?}", e);
+            return HttpResponse::InternalServerError().body("データベース削除失敗");
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("トランザクションコミット失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body("トランザクションコミット失敗");
+    }
 
-    let filenames = match rows {
-        Ok(rows) => rows.into_iter().map(|row| row.image_path).collect::<Vec<String>>(),
-        Err(_) => return HttpResponse::InternalServerError().body("画像情報の取得失敗"),
+    if trashed == 0 {
+        return HttpResponse::NotFound().body("対象の写真が見つからない、または削除権限がありません");
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": message::AppSuccess::Deleted(message::FileType::Photo).message(),
+    }))
+}
+
+// メタデータ未抽出の既存写真をまとめて処理待ちに登録する
+#[post("/photos/metadata/backfill")]
+pub async fn backfill_photo_metadata(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
     };
 
-    // 中間テーブルのレコード削除
-    let delete_relations_result = sqlx::query!(
+    let photo_ids = sqlx::query_scalar!(
         "
-        DELETE FROM photo_tag_relations
-        WHERE photo_id = ANY($1)
+        SELECT p.id
+        FROM photos p
+        LEFT JOIN photo_metadata pm ON pm.photo_id = p.id
+        WHERE p.user_id = $1 AND pm.photo_id IS NULL AND p.trash_id IS NULL
         ",
-        &photo_ids[..],
+        claims.user_id,
     )
-    .execute(&mut *tx)
+    .fetch_all(db_pool.get_ref())
     .await;
 
-    if let Err(_) = delete_relations_result {
-        return HttpResponse::InternalServerError().body("タグ関連データの削除に失敗しました");
+    let photo_ids = match photo_ids {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("バックフィル対象の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let jobs: Vec<Job> = photo_ids.iter().map(|&photo_id| Job::ExtractMetadata { photo_id }).collect();
+
+    match queue::enqueue_many(db_pool.get_ref(), &jobs).await {
+        Ok(count) => {
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": format!("{}枚の写真のメタデータ抽出を登録しました", count),
+                "count": count,
+            }))
+        }
+        Err(e) => {
+            eprintln!("ジョブの登録失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
     }
+}
+
+// プレースホルダー未計算の既存写真をまとめて処理待ちに登録する
+#[post("/photos/placeholders/backfill")]
+pub async fn backfill_photo_placeholders(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_ids = sqlx::query_scalar!(
+        "SELECT id FROM photos WHERE user_id = $1 AND blurhash IS NULL AND trash_id IS NULL",
+        claims.user_id,
+    )
+    .fetch_all(db_pool.get_ref())
+    .await;
 
-    // S3画像削除
-    let mut delete_errors = Vec::new();
+    let photo_ids = match photo_ids {
+        Ok(ids) => ids,
+        Err(e) => {
+            eprintln!("バックフィル対象の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
 
-    let (client, bucket_name, _) = create_s3_client();
+    let jobs: Vec<Job> = photo_ids.iter().map(|&photo_id| Job::ComputePlaceholders { photo_id }).collect();
 
-    for url in filenames {
-        if let Err(e) = delete_image_from_s3(&client, &bucket_name, &url).await {
-            delete_errors.push(e);
+    match queue::enqueue_many(db_pool.get_ref(), &jobs).await {
+        Ok(count) => {
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": format!("{}枚の写真のプレースホルダー計算を登録しました", count),
+                "count": count,
+            }))
+        }
+        Err(e) => {
+            eprintln!("ジョブの登録失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
         }
     }
+}
 
-    // photos テーブルから削除
-    let result = sqlx::query!(
-        "
-        DELETE FROM photos
-        WHERE id = ANY($1) AND user_id = $2
-        ",
-        &photo_ids[..],
-        claims.user_id
+// 知覚ハッシュ未計算の既存写真をまとめて処理待ちに登録する
+#[post("/photos/hashes/backfill")]
+pub async fn backfill_photo_hashes(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_ids = sqlx::query_scalar!(
+        "SELECT id FROM photos WHERE user_id = $1 AND phash IS NULL AND trash_id IS NULL",
+        claims.user_id,
     )
-    .execute(&mut *tx)
+    .fetch_all(db_pool.get_ref())
     .await;
 
-    match tx.commit().await {
-        Ok(_) => (),
+    let photo_ids = match photo_ids {
+        Ok(ids) => ids,
         Err(e) => {
-            println!("トランザクションコミット失敗: {:?}", e);
-            return HttpResponse::InternalServerError().body("トランザクションコミット失敗");
-        },
-    }
+            eprintln!("バックフィル対象の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
 
-    match result {
-        Ok(res) => {
-            if res.rows_affected() == 0 {
-                HttpResponse::NotFound().body("対象の写真が見つからない、または削除権限がありません")
-            } else {
-                if !delete_errors.is_empty() {
-                    println!("{:?}", delete_errors);
-
-                    HttpResponse::InternalServerError().body(delete_errors.join(", "))
-                } else {
-                    HttpResponse::Ok().json(serde_json::json!({
-                        "message": message::AppSuccess::Deleted(message::FileType::Photo).message(),
-                    }))
-                }
-            }
-        },
+    let jobs: Vec<Job> = photo_ids.iter().map(|&photo_id| Job::ComputePerceptualHash { photo_id }).collect();
+
+    match queue::enqueue_many(db_pool.get_ref(), &jobs).await {
+        Ok(count) => {
+            HttpResponse::Accepted().json(serde_json::json!({
+                "message": format!("{}枚の写真のハッシュ計算を登録しました", count),
+                "count": count,
+            }))
+        }
         Err(e) => {
-            println!("{:?}", e);
-            HttpResponse::InternalServerError().body("データベース削除失敗")
-        },
+            eprintln!("ジョブの登録失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
     }
 }
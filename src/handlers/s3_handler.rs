@@ -6,7 +6,7 @@ use aws_sdk_s3::presigning::PresigningConfig;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use crate::utils::s3::create_s3_client;
+use crate::utils::s3::{create_s3_client, object_key_from_url, public_url};
 
 #[derive(Deserialize)]
 pub struct PresignRequest {
@@ -37,7 +37,7 @@ pub async fn generate_presigned_url(
         Ok(presigned_request) => {
             HttpResponse::Ok().json(serde_json::json!({
                 "presigned_url": presigned_request.uri().to_string(),
-                "public_url": format!("https://{}.s3.{}.amazonaws.com/{}", bucket_name, region, filename)
+                "public_url": public_url(&bucket_name, &region, &filename)
             }))
         },
         Err(e) => HttpResponse::InternalServerError().body(format!("Failed to generate presigned URL: {}", e)),
@@ -49,10 +49,7 @@ pub async fn delete_image_from_s3(
     bucket: &str,
     image_url: &str
 ) -> Result<(), String> {
-    let key = image_url
-        .rsplit('/')
-        .next()
-        .ok_or_else(|| format!("無効なURL形式: {}", image_url))?;
+    let key = object_key_from_url(image_url)?;
 
     match client
         .delete_object()
@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::message;
+use crate::models::photo::{DuplicateCluster, DuplicateQuery, PhotoMatch, SimilarQuery};
+use crate::utils::perceptual_hash::{band_probes, hamming_distance, BAND_COUNT};
+
+const DEFAULT_DUPLICATE_THRESHOLD: u32 = 4;
+// 区間インデックスで取りこぼしなく探せる上限（区間数 - 1）
+const MAX_DUPLICATE_THRESHOLD: u32 = BAND_COUNT as u32 - 1;
+const DEFAULT_SIMILAR_THRESHOLD: u32 = 12;
+const MAX_SIMILAR_THRESHOLD: u32 = 24;
+const DEFAULT_SIMILAR_LIMIT: i64 = 20;
+const MAX_SIMILAR_LIMIT: i64 = 100;
+
+fn find_root(parents: &mut HashMap<i32, i32>, id: i32) -> i32 {
+    let parent = *parents.entry(id).or_insert(id);
+    if parent == id {
+        return id;
+    }
+
+    let root = find_root(parents, parent);
+    parents.insert(id, root);
+    root
+}
+
+// ほぼ同一の写真をまとめて返す
+#[get("/photos/duplicates")]
+pub async fn get_duplicate_photos(
+    req: HttpRequest,
+    query: web::Query<DuplicateQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let threshold = query.threshold.unwrap_or(DEFAULT_DUPLICATE_THRESHOLD);
+    if threshold > MAX_DUPLICATE_THRESHOLD {
+        return HttpResponse::BadRequest().body(format!("threshold は {} 以下で指定してください", MAX_DUPLICATE_THRESHOLD));
+    }
+
+    // 同じ区間の値を持つ写真同士だけを候補にしてから距離を計算する
+    let pairs = sqlx::query!(
+        "
+        SELECT DISTINCT
+            a.photo_id AS a_id,
+            b.photo_id AS b_id
+        FROM
+            photo_hash_bands a
+        INNER JOIN
+            photo_hash_bands b
+            ON b.user_id = a.user_id
+            AND b.band = a.band
+            AND b.value = a.value
+            AND b.photo_id > a.photo_id
+        INNER JOIN photos pa ON pa.id = a.photo_id
+        INNER JOIN photos pb ON pb.id = b.photo_id
+        WHERE
+            a.user_id = $1 AND
+            pa.trash_id IS NULL AND
+            pb.trash_id IS NULL AND
+            bit_count(int8send(pa.phash # pb.phash)) <= $2
+        ",
+        claims.user_id,
+        threshold as i64,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    let pairs = match pairs {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("重複候補の取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let mut parents: HashMap<i32, i32> = HashMap::new();
+    for pair in &pairs {
+        let a = find_root(&mut parents, pair.a_id);
+        let b = find_root(&mut parents, pair.b_id);
+        if a != b {
+            parents.insert(a.max(b), a.min(b));
+        }
+    }
+
+    let photo_ids: Vec<i32> = parents.keys().copied().collect();
+
+    let rows = sqlx::query!(
+        "SELECT
+            id,
+            name,
+            folder_id,
+            image_path,
+            width,
+            height,
+            size_in_bytes,
+            phash
+        FROM
+            photos
+        WHERE
+            id = ANY($1) AND
+            user_id = $2",
+        &photo_ids,
+        claims.user_id,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    let rows = match rows {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    let mut groups: HashMap<i32, Vec<_>> = HashMap::new();
+    for row in rows {
+        let root = find_root(&mut parents, row.id);
+        groups.entry(root).or_default().push(row);
+    }
+
+    let mut clusters: Vec<DuplicateCluster> = groups
+        .into_values()
+        .map(|mut rows| {
+            rows.sort_by_key(|r| std::cmp::Reverse(((r.width as i64) * (r.height as i64), r.size_in_bytes)));
+            let best_hash = rows[0].phash.unwrap_or_default();
+
+            DuplicateCluster {
+                photos: rows
+                    .into_iter()
+                    .map(|r| PhotoMatch {
+                        distance: hamming_distance(best_hash, r.phash.unwrap_or_default()),
+                        id: r.id,
+                        name: r.name,
+                        folder_id: r.folder_id,
+                        image_path: r.image_path,
+                        width: r.width,
+                        height: r.height,
+                        size_in_bytes: r.size_in_bytes,
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    clusters.sort_by_key(|c| (std::cmp::Reverse(c.photos.len()), c.photos[0].id));
+
+    HttpResponse::Ok().json(serde_json::json!({ "data": clusters }))
+}
+
+// 見た目が似ている写真を距離の近い順に返す
+#[get("/photos/{id}/similar")]
+pub async fn get_similar_photos(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<SimilarQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let photo_id = path.into_inner();
+
+    let threshold = query.threshold.unwrap_or(DEFAULT_SIMILAR_THRESHOLD);
+    if threshold > MAX_SIMILAR_THRESHOLD {
+        return HttpResponse::BadRequest().body(format!("threshold は {} 以下で指定してください", MAX_SIMILAR_THRESHOLD));
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_SIMILAR_LIMIT).clamp(1, MAX_SIMILAR_LIMIT);
+
+    let base = sqlx::query_scalar!(
+        "SELECT phash FROM photos WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+        photo_id,
+        claims.user_id,
+    )
+    .fetch_optional(db.get_ref())
+    .await;
+
+    let base_hash = match base {
+        Ok(Some(Some(hash))) => hash,
+        Ok(Some(None)) => return HttpResponse::Conflict().body("この写真のハッシュはまだ計算されていません"),
+        Ok(None) => return HttpResponse::NotFound().body("写真が見つかりません"),
+        Err(e) => {
+            eprintln!("写真取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+        }
+    };
+
+    // 近い区間の値を持つ写真だけを候補にしてから距離を計算する
+    let (bands, values) = band_probes(base_hash, threshold);
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            id,
+            name,
+            folder_id,
+            image_path,
+            width,
+            height,
+            size_in_bytes,
+            bit_count(int8send(phash # $2)) AS "distance!"
+        FROM
+            photos
+        WHERE
+            id IN (
+                SELECT b.photo_id
+                FROM photo_hash_bands b
+                INNER JOIN UNNEST($6::SMALLINT[], $7::SMALLINT[]) AS t(band, value)
+                    ON b.band = t.band AND b.value = t.value
+                WHERE b.user_id = $1
+            ) AND
+            user_id = $1 AND
+            id <> $3 AND
+            trash_id IS NULL AND
+            phash IS NOT NULL AND
+            bit_count(int8send(phash # $2)) <= $4
+        ORDER BY
+            bit_count(int8send(phash # $2)), id
+        LIMIT $5
+        "#,
+        claims.user_id,
+        base_hash,
+        photo_id,
+        threshold as i64,
+        limit,
+        &bands,
+        &values,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match rows {
+        Ok(rows) => {
+            let photos: Vec<PhotoMatch> = rows
+                .into_iter()
+                .map(|r| PhotoMatch {
+                    id: r.id,
+                    name: r.name,
+                    folder_id: r.folder_id,
+                    image_path: r.image_path,
+                    width: r.width,
+                    height: r.height,
+                    size_in_bytes: r.size_in_bytes,
+                    distance: r.distance as u32,
+                })
+                .collect();
+
+            HttpResponse::Ok().json(serde_json::json!({ "data": photos }))
+        }
+        Err(e) => {
+            eprintln!("類似写真の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message())
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use sqlx::PgPool;
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::handlers::files_handler::load_smart_folder_contents;
+use crate::handlers::folder_handler::is_unique_violation;
+use crate::message;
+use crate::models::photo::PhotoListQuery;
+use crate::models::smart_folder::{
+    SmartFolder, SmartFolderCreateRequest, SmartFolderCriteria, SmartFolderDeleteRequest, SmartFolderUpdateRequest,
+};
+use crate::utils::tag_query;
+
+fn smart_folder_name_conflict() -> HttpResponse {
+    HttpResponse::Conflict().body("同じ名前のスマートフォルダーが既に存在します")
+}
+
+fn smart_folder_not_found() -> HttpResponse {
+    HttpResponse::NotFound().body("スマートフォルダーが存在しないか、権限がありません")
+}
+
+fn validate_smart_folder_name(name: &str) -> Result<(), HttpResponse> {
+    if name.trim().is_empty() {
+        return Err(HttpResponse::BadRequest().body("スマートフォルダー名を入力してください"));
+    }
+    Ok(())
+}
+
+// 保存する前に、開いたときにエラーになる条件を弾く
+async fn validate_criteria(db: &PgPool, user_id: i32, criteria: &SmartFolderCriteria) -> Result<(), HttpResponse> {
+    if let Some(tags) = criteria.tags.as_deref() {
+        tag_query::compile(tags).map_err(|e| HttpResponse::BadRequest().body(e))?;
+    }
+
+    if criteria.q.as_deref().is_some_and(|q| q.trim().is_empty()) {
+        return Err(HttpResponse::BadRequest().body("q が空です"));
+    }
+
+    let ranges = [
+        (criteria.uploaded_from, criteria.uploaded_to),
+        (criteria.taken_from, criteria.taken_to),
+    ];
+    if ranges.iter().any(|&(from, to)| matches!((from, to), (Some(from), Some(to)) if from >= to)) {
+        return Err(HttpResponse::BadRequest().body("期間の開始は終了より前にしてください"));
+    }
+
+    if let Some(folder_id) = criteria.folder_id {
+        let folder = sqlx::query_scalar!(
+            "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+            folder_id,
+            user_id,
+        )
+        .fetch_optional(db)
+        .await;
+
+        match folder {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(HttpResponse::NotFound().body("フォルダが存在しないか、権限がありません")),
+            Err(e) => {
+                eprintln!("フォルダ確認失敗: {:?}", e);
+                return Err(HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// ピン留めしたものを先頭に、名前順で返す。smart_folder_id を指定した場合はその1件だけ
+pub(crate) async fn fetch_smart_folders(
+    db: &PgPool,
+    user_id: i32,
+    smart_folder_id: Option<i32>,
+) -> Result<Vec<SmartFolder>, sqlx::Error> {
+    let rows = sqlx::query!(
+        "SELECT
+            id,
+            name,
+            pinned,
+            tags,
+            q,
+            uploaded_from,
+            uploaded_to,
+            taken_from,
+            taken_to,
+            folder_id,
+            scope_deleted,
+            created_at
+        FROM
+            smart_folders
+        WHERE
+            user_id = $1
+            AND ($2::INTEGER IS NULL OR id = $2)
+        ORDER BY
+            pinned DESC,
+            name,
+            id",
+        user_id,
+        smart_folder_id,
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| SmartFolder {
+        id: row.id,
+        name: row.name,
+        pinned: row.pinned,
+        criteria: SmartFolderCriteria {
+            tags: row.tags,
+            q: row.q,
+            uploaded_from: row.uploaded_from,
+            uploaded_to: row.uploaded_to,
+            taken_from: row.taken_from,
+            taken_to: row.taken_to,
+            folder_id: row.folder_id,
+        },
+        scope_deleted: row.scope_deleted,
+        created_at: row.created_at,
+    }).collect())
+}
+
+#[get("/smart-folders")]
+pub async fn get_smart_folders(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    match fetch_smart_folders(db.get_ref(), claims.user_id, None).await {
+        Ok(smart_folders) => HttpResponse::Ok().json(serde_json::json!({ "data": smart_folders })),
+        Err(e) => {
+            eprintln!("スマートフォルダー一覧の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("Error fetching smart folders")
+        }
+    }
+}
+
+// 保存した条件で写真を検索し、フォルダーを開いたときと同じ形で返す
+// sort / order / limit / cursor などは通常のフォルダーと同じく指定できる。保存した条件はクエリより優先する
+#[get("/smart-folders/{smart_folder_id}")]
+pub async fn get_smart_folder_contents(
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<PhotoListQuery>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let smart_folder = match fetch_smart_folders(db.get_ref(), claims.user_id, Some(path.into_inner())).await {
+        Ok(mut rows) if !rows.is_empty() => rows.remove(0),
+        Ok(_) => return smart_folder_not_found(),
+        Err(e) => {
+            eprintln!("スマートフォルダーの取得失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("Error fetching smart folder");
+        }
+    };
+
+    match load_smart_folder_contents(db.get_ref(), claims.user_id, claims.root_folder, smart_folder, query.into_inner()).await {
+        Ok(contents) => HttpResponse::Ok().json(contents),
+        Err(resp) => resp,
+    }
+}
+
+#[post("/smart-folders")]
+pub async fn create_smart_folder(
+    req: HttpRequest,
+    payload: web::Json<SmartFolderCreateRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Err(resp) = validate_smart_folder_name(&payload.name) {
+        return resp;
+    }
+    if let Err(resp) = validate_criteria(db.get_ref(), claims.user_id, &payload.criteria).await {
+        return resp;
+    }
+
+    let criteria = &payload.criteria;
+    let result = sqlx::query_scalar!(
+        "INSERT INTO smart_folders
+            (user_id, name, pinned, tags, q, uploaded_from, uploaded_to, taken_from, taken_to, folder_id)
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING
+            id",
+        claims.user_id,
+        payload.name.trim(),
+        payload.pinned,
+        criteria.tags,
+        criteria.q,
+        criteria.uploaded_from,
+        criteria.uploaded_to,
+        criteria.taken_from,
+        criteria.taken_to,
+        criteria.folder_id,
+    )
+    .fetch_one(db.get_ref())
+    .await;
+
+    match result {
+        Ok(id) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "スマートフォルダーを作成しました。",
+            "id": id,
+        })),
+        Err(e) if is_unique_violation(&e) => smart_folder_name_conflict(),
+        Err(e) => {
+            eprintln!("スマートフォルダー作成エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("スマートフォルダーの作成に失敗しました")
+        }
+    }
+}
+
+// 名前の変更・ピン留め・検索条件の変更
+#[put("/smart-folders")]
+pub async fn update_smart_folder(
+    req: HttpRequest,
+    payload: web::Json<SmartFolderUpdateRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if let Some(name) = payload.name.as_deref() {
+        if let Err(resp) = validate_smart_folder_name(name) {
+            return resp;
+        }
+    }
+    if let Some(criteria) = payload.criteria.as_ref() {
+        if let Err(resp) = validate_criteria(db.get_ref(), claims.user_id, criteria).await {
+            return resp;
+        }
+    }
+
+    let criteria = payload.criteria.clone().unwrap_or_default();
+    let result = sqlx::query_scalar!(
+        "UPDATE smart_folders
+        SET
+            name = COALESCE($3, name),
+            pinned = COALESCE($4, pinned),
+            tags = CASE WHEN $5 THEN $6 ELSE tags END,
+            q = CASE WHEN $5 THEN $7 ELSE q END,
+            uploaded_from = CASE WHEN $5 THEN $8 ELSE uploaded_from END,
+            uploaded_to = CASE WHEN $5 THEN $9 ELSE uploaded_to END,
+            taken_from = CASE WHEN $5 THEN $10 ELSE taken_from END,
+            taken_to = CASE WHEN $5 THEN $11 ELSE taken_to END,
+            folder_id = CASE WHEN $5 THEN $12 ELSE folder_id END,
+            scope_deleted = scope_deleted AND NOT $5
+        WHERE
+            id = $1 AND user_id = $2
+        RETURNING
+            id",
+        payload.id,
+        claims.user_id,
+        payload.name.as_deref().map(str::trim),
+        payload.pinned,
+        payload.criteria.is_some(),
+        criteria.tags,
+        criteria.q,
+        criteria.uploaded_from,
+        criteria.uploaded_to,
+        criteria.taken_from,
+        criteria.taken_to,
+        criteria.folder_id,
+    )
+    .fetch_optional(db.get_ref())
+    .await;
+
+    match result {
+        Ok(Some(_)) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Updated(message::FileType::SmartFolder).message(),
+        })),
+        Ok(None) => smart_folder_not_found(),
+        Err(e) if is_unique_violation(&e) => smart_folder_name_conflict(),
+        Err(e) => {
+            eprintln!("スマートフォルダー更新エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("スマートフォルダーの更新に失敗しました")
+        }
+    }
+}
+
+// スマートフォルダーを削除しても写真は削除しない
+#[delete("/smart-folders")]
+pub async fn delete_smart_folder(
+    req: HttpRequest,
+    payload: web::Json<SmartFolderDeleteRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if payload.ids.is_empty() {
+        return HttpResponse::BadRequest().body("削除するスマートフォルダーIDが指定されていません");
+    }
+
+    let result = sqlx::query_scalar!(
+        "DELETE FROM smart_folders WHERE id = ANY($1) AND user_id = $2 RETURNING id",
+        &payload.ids,
+        claims.user_id,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    match result {
+        Ok(ids) if ids.is_empty() => smart_folder_not_found(),
+        Ok(ids) => HttpResponse::Ok().json(serde_json::json!({
+            "message": message::AppSuccess::Deleted(message::FileType::SmartFolder).message(),
+            "deleted_ids": ids,
+        })),
+        Err(e) => {
+            eprintln!("スマートフォルダー削除エラー: {:?}", e);
+            HttpResponse::InternalServerError().body(message::AppError::DeleteFailed(message::FileType::SmartFolder).message())
+        }
+    }
+}
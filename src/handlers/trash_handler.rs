@@ -0,0 +1,611 @@
+use std::env;
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use sqlx::{PgPool, Postgres, Transaction};
+use time::Duration;
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::handlers::files_handler::walk_folder_path;
+use crate::handlers::folder_handler::{available_folder_name, lock_folder_tree};
+use crate::handlers::image_handler::derived_image_prefix;
+use crate::message;
+use crate::models::trash::{TrashEntry, TrashRequest};
+use crate::workers::job::Job;
+use crate::workers::queue;
+
+const DEFAULT_TRASH_RETENTION_DAYS: i32 = 30;
+
+// ゴミ箱に入れてから完全に削除するまでの日数。TRASH_RETENTION_DAYS で変更できる
+pub fn trash_retention_days() -> i32 {
+    env::var("TRASH_RETENTION_DAYS")
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|&d| d > 0)
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+}
+
+// ゴミ箱の写真を表紙にしているフォルダー・アルバムは、表紙の指定を解除する（一番新しい写真・先頭の写真に戻る）
+async fn clear_trashed_covers(tx: &mut Transaction<'_, Postgres>, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE folders f
+        SET cover_photo_id = NULL
+        FROM photos p
+        WHERE p.id = f.cover_photo_id
+        AND p.trash_id IS NOT NULL
+        AND f.user_id = $1",
+        user_id,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE albums a
+        SET cover_photo_id = NULL
+        FROM photos p
+        WHERE p.id = a.cover_photo_id
+        AND p.trash_id IS NOT NULL
+        AND a.user_id = $1",
+        user_id,
+    )
+    .execute(&mut **tx)
+    .await
+    .map(|_| ())
+}
+
+// 写真をゴミ箱に移す。既にゴミ箱にある写真と他のユーザーの写真は無視し、移した枚数を返す
+pub(crate) async fn trash_photos(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    photo_ids: &[i32],
+) -> Result<usize, sqlx::Error> {
+    let trashed = sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE targets AS (
+            SELECT id, folder_id
+            FROM photos
+            WHERE id = ANY($1) AND user_id = $2 AND trash_id IS NULL
+        ),
+        walk AS (
+            SELECT t.id AS photo_id, f.name, f.parent_id, 0 AS depth
+            FROM targets t
+            INNER JOIN folders f ON f.id = t.folder_id
+            UNION ALL
+            SELECT w.photo_id, f.name, f.parent_id, w.depth + 1
+            FROM walk w
+            INNER JOIN folders f ON f.id = w.parent_id
+        ),
+        paths AS (
+            SELECT photo_id, ARRAY_AGG(name ORDER BY depth DESC) FILTER (WHERE parent_id IS NOT NULL) AS names
+            FROM walk
+            GROUP BY photo_id
+        ),
+        entries AS (
+            INSERT INTO trash_entries (user_id, photo_id, original_parent_id, original_path)
+            SELECT $2, t.id, t.folder_id, COALESCE(p.names, '{}')
+            FROM targets t
+            LEFT JOIN paths p ON p.photo_id = t.id
+            RETURNING id, photo_id
+        )
+        UPDATE photos
+        SET trash_id = entries.id
+        FROM entries
+        WHERE photos.id = entries.photo_id
+        RETURNING photos.id
+        "#,
+        photo_ids,
+        user_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    clear_trashed_covers(tx, user_id).await?;
+
+    Ok(trashed.len())
+}
+
+// フォルダーを配下のフォルダー・写真ごとゴミ箱に移し、(フォルダー数, 写真の枚数) を返す
+// 呼び出し側でフォルダー構成をロックし、所有者とルートフォルダーでないことを確認しておく
+// 選択したフォルダーの中に選択した別のフォルダーがある場合は、外側のフォルダーにまとめる
+pub(crate) async fn trash_folders(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    folder_ids: &[i32],
+) -> Result<(usize, usize), sqlx::Error> {
+    let ordered = sqlx::query_scalar!(
+        r#"
+        WITH RECURSIVE up AS (
+            SELECT id AS start_id, parent_id, 0 AS depth
+            FROM folders
+            WHERE id = ANY($1)
+            UNION ALL
+            SELECT u.start_id, f.parent_id, u.depth + 1
+            FROM up u
+            INNER JOIN folders f ON f.id = u.parent_id
+        )
+        SELECT start_id AS "id!"
+        FROM up
+        GROUP BY start_id
+        ORDER BY MAX(depth), start_id
+        "#,
+        folder_ids,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut folder_count = 0;
+    let mut photo_count = 0;
+
+    for folder_id in ordered {
+        // 外側のフォルダーと一緒にゴミ箱に移した場合は None
+        let entry_id = sqlx::query_scalar!(
+            r#"
+            WITH RECURSIVE walk AS (
+                SELECT f.name, f.parent_id, 0 AS depth
+                FROM folders f
+                WHERE f.id = (SELECT parent_id FROM folders WHERE id = $1)
+                UNION ALL
+                SELECT f.name, f.parent_id, w.depth + 1
+                FROM walk w
+                INNER JOIN folders f ON f.id = w.parent_id
+            )
+            INSERT INTO trash_entries (user_id, folder_id, original_parent_id, original_path)
+            SELECT
+                $2,
+                f.id,
+                f.parent_id,
+                COALESCE((SELECT ARRAY_AGG(name ORDER BY depth DESC) FILTER (WHERE parent_id IS NOT NULL) FROM walk), '{}')
+            FROM folders f
+            WHERE f.id = $1 AND f.user_id = $2 AND f.trash_id IS NULL
+            RETURNING id
+            "#,
+            folder_id,
+            user_id,
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let Some(entry_id) = entry_id else {
+            continue;
+        };
+
+        // 先に個別にゴミ箱に移したフォルダー・写真は、それぞれの項目のまま残す
+        let subtree = sqlx::query_scalar!(
+            r#"
+            WITH RECURSIVE subtree AS (
+                SELECT id FROM folders WHERE id = $1
+                UNION ALL
+                SELECT f.id
+                FROM folders f
+                INNER JOIN subtree s ON f.parent_id = s.id
+                WHERE f.trash_id IS NULL
+            )
+            UPDATE folders
+            SET trash_id = $2
+            WHERE id IN (SELECT id FROM subtree)
+            RETURNING id
+            "#,
+            folder_id,
+            entry_id,
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        let photos = sqlx::query!(
+            "UPDATE photos SET trash_id = $1 WHERE folder_id = ANY($2) AND trash_id IS NULL",
+            entry_id,
+            &subtree,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        folder_count += subtree.len();
+        photo_count += photos.rows_affected() as usize;
+    }
+
+    clear_trashed_covers(tx, user_id).await?;
+
+    Ok((folder_count, photo_count))
+}
+
+// ゴミ箱の項目を完全に削除し、(フォルダー数, 写真の枚数) を返す。S3 の画像はコミット後にジョブで削除する
+// フォルダーの項目は、配下から個別にゴミ箱に移したフォルダー・写真もあわせて削除する
+// 呼び出し側でフォルダー構成をロックしておく
+pub(crate) async fn purge_trash_entries(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    entry_ids: &[i32],
+) -> Result<(usize, usize), sqlx::Error> {
+    let entries = sqlx::query!(
+        "SELECT photo_id, folder_id FROM trash_entries WHERE id = ANY($1) AND user_id = $2",
+        entry_ids,
+        user_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let root_folder_ids: Vec<i32> = entries.iter().filter_map(|e| e.folder_id).collect();
+    let entry_photo_ids: Vec<i32> = entries.iter().filter_map(|e| e.photo_id).collect();
+
+    // 配下のフォルダーを深さ付きで取得し、深いものから順に削除する
+    // ゴミ箱にないものは削除しない（残っている場合は親フォルダーを削除できずにエラーになる）
+    let subtree = sqlx::query!(
+        r#"
+        WITH RECURSIVE all_folders AS (
+            SELECT id, 0 AS depth FROM folders WHERE id = ANY($1) AND trash_id IS NOT NULL
+            UNION ALL
+            SELECT f.id, af.depth + 1
+            FROM folders f
+            INNER JOIN all_folders af ON f.parent_id = af.id
+            WHERE f.trash_id IS NOT NULL
+        )
+        SELECT id AS "id!", MAX(depth) AS "depth!"
+        FROM all_folders
+        GROUP BY id
+        ORDER BY 2 DESC
+        "#,
+        &root_folder_ids,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let subtree_ids: Vec<i32> = subtree.iter().map(|f| f.id).collect();
+
+    let photos = sqlx::query!(
+        "SELECT
+            id,
+            image_path,
+            preview_path
+        FROM
+            photos
+        WHERE
+            user_id = $3
+            AND trash_id IS NOT NULL
+            AND (folder_id = ANY($1) OR id = ANY($2))",
+        &subtree_ids,
+        &entry_photo_ids,
+        user_id,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let photo_ids: Vec<i32> = photos.iter().map(|p| p.id).collect();
+
+    let renditions = sqlx::query_scalar!(
+        "SELECT image_path FROM photo_renditions WHERE photo_id = ANY($1)",
+        &photo_ids,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut image_paths = Vec::new();
+    let mut prefixes = Vec::new();
+
+    for photo in photos {
+        image_paths.push(photo.image_path);
+        image_paths.extend(photo.preview_path);
+        prefixes.push(derived_image_prefix(photo.id));
+    }
+    image_paths.extend(renditions);
+
+    sqlx::query!(
+        "DELETE FROM photo_tag_relations WHERE photo_id = ANY($1)",
+        &photo_ids,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    // ゴミ箱の項目は写真・フォルダーの削除にあわせて消える
+    sqlx::query!("DELETE FROM photos WHERE id = ANY($1)", &photo_ids)
+        .execute(&mut **tx)
+        .await?;
+
+    // 削除するフォルダーを検索範囲にしたスマートフォルダーは残し、範囲が無くなったことを記録する
+    sqlx::query!(
+        "UPDATE smart_folders SET folder_id = NULL, scope_deleted = TRUE WHERE folder_id = ANY($1)",
+        &subtree_ids,
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    for level in subtree.chunk_by(|a, b| a.depth == b.depth) {
+        let ids: Vec<i32> = level.iter().map(|f| f.id).collect();
+        sqlx::query!(
+            "DELETE FROM folders WHERE id = ANY($1) AND user_id = $2",
+            &ids,
+            user_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    if !image_paths.is_empty() {
+        queue::enqueue(&mut **tx, &Job::DeleteObjects { image_paths, prefixes }).await?;
+    }
+
+    Ok((subtree_ids.len(), photo_ids.len()))
+}
+
+// 元の親フォルダーが残っていればそこへ、無ければ元のパスのフォルダーを作り直して戻す。戻した先のフォルダーIDを返す
+async fn restore_trash_entry(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    root_folder: i32,
+    entry_id: i32,
+) -> Result<Option<i32>, sqlx::Error> {
+    let Some(entry) = sqlx::query!(
+        "SELECT photo_id, folder_id, original_parent_id, original_path
+        FROM trash_entries
+        WHERE id = $1 AND user_id = $2",
+        entry_id,
+        user_id,
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let original_parent = match entry.original_parent_id {
+        Some(parent_id) => sqlx::query_scalar!(
+            "SELECT id FROM folders WHERE id = $1 AND user_id = $2 AND trash_id IS NULL",
+            parent_id,
+            user_id,
+        )
+        .fetch_optional(&mut **tx)
+        .await?,
+        None => None,
+    };
+
+    let parent_id = match original_parent {
+        Some(parent_id) => parent_id,
+        None => match walk_folder_path(tx, user_id, root_folder, &entry.original_path, true).await? {
+            Some(parent_id) => parent_id,
+            None => return Err(sqlx::Error::RowNotFound),
+        },
+    };
+
+    if let Some(photo_id) = entry.photo_id {
+        // 別のフォルダーに戻した場合は手動の並び順を解除する
+        sqlx::query!(
+            "UPDATE photos
+            SET
+                trash_id = NULL,
+                sort_position = CASE WHEN folder_id = $1 THEN sort_position END,
+                folder_id = $1
+            WHERE id = $2 AND trash_id = $3",
+            parent_id,
+            photo_id,
+            entry_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    if let Some(folder_id) = entry.folder_id {
+        let name = sqlx::query_scalar!("SELECT name FROM folders WHERE id = $1", folder_id)
+            .fetch_one(&mut **tx)
+            .await?;
+        let name = available_folder_name(tx, parent_id, &name).await?;
+
+        sqlx::query!(
+            "UPDATE folders
+            SET
+                trash_id = NULL,
+                sort_position = CASE WHEN parent_id = $1 THEN sort_position END,
+                parent_id = $1,
+                name = $2
+            WHERE id = $3",
+            parent_id,
+            name,
+            folder_id,
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!("UPDATE folders SET trash_id = NULL WHERE trash_id = $1", entry_id)
+            .execute(&mut **tx)
+            .await?;
+        sqlx::query!("UPDATE photos SET trash_id = NULL WHERE trash_id = $1", entry_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    sqlx::query!("DELETE FROM trash_entries WHERE id = $1", entry_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Some(parent_id))
+}
+
+#[get("/trash")]
+pub async fn get_trash(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            e.id,
+            e.photo_id,
+            e.folder_id,
+            COALESCE(p.name, f.name) AS "name!",
+            (SELECT COUNT(*) FROM photos tp WHERE tp.trash_id = e.id) AS "photo_count!",
+            e.original_parent_id,
+            e.original_path,
+            e.deleted_at
+        FROM
+            trash_entries e
+        LEFT JOIN
+            photos p ON p.id = e.photo_id
+        LEFT JOIN
+            folders f ON f.id = e.folder_id
+        WHERE
+            e.user_id = $1
+        ORDER BY
+            e.deleted_at DESC,
+            e.id DESC
+        "#,
+        claims.user_id,
+    )
+    .fetch_all(db.get_ref())
+    .await;
+
+    let retention = Duration::days(trash_retention_days() as i64);
+
+    match rows {
+        Ok(rows) => {
+            let entries: Vec<TrashEntry> = rows.into_iter().map(|row| TrashEntry {
+                id: row.id,
+                item_type: if row.photo_id.is_some() { "photo" } else { "folder" },
+                photo_id: row.photo_id,
+                folder_id: row.folder_id,
+                name: row.name,
+                photo_count: row.photo_count,
+                original_parent_id: row.original_parent_id,
+                original_path: row.original_path,
+                deleted_at: row.deleted_at,
+                expires_at: row.deleted_at + retention,
+            }).collect();
+
+            HttpResponse::Ok().json(serde_json::json!({ "data": entries }))
+        }
+        Err(e) => {
+            eprintln!("ゴミ箱の取得失敗: {:?}", e);
+            HttpResponse::InternalServerError().body("Error fetching trash")
+        }
+    }
+}
+
+#[post("/trash/restore")]
+pub async fn restore_from_trash(
+    req: HttpRequest,
+    payload: web::Json<TrashRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    if payload.ids.is_empty() {
+        return HttpResponse::BadRequest().body("復元する項目が指定されていません");
+    }
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダーのロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    let mut restored = Vec::new();
+
+    for &entry_id in &payload.ids {
+        match restore_trash_entry(&mut tx, claims.user_id, claims.root_folder, entry_id).await {
+            Ok(Some(parent_id)) => restored.push(serde_json::json!({ "id": entry_id, "parent_id": parent_id })),
+            Ok(None) => {
+                return HttpResponse::NotFound()
+                    .body(format!("ゴミ箱の項目 {} が存在しないか、権限がありません", entry_id));
+            }
+            Err(e) => {
+                eprintln!("ゴミ箱からの復元失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body("ゴミ箱からの復元に失敗しました");
+            }
+        }
+    }
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("トランザクションコミット失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "ゴミ箱から復元しました",
+        "restored": restored,
+    }))
+}
+
+// ids を省略した場合（/trash/all）はゴミ箱を空にする
+async fn purge_trash(req: HttpRequest, entry_ids: Option<&[i32]>, db: &PgPool) -> HttpResponse {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            eprintln!("トランザクション開始エラー: {:?}", e);
+            return HttpResponse::InternalServerError().body(message::AppError::TransactionStartFailed.message());
+        }
+    };
+
+    if let Err(e) = lock_folder_tree(&mut tx, claims.user_id).await {
+        eprintln!("フォルダーのロック失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    let entry_ids = match entry_ids {
+        Some(ids) => ids.to_vec(),
+        None => match sqlx::query_scalar!("SELECT id FROM trash_entries WHERE user_id = $1", claims.user_id)
+            .fetch_all(&mut *tx)
+            .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                eprintln!("ゴミ箱の取得失敗: {:?}", e);
+                return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+            }
+        },
+    };
+
+    let (deleted_folders, deleted_photos) = match purge_trash_entries(&mut tx, claims.user_id, &entry_ids).await {
+        Ok(counts) => counts,
+        Err(e) => {
+            eprintln!("ゴミ箱の削除失敗: {:?}", e);
+            return HttpResponse::InternalServerError().body("ゴミ箱の削除に失敗しました");
+        }
+    };
+
+    if let Err(e) = tx.commit().await {
+        eprintln!("トランザクションコミット失敗: {:?}", e);
+        return HttpResponse::InternalServerError().body(message::AppError::InternalServerError.message());
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "ゴミ箱から完全に削除しました",
+        "deleted_folders": deleted_folders,
+        "deleted_photos": deleted_photos,
+    }))
+}
+
+#[delete("/trash")]
+pub async fn delete_from_trash(
+    req: HttpRequest,
+    payload: web::Json<TrashRequest>,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    if payload.ids.is_empty() {
+        return HttpResponse::BadRequest().body("削除する項目が指定されていません");
+    }
+
+    purge_trash(req, Some(&payload.ids), db.get_ref()).await
+}
+
+#[delete("/trash/all")]
+pub async fn empty_trash(
+    req: HttpRequest,
+    db: web::Data<PgPool>,
+) -> impl Responder {
+    purge_trash(req, None, db.get_ref()).await
+}
@@ -1,8 +1,9 @@
-use actix_web::{post, web, HttpResponse, Responder};
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Responder};
 use bcrypt::verify;
 use chrono::{Utc, Duration};
 use jsonwebtoken::{encode, Header, EncodingKey};
-use crate::models::{user::{Claims, LoginRequest, UserCreateRequest}, User};
+use crate::handlers::auth_handler::extract_user_from_jwt;
+use crate::models::{privacy::{MetadataStripMode, MetadataStripTarget, PrivacySettings}, user::{Claims, LoginRequest, UserCreateRequest}, User};
 
 #[post("signup")]
 async fn signup(db_pool: web::Data<sqlx::PgPool>, paylod: web::Json<UserCreateRequest>) -> impl Responder {
@@ -77,3 +78,75 @@ pub async fn signin(db_pool: web::Data<sqlx::PgPool>, form: web::Json<LoginReque
 
     HttpResponse::Ok().json(serde_json::json!({ "token": token }))
 }
+
+#[get("/users/settings/privacy")]
+pub async fn get_privacy_settings(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let result = sqlx::query!(
+        "SELECT
+            metadata_strip_mode,
+            metadata_strip_target
+        FROM
+            users
+        WHERE
+            id = $1",
+        claims.user_id,
+    )
+    .fetch_optional(db_pool.get_ref())
+    .await;
+
+    match result {
+        Ok(Some(row)) => HttpResponse::Ok().json(PrivacySettings {
+            metadata_strip_mode: MetadataStripMode::parse(&row.metadata_strip_mode).unwrap_or(MetadataStripMode::None),
+            metadata_strip_target: MetadataStripTarget::parse(&row.metadata_strip_target).unwrap_or(MetadataStripTarget::Original),
+        }),
+        Ok(None) => HttpResponse::NotFound().body("ユーザーが見つかりません"),
+        Err(e) => {
+            eprintln!("設定取得エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("設定の取得に失敗しました")
+        }
+    }
+}
+
+// 以降にアップロードされる写真のデフォルトになる（既存の写真には影響しない）
+#[put("/users/settings/privacy")]
+pub async fn update_privacy_settings(
+    req: HttpRequest,
+    db_pool: web::Data<sqlx::PgPool>,
+    payload: web::Json<PrivacySettings>,
+) -> impl Responder {
+    let claims = match extract_user_from_jwt(&req) {
+        Ok(c) => c,
+        Err(resp) => return resp,
+    };
+
+    let result = sqlx::query!(
+        "UPDATE users
+        SET metadata_strip_mode = $1,
+            metadata_strip_target = $2
+        WHERE id = $3",
+        payload.metadata_strip_mode.as_str(),
+        payload.metadata_strip_target.as_str(),
+        claims.user_id,
+    )
+    .execute(db_pool.get_ref())
+    .await;
+
+    match result {
+        Ok(_) => HttpResponse::Ok().json(serde_json::json!({
+            "message": "設定を更新しました",
+            "data": payload.into_inner(),
+        })),
+        Err(e) => {
+            eprintln!("設定更新エラー: {:?}", e);
+            HttpResponse::InternalServerError().body("設定の更新に失敗しました")
+        }
+    }
+}
@@ -7,12 +7,41 @@ mod handlers {
     pub mod user_handler;
     pub mod tags_handler;
     pub mod s3_handler;
+    pub mod image_handler;
+    pub mod edit_handler;
+    pub mod similarity_handler;
+    pub mod media_handler;
+    pub mod admin_handler;
+    pub mod smart_folder_handler;
+    pub mod trash_handler;
+    pub mod album_handler;
 }
 mod routes {
     pub mod routes;
 }
 mod utils {
     pub mod s3;
+    pub mod image_processing;
+    pub mod metadata;
+    pub mod metadata_strip;
+    pub mod image_transform;
+    pub mod signed_url;
+    pub mod photo_edit;
+    pub mod placeholder;
+    pub mod perceptual_hash;
+    pub mod color;
+    pub mod media;
+    pub mod cron;
+    pub mod pagination;
+    pub mod tag_query;
+    pub mod search_highlight;
+}
+mod workers {
+    pub mod job;
+    pub mod queue;
+    pub mod runtime;
+    pub mod scheduler;
+    pub mod photo_processor;
 }
 mod message;
 
@@ -26,6 +55,10 @@ use utils::s3::verify_s3_credentials;
 use crate::routes::routes::config as protected_routes;
 use handlers::auth_handler::validate_jwt;
 use handlers::user_handler::{signin, signup};
+use handlers::image_handler::get_transformed_image;
+use handlers::media_handler::stream_media;
+use workers::runtime::spawn_job_workers;
+use workers::scheduler::spawn_scheduler;
 
 #[get("/check-s3-auth")]
 async fn check_s3_authentication() -> impl Responder {
@@ -46,6 +79,12 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("Failed to connect to DB");
 
+    // JOB_WORKERS_ENABLED=false の場合はジョブ・定期タスクを実行しない（APIのみのレプリカ用）
+    if env::var("JOB_WORKERS_ENABLED").map_or(true, |v| v != "false") {
+        spawn_job_workers(pool.clone());
+        spawn_scheduler(pool.clone());
+    }
+
     let pool_data = web::Data::new(pool);
 
     HttpServer::new(move || {
@@ -61,6 +100,9 @@ async fn main() -> std::io::Result<()> {
             .service(hello)
             .service(signin)
             .service(signup)
+            // 署名付きURLで認可するためJWTの検証対象外
+            .service(get_transformed_image)
+            .service(stream_media)
             .service(
                 web::scope("")
                     .wrap(HttpAuthentication::with_fn(validate_jwt))
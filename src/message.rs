@@ -5,6 +5,8 @@ use serde::Serialize;
 pub enum FileType {
     Folder,
     Photo,
+    SmartFolder,
+    Album,
 }
 
 impl fmt::Display for FileType {
@@ -12,6 +14,8 @@ impl fmt::Display for FileType {
         match self {
             FileType::Folder => write!(f, "フォルダー"),
             FileType::Photo => write!(f, "写真"),
+            FileType::SmartFolder => write!(f, "スマートフォルダー"),
+            FileType::Album => write!(f, "アルバム"),
         }
     }
 }
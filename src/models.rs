@@ -3,9 +3,20 @@ pub mod folder;
 pub mod breadcrumb;
 pub mod tag;
 pub mod user;
+pub mod rendition;
+pub mod metadata;
+pub mod privacy;
+pub mod edit;
+pub mod job;
+pub mod scheduled_task;
+pub mod smart_folder;
+pub mod trash;
+pub mod album;
 
 pub use photo::Photo;
 pub use folder::Folder;
 pub use breadcrumb::Breadcrumb;
 pub use tag::Tag;
 pub use user::User;
+pub use rendition::Rendition;
+pub use metadata::PhotoMetadata;
@@ -0,0 +1,75 @@
+use serde::{Serialize, Deserialize};
+use time::OffsetDateTime;
+use crate::models::photo::PhotoPage;
+
+#[derive(Debug, Serialize)]
+pub struct Album {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    // ゴミ箱にある写真は含まない
+    pub photo_count: i64,
+    // 指定が無い場合はアルバムの先頭の写真
+    pub cover_photo_id: Option<i32>,
+    pub cover_thumbnail: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumContents {
+    pub album: Album,
+    // アルバム内の並び順
+    pub photos: PhotoPage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumPhotosQuery {
+    pub limit: Option<i64>,
+    // 前のページの next_cursor
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumCreateRequest {
+    pub name: String,
+    pub description: Option<String>,
+    // 作成と同時に追加する写真
+    #[serde(default)]
+    pub photo_ids: Vec<i32>,
+}
+
+// 省略した項目は変更しない。description を空文字にすると説明を消す
+#[derive(Debug, Deserialize)]
+pub struct AlbumUpdateRequest {
+    pub id: i32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumDeleteRequest {
+    pub ids: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumPhotosRequest {
+    pub album_id: i32,
+    pub photo_ids: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumOrderRequest {
+    pub album_id: i32,
+    // 並べたい順の写真ID。含まれない写真はその後ろに今の順番のまま並ぶ
+    pub ids: Vec<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlbumCoverRequest {
+    pub album_id: i32,
+    // null の場合は指定を解除し、アルバムの先頭の写真に戻す
+    pub photo_id: Option<i32>,
+}
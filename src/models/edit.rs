@@ -0,0 +1,62 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlipDirection {
+    Horizontal,
+    Vertical,
+}
+
+// トリミングの座標は直前までの編集を適用した画像に対する割合（0.0〜1.0）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum EditOperation {
+    Rotate { degrees: i32 },
+    Flip { direction: FlipDirection },
+    Crop { x: f64, y: f64, width: f64, height: f64 },
+    // -100〜100
+    Brightness { value: i32 },
+    // -100〜100
+    Contrast { value: i32 },
+}
+
+impl EditOperation {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            EditOperation::Rotate { degrees } => {
+                if degrees % 90 != 0 || degrees == 0 {
+                    return Err("回転は90度単位で指定してください".to_string());
+                }
+            }
+            EditOperation::Flip { .. } => {}
+            EditOperation::Crop { x, y, width, height } => {
+                let in_range = |v: f64| (0.0..=1.0).contains(&v);
+                if !(in_range(x) && in_range(y) && width > 0.0 && height > 0.0 && x + width <= 1.0 && y + height <= 1.0) {
+                    return Err("トリミング範囲は画像内に収まる割合（0〜1）で指定してください".to_string());
+                }
+            }
+            EditOperation::Brightness { value } | EditOperation::Contrast { value } => {
+                if !(-100..=100).contains(&value) || value == 0 {
+                    return Err("明るさ・コントラストは -100〜100（0以外）で指定してください".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EditAppendRequest {
+    pub operation: EditOperation,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditStackResponse {
+    pub photo_id: i32,
+    pub edits: Vec<EditOperation>,
+    pub width: i32,
+    pub height: i32,
+    pub original_width: i32,
+    pub original_height: i32,
+}
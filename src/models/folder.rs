@@ -2,13 +2,18 @@ use serde::{Serialize, Deserialize};
 
 #[derive(Serialize, Debug)]
 pub struct Folder {
-    pub id: i32,
+    // スマートフォルダーの検索結果をまとめた仮のフォルダーは null
+    pub id: Option<i32>,
     pub user_id: Option<i32>,
     pub name: String,
     pub description: Option<String>,
     pub parent_id: Option<i32>,
     pub total_photo_count: Option<usize>,
-    pub total_photo_size: Option<i64>
+    pub total_photo_size: Option<i64>,
+    // 指定が無い場合は配下で一番新しい写真
+    pub cover_photo_id: Option<i32>,
+    pub cover_thumbnail: Option<String>,
+    pub sort_position: Option<i32>,
 }
 
 #[derive(Deserialize)]
@@ -29,3 +34,53 @@ pub struct FolderUpdateRequest {
 pub struct FolderDeleteRequest {
     pub ids: Vec<i32>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct FolderMoveRequest {
+    pub ids: Vec<i32>,
+    // 移動先の親フォルダー
+    pub parent_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FolderCopyRequest {
+    pub ids: Vec<i32>,
+    // 複製先の親フォルダー
+    pub parent_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FolderTreeQuery {
+    // 省略時はルートフォルダー
+    pub root: Option<i32>,
+    // root を 0 とした深さ。省略時は全階層
+    pub max_depth: Option<i32>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FolderTreeNode {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub depth: i32,
+    // 直下の写真
+    pub photo_count: i64,
+    // max_depth で省略したフォルダーも含む、配下すべての写真
+    pub total_photo_count: i64,
+    pub total_photo_size: i64,
+    pub children: Vec<FolderTreeNode>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FolderCoverRequest {
+    pub folder_id: i32,
+    // null の場合は指定を解除し、配下で一番新しい写真に戻す
+    pub photo_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FolderOrderRequest {
+    pub parent_id: i32,
+    // 並べたい順のフォルダーID。含まれないフォルダーはその後ろに名前順で並ぶ
+    pub ids: Vec<i32>,
+}
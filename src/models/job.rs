@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize)]
+pub struct JobListQuery {
+    pub status: Option<String>,
+    pub job_type: Option<String>,
+    pub limit: Option<i64>,
+    // 指定したIDより古いものを返す（ページ送り用）
+    pub before_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobResponse {
+    pub id: i64,
+    pub job_type: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub run_at: OffsetDateTime,
+    pub last_error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub completed_at: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobStats {
+    pub job_type: String,
+    pub status: String,
+    pub count: i64,
+}
@@ -0,0 +1,20 @@
+use serde::Serialize;
+use time::OffsetDateTime;
+
+#[derive(Clone, Serialize, Debug)]
+pub struct PhotoMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    pub aperture: Option<f64>,
+    pub exposure_time: Option<String>,
+    pub iso: Option<i32>,
+    // 撮影地のタイムゾーンで表現する
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub taken_at: Option<OffsetDateTime>,
+    pub timezone_offset: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
@@ -1,6 +1,10 @@
 use serde::{Serialize, Deserialize};
 use serde_with::serde_as;
 use super::tag::TagResponse;
+use super::rendition::Rendition;
+use super::metadata::PhotoMetadata;
+use super::breadcrumb::Breadcrumb;
+use super::privacy::{MetadataStripMode, MetadataStripTarget};
 use time::OffsetDateTime;
 
 fn serialize_datetime<S>(
@@ -35,6 +39,29 @@ pub struct Photo {
     pub width: i32,
     pub height: i32,
     pub tags: Vec<TagResponse>,
+    pub renditions: Vec<Rendition>,
+    pub metadata: Option<PhotoMetadata>,
+    pub blurhash: Option<String>,
+    pub lqip: Option<String>,
+    pub dominant_color: Option<String>,
+    pub average_color: Option<String>,
+    // image / animated / video
+    pub media_type: String,
+    pub duration_seconds: Option<f64>,
+    pub codec: Option<String>,
+    pub frame_rate: Option<f64>,
+    pub preview_path: Option<String>,
+    // q で検索した場合のみ。一致した項目ごとの抜粋
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<SearchHighlight>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SearchHighlight {
+    // name / description / folder_name / tags
+    pub field: &'static str,
+    // 一致した部分を <mark> で囲んだ HTML
+    pub snippet: String,
 }
 
 #[derive(Deserialize)]
@@ -44,6 +71,9 @@ pub struct PhotoUploadRequest {
     pub folder_id: Option<i32>,
     pub image_path: String,
     pub size_in_bytes: i64,
+    // 未指定の場合はユーザー設定に従う
+    pub metadata_strip_mode: Option<MetadataStripMode>,
+    pub metadata_strip_target: Option<MetadataStripTarget>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -59,14 +89,91 @@ pub struct PhotoMoveRequest {
     pub folder_id: i32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PhotoCopyRequest {
+    pub ids: Vec<i32>,
+    pub folder_id: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhotoOrderRequest {
+    pub folder_id: i32,
+    // 並べたい順の写真ID。含まれない写真はその後ろに並ぶ
+    pub ids: Vec<i32>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PhotoDeleteRequest {
     pub ids: Vec<i32>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+pub struct PhotoListQuery {
+    // uploaded_at（デフォルト） / taken_at / name / size / manual（フォルダー内の手動の並び順）
+    // relevance（q を指定した場合のみ。q を指定した場合のデフォルト）
+    pub sort: Option<String>,
+    // asc / desc。省略時は name と manual が昇順、それ以外は降順
+    pub order: Option<String>,
+    pub limit: Option<i64>,
+    // 前のページの next_cursor
+    pub cursor: Option<String>,
+    // 指定したフォルダーと配下のフォルダーの写真に絞り込む（/search のみ）
+    pub folder_id: Option<i32>,
+    // RFC 3339 形式。*_to の時刻は含まない
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub uploaded_from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub uploaded_to: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub taken_from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub taken_to: Option<OffsetDateTime>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    pub min_width: Option<i32>,
+    pub max_width: Option<i32>,
+    pub min_height: Option<i32>,
+    pub max_height: Option<i32>,
+    // true: タグ付きのみ / false: タグなしのみ
+    pub tagged: Option<bool>,
+    // 名前・説明・フォルダー名・タグの全文検索。部分一致・あいまい一致も含む
+    pub q: Option<String>,
+    // タグの検索式（/photos/search の tags と同じ書式）
+    pub tags: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PhotoPage {
+    pub data: Vec<Photo>,
+    // 次のページが無い場合は null
+    pub next_cursor: Option<String>,
+    // 絞り込み条件に一致する写真の総数
+    pub total_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PhotoDetailQuery {
+    // 前後の写真を決める並び順。写真一覧の sort / order と同じ値
+    pub sort: Option<String>,
+    pub order: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PhotoDetail {
+    pub photo: Photo,
+    pub breadcrumbs: Vec<Breadcrumb>,
+    // 同じフォルダー内の前後の写真。端の場合は null
+    pub previous_id: Option<i32>,
+    pub next_id: Option<i32>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PhotoSearchRequest {
-    pub tags: String,
+    // タグの検索式。例: "(cat OR dog) AND -blurry"。大文字小文字は区別しない
+    pub tags: Option<String>,
+    // "#1e90ff" 形式。tolerance は CIELAB の色差（ΔE）
+    pub color: Option<String>,
+    pub tolerance: Option<f64>,
 }
 
 #[derive(Serialize)]
@@ -83,6 +190,14 @@ pub struct PhotoResponse {
     pub folder_id: i32,
     pub width: i32,
     pub height: i32,
+    pub blurhash: Option<String>,
+    pub lqip: Option<String>,
+    pub dominant_color: Option<String>,
+    pub average_color: Option<String>,
+    pub media_type: String,
+    // 色で検索した場合のみ。指定色とパレットの最小色差（ΔE）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_distance: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -90,3 +205,72 @@ pub struct TagAddRequest {
     pub photo_ids: Vec<i32>,
     pub tag_ids: Vec<i32>,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ImageUrlRequest {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    pub fit: Option<String>,
+    pub fmt: Option<String>,
+    pub q: Option<u8>,
+    // 秒
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageTransformQuery {
+    pub w: Option<u32>,
+    pub h: Option<u32>,
+    pub fit: Option<String>,
+    pub fmt: Option<String>,
+    pub q: Option<u8>,
+    pub uid: i32,
+    pub exp: i64,
+    pub sig: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateQuery {
+    pub threshold: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimilarQuery {
+    pub threshold: Option<u32>,
+    pub limit: Option<i64>,
+}
+
+// distance は基準となる写真とのハミング距離
+#[derive(Debug, Serialize)]
+pub struct PhotoMatch {
+    pub id: i32,
+    pub name: String,
+    pub folder_id: i32,
+    pub image_path: String,
+    pub width: i32,
+    pub height: i32,
+    pub size_in_bytes: i64,
+    pub distance: u32,
+}
+
+// 先頭が残す候補（解像度・サイズが最も大きいもの）
+#[derive(Debug, Serialize)]
+pub struct DuplicateCluster {
+    pub photos: Vec<PhotoMatch>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaUrlRequest {
+    // "preview" の場合は短いプレビューを返す
+    pub variant: Option<String>,
+    // 秒
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaStreamQuery {
+    pub variant: Option<String>,
+    pub uid: i32,
+    pub exp: i64,
+    pub sig: String,
+}
@@ -0,0 +1,62 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataStripMode {
+    None,
+    // GPS情報のみ削除
+    Location,
+    // EXIF/XMP/IPTCをすべて削除
+    All,
+}
+
+impl MetadataStripMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "none" => Some(MetadataStripMode::None),
+            "location" => Some(MetadataStripMode::Location),
+            "all" => Some(MetadataStripMode::All),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataStripMode::None => "none",
+            MetadataStripMode::Location => "location",
+            MetadataStripMode::All => "all",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataStripTarget {
+    // 保存する元画像そのものから削除
+    Original,
+    // 配信用のレンディションからのみ削除（元画像はそのまま）
+    Renditions,
+}
+
+impl MetadataStripTarget {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "original" => Some(MetadataStripTarget::Original),
+            "renditions" => Some(MetadataStripTarget::Renditions),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetadataStripTarget::Original => "original",
+            MetadataStripTarget::Renditions => "renditions",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PrivacySettings {
+    pub metadata_strip_mode: MetadataStripMode,
+    pub metadata_strip_target: MetadataStripTarget,
+}
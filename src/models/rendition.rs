@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+#[derive(Clone, Serialize, Debug)]
+pub struct Rendition {
+    pub size: i32,
+    pub format: String,
+    pub image_path: String,
+    pub width: i32,
+    pub height: i32,
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduledTaskRunQuery {
+    pub task_name: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledTaskRun {
+    pub id: i64,
+    pub task_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub scheduled_for: OffsetDateTime,
+    pub status: String,
+    pub detail: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+// schedule が None の場合は無効
+#[derive(Debug, Serialize)]
+pub struct ScheduledTaskResponse {
+    pub task_name: String,
+    pub schedule: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub next_run_at: Option<OffsetDateTime>,
+    pub last_run: Option<ScheduledTaskRun>,
+}
@@ -0,0 +1,58 @@
+use serde::{Serialize, Deserialize};
+use time::OffsetDateTime;
+
+// スマートフォルダーの検索条件。写真一覧の同名のパラメーターと同じ意味
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmartFolderCriteria {
+    // タグの検索式
+    pub tags: Option<String>,
+    // 全文検索
+    pub q: Option<String>,
+    // RFC 3339 形式。*_to の時刻は含まない
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub uploaded_from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub uploaded_to: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub taken_from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub taken_to: Option<OffsetDateTime>,
+    // 検索範囲のフォルダー（配下を含む）。省略時はすべてのフォルダー
+    pub folder_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SmartFolder {
+    pub id: i32,
+    pub name: String,
+    pub pinned: bool,
+    #[serde(flatten)]
+    pub criteria: SmartFolderCriteria,
+    // 検索範囲のフォルダーが完全に削除された。検索条件を変更するまで写真は表示しない
+    pub scope_deleted: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmartFolderCreateRequest {
+    pub name: String,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(flatten)]
+    pub criteria: SmartFolderCriteria,
+}
+
+// 省略した項目は変更しない。criteria を指定した場合は検索条件をすべて置き換える
+#[derive(Debug, Deserialize)]
+pub struct SmartFolderUpdateRequest {
+    pub id: i32,
+    pub name: Option<String>,
+    pub pinned: Option<bool>,
+    pub criteria: Option<SmartFolderCriteria>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SmartFolderDeleteRequest {
+    pub ids: Vec<i32>,
+}
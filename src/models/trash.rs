@@ -0,0 +1,29 @@
+use serde::{Serialize, Deserialize};
+use time::OffsetDateTime;
+
+#[derive(Debug, Serialize)]
+pub struct TrashEntry {
+    pub id: i32,
+    // photo / folder
+    pub item_type: &'static str,
+    pub photo_id: Option<i32>,
+    pub folder_id: Option<i32>,
+    pub name: String,
+    // フォルダーの場合は配下の写真も含む
+    pub photo_count: i64,
+    // 削除したときの親フォルダー。既に無い場合は null
+    pub original_parent_id: Option<i32>,
+    // ルートフォルダーから親フォルダーまでの名前
+    pub original_path: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub deleted_at: OffsetDateTime,
+    // この時刻を過ぎると自動で完全に削除される
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrashRequest {
+    // ゴミ箱の項目ID（写真・フォルダーのIDではない）
+    pub ids: Vec<i32>,
+}
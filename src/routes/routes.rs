@@ -2,47 +2,162 @@ use actix_web::web;
 
 use crate::handlers::files_handler::{
     get_folder_contents,
+    get_folder_contents_by_path,
+    create_folder_by_path,
     get_all_photos,
 };
 use crate::handlers::photo_handler::{
     upload_photo,
     update_photo,
     move_photo,
+    copy_photo,
+    reorder_photos,
     delete_photo,
     search_photos,
+    get_photo,
     add_tag_to_photo,
+    backfill_photo_metadata,
+    backfill_photo_placeholders,
+    backfill_photo_hashes,
+};
+use crate::handlers::media_handler::create_stream_url;
+use crate::handlers::admin_handler::{
+    get_jobs,
+    get_job_stats,
+    retry_job,
+    get_scheduled_tasks,
+    get_scheduled_task_runs,
+};
+use crate::handlers::similarity_handler::{
+    get_duplicate_photos,
+    get_similar_photos,
 };
 use crate::handlers::folder_handler::{
     create_folder,
+    get_folder_tree,
     update_folder,
+    move_folder,
+    copy_folder,
+    set_folder_cover,
+    reorder_folders,
     delete_folder,
 };
+use crate::handlers::smart_folder_handler::{
+    get_smart_folders,
+    get_smart_folder_contents,
+    create_smart_folder,
+    update_smart_folder,
+    delete_smart_folder,
+};
+use crate::handlers::album_handler::{
+    get_albums,
+    get_album_contents,
+    create_album,
+    update_album,
+    delete_album,
+    add_album_photos,
+    remove_album_photos,
+    reorder_album_photos,
+    set_album_cover,
+};
+use crate::handlers::trash_handler::{
+    get_trash,
+    restore_from_trash,
+    delete_from_trash,
+    empty_trash,
+};
 use crate::handlers::tags_handler::{
     get_tags,
     add_tag,
 };
+use crate::handlers::user_handler::{
+    get_privacy_settings,
+    update_privacy_settings,
+};
+use crate::handlers::edit_handler::{
+    get_photo_edits,
+    append_photo_edit,
+    undo_photo_edit,
+    reset_photo_edits,
+};
+use crate::handlers::image_handler::{
+    create_image_url,
+};
 use crate::handlers::s3_handler::{
     generate_presigned_url,
 };
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     cfg
+        .service(get_folder_contents_by_path)
+        .service(create_folder_by_path)
         .service(get_folder_contents)
         .service(get_all_photos)
         // 写真
         .service(upload_photo)
         .service(update_photo)
         .service(move_photo)
+        .service(copy_photo)
+        .service(reorder_photos)
         .service(delete_photo)
         .service(search_photos)
         .service(add_tag_to_photo)
+        .service(create_image_url)
+        .service(backfill_photo_metadata)
+        .service(backfill_photo_placeholders)
+        .service(backfill_photo_hashes)
+        .service(get_duplicate_photos)
+        .service(get_similar_photos)
+        .service(create_stream_url)
+        // /photos/search などの固定のパスより後に登録する
+        .service(get_photo)
+        // 写真の編集
+        .service(get_photo_edits)
+        .service(append_photo_edit)
+        .service(undo_photo_edit)
+        .service(reset_photo_edits)
         // フォルダー
+        .service(get_folder_tree)
         .service(create_folder)
         .service(update_folder)
+        .service(move_folder)
+        .service(copy_folder)
+        .service(set_folder_cover)
+        .service(reorder_folders)
         .service(delete_folder)
+        // スマートフォルダー
+        .service(get_smart_folders)
+        .service(get_smart_folder_contents)
+        .service(create_smart_folder)
+        .service(update_smart_folder)
+        .service(delete_smart_folder)
+        // アルバム
+        .service(get_albums)
+        .service(get_album_contents)
+        .service(create_album)
+        .service(update_album)
+        .service(delete_album)
+        .service(add_album_photos)
+        .service(remove_album_photos)
+        .service(reorder_album_photos)
+        .service(set_album_cover)
+        // ゴミ箱
+        .service(get_trash)
+        .service(restore_from_trash)
+        .service(empty_trash)
+        .service(delete_from_trash)
         // タグ
         .service(get_tags)
         .service(add_tag)
+        // ユーザー設定
+        .service(get_privacy_settings)
+        .service(update_privacy_settings)
+        // 管理
+        .service(get_jobs)
+        .service(get_job_stats)
+        .service(retry_job)
+        .service(get_scheduled_tasks)
+        .service(get_scheduled_task_runs)
         // S3
         .service(generate_presigned_url);
 }
@@ -0,0 +1,150 @@
+use image::RgbImage;
+
+use crate::utils::placeholder::hex_color;
+
+pub const PALETTE_SIZE: usize = 5;
+// これより近い色はパレット上で同じ色として扱う
+const MERGE_DISTANCE: f32 = 12.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteColor {
+    pub color: String,
+    pub lab: Lab,
+    pub weight: f32,
+}
+
+pub fn parse_hex_color(value: &str) -> Result<[u8; 3], String> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let invalid = || format!("不正な色です: {}", value);
+
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+// sRGB (D65) → CIELAB
+pub fn rgb_to_lab([r, g, b]: [u8; 3]) -> Lab {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    };
+    let (r, g, b) = (linear(r), linear(g), linear(b));
+
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    Lab {
+        l: 116.0 * fy - 16.0,
+        a: 500.0 * (fx - fy),
+        b: 200.0 * (fy - fz),
+    }
+}
+
+// CIE76 の色差 ΔE
+pub fn delta_e(x: Lab, y: Lab) -> f32 {
+    ((x.l - y.l).powi(2) + (x.a - y.a).powi(2) + (x.b - y.b).powi(2)).sqrt()
+}
+
+// 各チャンネルを上位4ビットで量子化し、多い色域から順に近い色をまとめて上位 PALETTE_SIZE 色を返す
+pub fn extract_palette(pixels: &RgbImage) -> Vec<PaletteColor> {
+    let total = pixels.width() * pixels.height();
+    if total == 0 {
+        return Vec::new();
+    }
+
+    let mut buckets = vec![([0u64; 3], 0u32); 4096];
+    for p in pixels.pixels() {
+        let bucket = ((p[0] >> 4) as usize) << 8 | ((p[1] >> 4) as usize) << 4 | (p[2] >> 4) as usize;
+        let (sum, count) = &mut buckets[bucket];
+        for (s, &c) in sum.iter_mut().zip(p.0.iter()) {
+            *s += c as u64;
+        }
+        *count += 1;
+    }
+
+    let mut buckets: Vec<([u8; 3], u32)> = buckets
+        .into_iter()
+        .filter(|&(_, count)| count > 0)
+        .map(|(sum, count)| (sum.map(|s| (s / count as u64) as u8), count))
+        .collect();
+    buckets.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+    // (代表色, Lab, 画素数)
+    let mut palette: Vec<([u8; 3], Lab, u32)> = Vec::new();
+    for (rgb, count) in buckets {
+        let lab = rgb_to_lab(rgb);
+
+        if let Some((_, _, existing_count)) = palette.iter_mut().find(|(_, existing, _)| delta_e(*existing, lab) < MERGE_DISTANCE) {
+            *existing_count += count;
+        } else if palette.len() < PALETTE_SIZE {
+            palette.push((rgb, lab, count));
+        }
+    }
+
+    palette.sort_by_key(|&(_, _, count)| std::cmp::Reverse(count));
+
+    palette
+        .into_iter()
+        .map(|(rgb, lab, count)| PaletteColor {
+            color: hex_color(rgb),
+            lab,
+            weight: count as f32 / total as f32,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#1e90ff").unwrap(), [0x1e, 0x90, 0xff]);
+        assert_eq!(parse_hex_color("1E90FF").unwrap(), [0x1e, 0x90, 0xff]);
+        assert!(parse_hex_color("#12345").is_err());
+        assert!(parse_hex_color("#gggggg").is_err());
+    }
+
+    #[test]
+    fn test_rgb_to_lab() {
+        let white = rgb_to_lab([255, 255, 255]);
+        assert!((white.l - 100.0).abs() < 0.1 && white.a.abs() < 0.1 && white.b.abs() < 0.1);
+
+        // 青は b* が大きく負になる
+        let blue = rgb_to_lab([0, 0, 255]);
+        assert!((blue.l - 32.3).abs() < 0.5 && blue.b < -100.0);
+
+        assert!(delta_e(rgb_to_lab([0, 0, 255]), rgb_to_lab([20, 20, 230])) < delta_e(rgb_to_lab([0, 0, 255]), rgb_to_lab([255, 0, 0])));
+    }
+
+    #[test]
+    fn test_extract_palette() {
+        // 3/4 が青系（わずかに異なる2色）、1/4 が赤
+        let img = RgbImage::from_fn(40, 40, |x, y| match (x, y) {
+            (30.., _) => image::Rgb([255, 0, 0]),
+            (_, 0..25) => image::Rgb([0, 0, 255]),
+            _ => image::Rgb([0, 0, 228]),
+        });
+
+        let palette = extract_palette(&img);
+
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette[0].color, "#0000ff");
+        assert!((palette[0].weight - 0.75).abs() < 0.01);
+        assert_eq!(palette[1].color, "#ff0000");
+    }
+}
@@ -0,0 +1,172 @@
+use time::{Date, Duration, OffsetDateTime, Time};
+
+// 探索する上限。これを超えても一致しない式（2月30日など）は実行されない
+const MAX_SEARCH_DAYS: i64 = 366 * 5;
+
+// 「分 時 日 月 曜日」の5項目の cron 式（UTC）
+// 各項目は *, 数値, 範囲 (1-5), 間隔 (*/15, 0-30/5), カンマ区切りに対応する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // 日と曜日の両方が指定された場合は、どちらかに一致すれば実行する（一般的な cron と同じ）
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<(u64, bool), String> {
+    let invalid = || format!("cron式の項目が不正です: {}", field);
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (
+                    start.parse::<u32>().map_err(|_| invalid())?,
+                    end.parse::<u32>().map_err(|_| invalid())?,
+                ),
+                // "5/10" は 5 から最大値まで 10 おき
+                None => {
+                    let start = range.parse::<u32>().map_err(|_| invalid())?;
+                    (start, if part.contains('/') { max } else { start })
+                }
+            },
+        };
+
+        if step == 0 || start < min || end > max || start > end {
+            return Err(invalid());
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    // "*" や "*/2" は制限なしとして扱う
+    Ok((bits, !field.starts_with('*')))
+}
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("cron式は5項目で指定してください: {}", expression));
+        };
+
+        let (minutes, _) = parse_field(minutes, 0, 59)?;
+        let (hours, _) = parse_field(hours, 0, 23)?;
+        let (days, days_restricted) = parse_field(days, 1, 31)?;
+        let (months, _) = parse_field(months, 1, 12)?;
+        // 日曜日は 0 と 7 のどちらでもよい
+        let (mut weekdays, weekdays_restricted) = parse_field(weekdays, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            days_restricted,
+            weekdays_restricted,
+        })
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        if self.months & (1 << u8::from(date.month())) == 0 {
+            return false;
+        }
+
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().number_days_from_sunday()) != 0;
+
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    // 指定時刻より後で最初に実行する時刻
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(time::UtcOffset::UTC);
+        let start = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::minutes(1);
+
+        let mut date = start.date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.matches_date(date) {
+                let from = if date == start.date() { start.time() } else { Time::MIDNIGHT };
+
+                for hour in from.hour()..24 {
+                    if self.hours & (1 << hour) == 0 {
+                        continue;
+                    }
+
+                    let first_minute = if hour == from.hour() { from.minute() } else { 0 };
+                    if let Some(minute) = (first_minute..60).find(|&m| self.minutes & (1 << m) != 0) {
+                        let time = Time::from_hms(hour, minute, 0).ok()?;
+                        return Some(date.with_time(time).assume_utc());
+                    }
+                }
+            }
+
+            date = date.next_day()?;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    fn at(month: Month, day: u8, hour: u8, minute: u8, second: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2024, month, day)
+            .unwrap()
+            .with_hms(hour, minute, second)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_expressions() {
+        assert!(CronSchedule::parse("* * * *").is_err());
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+        assert!(CronSchedule::parse("5-1 * * * *").is_err());
+        assert!(CronSchedule::parse("a * * * *").is_err());
+    }
+
+    #[test]
+    fn test_next_after() {
+        let every_15 = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(every_15.next_after(at(Month::January, 1, 10, 7, 30)), Some(at(Month::January, 1, 10, 15, 0)));
+        assert_eq!(every_15.next_after(at(Month::January, 1, 10, 15, 0)), Some(at(Month::January, 1, 10, 30, 0)));
+
+        let daily = CronSchedule::parse("30 3 * * *").unwrap();
+        assert_eq!(daily.next_after(at(Month::January, 1, 4, 0, 0)), Some(at(Month::January, 2, 3, 30, 0)));
+
+        // 2024-01-01 は月曜日
+        let sunday = CronSchedule::parse("0 0 * * 7").unwrap();
+        assert_eq!(sunday.next_after(at(Month::January, 1, 0, 0, 0)), Some(at(Month::January, 7, 0, 0, 0)));
+
+        let month_end = CronSchedule::parse("0 12 31 * *").unwrap();
+        assert_eq!(month_end.next_after(at(Month::February, 1, 0, 0, 0)), Some(at(Month::March, 31, 12, 0, 0)));
+
+        assert_eq!(CronSchedule::parse("0 0 30 2 *").unwrap().next_after(at(Month::January, 1, 0, 0, 0)), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::env;
+use std::io::Cursor;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+
+const DEFAULT_RENDITION_SIZES: [u32; 3] = [256, 1024, 2048];
+const JPEG_QUALITY: u8 = 85;
+const WEBP_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionFormat {
+    Jpeg,
+    WebP,
+}
+
+impl RenditionFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(RenditionFormat::Jpeg),
+            "webp" => Some(RenditionFormat::WebP),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "jpeg",
+            RenditionFormat::WebP => "webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "jpg",
+            RenditionFormat::WebP => "webp",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RenditionFormat::Jpeg => "image/jpeg",
+            RenditionFormat::WebP => "image/webp",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenditionConfig {
+    pub sizes: Vec<u32>,
+    pub formats: Vec<RenditionFormat>,
+}
+
+impl RenditionConfig {
+    // RENDITION_SIZES="256,1024,2048" / RENDITION_FORMATS="webp,jpeg"
+    pub fn from_env() -> Self {
+        let sizes = env::var("RENDITION_SIZES")
+            .ok()
+            .map(|v| v.split(',').filter_map(|s| s.trim().parse::<u32>().ok()).filter(|&s| s > 0).collect::<Vec<_>>())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| DEFAULT_RENDITION_SIZES.to_vec());
+
+        let formats = env::var("RENDITION_FORMATS")
+            .ok()
+            .map(|v| v.split(',').filter_map(RenditionFormat::parse).collect::<Vec<_>>())
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| vec![RenditionFormat::WebP, RenditionFormat::Jpeg]);
+
+        RenditionConfig { sizes, formats }
+    }
+}
+
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+// EXIFの向き情報を反映した状態でデコードする
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage, String> {
+    let reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| format!("画像形式の判定に失敗: {}", e))?;
+
+    let mut decoder = reader
+        .into_decoder()
+        .map_err(|e| format!("画像のデコードに失敗: {}", e))?;
+
+    let orientation = decoder
+        .orientation()
+        .map_err(|e| format!("向き情報の取得に失敗: {}", e))?;
+
+    let mut img = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("画像のデコードに失敗: {}", e))?;
+
+    img.apply_orientation(orientation);
+
+    Ok(img)
+}
+
+// 長辺が size 以下になるよう縮小してエンコードする（拡大はしない）
+pub fn render_rendition(
+    img: &DynamicImage,
+    size: u32,
+    format: RenditionFormat,
+) -> Result<EncodedImage, String> {
+    let resized = if img.width() > size || img.height() > size {
+        img.resize(size, size, FilterType::CatmullRom)
+    } else {
+        img.clone()
+    };
+
+    let bytes = encode_image(&resized, format)?;
+
+    Ok(EncodedImage {
+        bytes,
+        width: resized.width(),
+        height: resized.height(),
+    })
+}
+
+pub fn encode_image(img: &DynamicImage, format: RenditionFormat) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+
+    let result = match format {
+        RenditionFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY);
+            DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)
+        }
+        RenditionFormat::WebP => return encode_webp(img, WEBP_QUALITY),
+    };
+
+    result.map_err(|e| format!("{}へのエンコードに失敗: {}", format.as_str(), e))?;
+
+    Ok(buf)
+}
+
+// 非可逆圧縮の WebP。image クレートのエンコーダーは可逆圧縮のみで、写真では JPEG より大きくなるため libwebp を使う
+pub fn encode_webp(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let rgba = img.to_rgba8();
+    let encoded = webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height())
+        .encode_simple(false, quality as f32)
+        .map_err(|e| format!("webpへのエンコードに失敗: {:?}", e))?;
+
+    Ok(encoded.to_vec())
+}
+
+// 元画像のキーの隣に置くレンディションのキー（例: abc-photo.jpg → abc-photo_256w.webp）
+pub fn rendition_key(original_key: &str, size: u32, format: RenditionFormat) -> String {
+    let stem = original_key
+        .rsplit_once('.')
+        .map(|(stem, _)| stem)
+        .unwrap_or(original_key);
+
+    format!("{}_{}w.{}", stem, size, format.extension())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendition_key() {
+        assert_eq!(rendition_key("abc-photo.jpg", 256, RenditionFormat::WebP), "abc-photo_256w.webp");
+        assert_eq!(rendition_key("abc-photo", 1024, RenditionFormat::Jpeg), "abc-photo_1024w.jpg");
+    }
+
+    #[test]
+    fn test_render_rendition_keeps_aspect_ratio_and_never_upscales() {
+        let img = DynamicImage::new_rgb8(400, 200);
+
+        let small = render_rendition(&img, 100, RenditionFormat::Jpeg).unwrap();
+        assert_eq!((small.width, small.height), (100, 50));
+
+        let large = render_rendition(&img, 2048, RenditionFormat::WebP).unwrap();
+        assert_eq!((large.width, large.height), (400, 200));
+        assert!(!large.bytes.is_empty());
+    }
+
+    #[test]
+    fn test_encode_webp_is_lossy() {
+        let img = DynamicImage::new_rgb8(64, 64);
+        let bytes = encode_webp(&img, 80).unwrap();
+
+        // 可逆圧縮は "VP8L"、非可逆圧縮は "VP8 " のチャンクになる
+        assert_eq!(&bytes[12..16], b"VP8 ");
+        assert_eq!(decode_image(&bytes).unwrap().width(), 64);
+    }
+}
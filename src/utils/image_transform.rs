@@ -0,0 +1,223 @@
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use crate::utils::image_processing::encode_webp;
+
+pub const MAX_DIMENSION: u32 = 4096;
+const DEFAULT_JPEG_QUALITY: u8 = 85;
+const DEFAULT_WEBP_QUALITY: u8 = 80;
+const DEFAULT_AVIF_QUALITY: u8 = 70;
+const AVIF_SPEED: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fit {
+    // 指定サイズを埋めるように拡大縮小し、はみ出た部分を切り取る
+    Cover,
+    // 指定サイズに収まるように縮小する
+    Contain,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Jpeg,
+    WebP,
+    Avif,
+}
+
+impl OutputFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpeg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Avif => "image/avif",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformParams {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: Fit,
+    pub format: OutputFormat,
+    pub quality: Option<u8>,
+}
+
+impl TransformParams {
+    pub fn parse(
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: Option<&str>,
+        format: Option<&str>,
+        quality: Option<u8>,
+    ) -> Result<Self, String> {
+        if width.is_none() && height.is_none() {
+            return Err("w と h のどちらかを指定してください".to_string());
+        }
+
+        for dimension in [width, height].into_iter().flatten() {
+            if dimension == 0 || dimension > MAX_DIMENSION {
+                return Err(format!("w と h は 1〜{} の範囲で指定してください", MAX_DIMENSION));
+            }
+        }
+
+        let fit = match fit {
+            None => Fit::Contain,
+            Some(_) if width.is_none() || height.is_none() => {
+                return Err("fit は w と h を両方指定した場合のみ使用できます".to_string());
+            }
+            Some("cover") => Fit::Cover,
+            Some("contain") => Fit::Contain,
+            Some(other) => return Err(format!("不正な fit です: {}", other)),
+        };
+
+        let format = match format {
+            None | Some("jpeg") | Some("jpg") => OutputFormat::Jpeg,
+            Some("webp") => OutputFormat::WebP,
+            Some("avif") => OutputFormat::Avif,
+            Some(other) => return Err(format!("不正な fmt です: {}", other)),
+        };
+
+        if let Some(q) = quality {
+            if !(1..=100).contains(&q) {
+                return Err("q は 1〜100 の範囲で指定してください".to_string());
+            }
+        }
+
+        Ok(TransformParams { width, height, fit, format, quality })
+    }
+
+    // 署名やキャッシュキーに使う正規化済みのクエリ文字列（そのままURLにも使う）
+    pub fn canonical(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(w) = self.width {
+            parts.push(format!("w={}", w));
+        }
+        if let Some(h) = self.height {
+            parts.push(format!("h={}", h));
+        }
+        if self.width.is_some() && self.height.is_some() {
+            parts.push(format!("fit={}", match self.fit {
+                Fit::Cover => "cover",
+                Fit::Contain => "contain",
+            }));
+        }
+        parts.push(format!("fmt={}", self.format.as_str()));
+        if let Some(q) = self.quality {
+            parts.push(format!("q={}", q));
+        }
+
+        parts.join("&")
+    }
+
+    // 元画像より大きくはしない
+    pub fn apply(&self, img: &DynamicImage) -> DynamicImage {
+        let (src_w, src_h) = (img.width(), img.height());
+
+        match (self.width, self.height, self.fit) {
+            (Some(w), Some(h), Fit::Cover) => {
+                let scale = (src_w as f64 / w as f64).min(src_h as f64 / h as f64).min(1.0);
+                let w = ((w as f64 * scale).round() as u32).max(1);
+                let h = ((h as f64 * scale).round() as u32).max(1);
+                img.resize_to_fill(w, h, FilterType::CatmullRom)
+            }
+            (w, h, _) => {
+                let w = w.unwrap_or(u32::MAX).min(src_w);
+                let h = h.unwrap_or(u32::MAX).min(src_h);
+                if w == src_w && h == src_h {
+                    img.clone()
+                } else {
+                    img.resize(w, h, FilterType::CatmullRom)
+                }
+            }
+        }
+    }
+
+    pub fn encode(&self, img: &DynamicImage) -> Result<Vec<u8>, String> {
+        let mut buf = Vec::new();
+
+        let result = match self.format {
+            OutputFormat::Jpeg => {
+                let encoder = JpegEncoder::new_with_quality(&mut buf, self.quality.unwrap_or(DEFAULT_JPEG_QUALITY));
+                DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(encoder)
+            }
+            OutputFormat::WebP => return encode_webp(img, self.quality.unwrap_or(DEFAULT_WEBP_QUALITY)),
+            OutputFormat::Avif => {
+                let encoder = AvifEncoder::new_with_speed_quality(
+                    &mut buf,
+                    AVIF_SPEED,
+                    self.quality.unwrap_or(DEFAULT_AVIF_QUALITY),
+                );
+                DynamicImage::ImageRgba8(img.to_rgba8()).write_with_encoder(encoder)
+            }
+        };
+
+        result.map_err(|e| format!("{}へのエンコードに失敗: {}", self.format.as_str(), e))?;
+
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_invalid_combinations() {
+        assert!(TransformParams::parse(None, None, None, None, None).is_err());
+        assert!(TransformParams::parse(Some(MAX_DIMENSION + 1), None, None, None, None).is_err());
+        assert!(TransformParams::parse(Some(100), None, Some("cover"), None, None).is_err());
+        assert_eq!(TransformParams::parse(Some(100), None, None, Some("webp"), Some(80)).unwrap().quality, Some(80));
+        assert!(TransformParams::parse(Some(100), None, None, Some("gif"), None).is_err());
+        assert!(TransformParams::parse(Some(100), None, None, Some("jpeg"), Some(0)).is_err());
+    }
+
+    #[test]
+    fn test_canonical() {
+        let params = TransformParams::parse(Some(300), Some(200), Some("cover"), Some("avif"), Some(60)).unwrap();
+        assert_eq!(params.canonical(), "w=300&h=200&fit=cover&fmt=avif&q=60");
+
+        let params = TransformParams::parse(None, Some(200), None, None, None).unwrap();
+        assert_eq!(params.canonical(), "h=200&fmt=jpeg");
+    }
+
+    #[test]
+    fn test_apply() {
+        let img = DynamicImage::new_rgb8(400, 200);
+
+        let cover = TransformParams::parse(Some(100), Some(100), Some("cover"), None, None).unwrap();
+        let out = cover.apply(&img);
+        assert_eq!((out.width(), out.height()), (100, 100));
+
+        let contain = TransformParams::parse(Some(100), Some(100), Some("contain"), None, None).unwrap();
+        let out = contain.apply(&img);
+        assert_eq!((out.width(), out.height()), (100, 50));
+
+        // 拡大はしない
+        let large = TransformParams::parse(Some(1000), None, None, None, None).unwrap();
+        let out = large.apply(&img);
+        assert_eq!((out.width(), out.height()), (400, 200));
+
+        let large_cover = TransformParams::parse(Some(800), Some(800), Some("cover"), None, None).unwrap();
+        let out = large_cover.apply(&img);
+        assert_eq!((out.width(), out.height()), (200, 200));
+    }
+}
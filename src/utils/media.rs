@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, ImageFormat};
+use serde::Deserialize;
+use tokio::process::Command;
+
+use crate::models::privacy::MetadataStripMode;
+use crate::utils::metadata_strip::StrippedImage;
+
+// 代表フレームを取り出す位置（秒）。短い動画では長さの半分を使う
+const POSTER_OFFSET_SECONDS: f64 = 1.0;
+const PREVIEW_SECONDS: u32 = 3;
+const PREVIEW_HEIGHT: u32 = 480;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Image,
+    // アニメーションGIF / アニメーションWebP
+    Animated,
+    Video,
+}
+
+impl MediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Image => "image",
+            MediaType::Animated => "animated",
+            MediaType::Video => "video",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaInfo {
+    pub media_type: MediaType,
+    pub duration_seconds: f64,
+    pub codec: String,
+    pub frame_rate: Option<f64>,
+    pub width: u32,
+    pub height: u32,
+    // 動画のコンテナに記録された撮影日時（ISO 8601）と撮影地（ISO 6709）。アニメーション画像では None
+    pub creation_time: Option<String>,
+    pub location: Option<String>,
+}
+
+// 先頭のバイト列から種類を判定する。静止画・アニメーションの区別は detect_animation で行う
+pub fn is_video(data: &[u8]) -> bool {
+    // MP4 / MOV は ftyp ボックス、WebM は EBML ヘッダーで始まる
+    let is_iso_bmff = data.len() >= 12 && &data[4..8] == b"ftyp" && !is_heif_brand(&data[8..12]);
+    let is_ebml = data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]);
+
+    is_iso_bmff || is_ebml
+}
+
+fn is_heif_brand(brand: &[u8]) -> bool {
+    matches!(brand, b"heic" | b"heix" | b"mif1" | b"msf1" | b"avif" | b"avis")
+}
+
+// 複数フレームを持つGIF / WebPの場合のみ情報を返す
+pub fn detect_animation(data: &[u8]) -> Result<Option<MediaInfo>, String> {
+    let format = match image::guess_format(data) {
+        Ok(format @ (ImageFormat::Gif | ImageFormat::WebP)) => format,
+        _ => return Ok(None),
+    };
+
+    let frames = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data))
+            .map_err(|e| format!("GIFのデコードに失敗: {}", e))?
+            .into_frames(),
+        _ => {
+            let decoder = WebPDecoder::new(Cursor::new(data))
+                .map_err(|e| format!("WebPのデコードに失敗: {}", e))?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+    };
+
+    let mut frame_count = 0u32;
+    let mut duration_ms = 0.0;
+    let mut dimensions = (0, 0);
+
+    for frame in frames {
+        let frame = frame.map_err(|e| format!("フレームのデコードに失敗: {}", e))?;
+        let (numer, denom) = frame.delay().numer_denom_ms();
+        duration_ms += numer as f64 / denom.max(1) as f64;
+        if frame_count == 0 {
+            dimensions = frame.buffer().dimensions();
+        }
+        frame_count += 1;
+    }
+
+    if frame_count < 2 {
+        return Ok(None);
+    }
+
+    let duration_seconds = duration_ms / 1000.0;
+
+    Ok(Some(MediaInfo {
+        media_type: MediaType::Animated,
+        duration_seconds,
+        codec: format.extensions_str()[0].to_string(),
+        frame_rate: (duration_seconds > 0.0).then(|| frame_count as f64 / duration_seconds),
+        width: dimensions.0,
+        height: dimensions.1,
+        creation_time: None,
+        location: None,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeOutput {
+    #[serde(default)]
+    streams: Vec<ProbeStream>,
+    format: Option<ProbeFormat>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeStream {
+    codec_name: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    avg_frame_rate: Option<String>,
+    #[serde(default)]
+    side_data_list: Vec<ProbeSideData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeSideData {
+    rotation: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ProbeFormat {
+    duration: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+// "30000/1001" 形式のフレームレート
+fn parse_frame_rate(value: &str) -> Option<f64> {
+    let (numer, denom) = value.split_once('/').unwrap_or((value, "1"));
+    let (numer, denom) = (numer.parse::<f64>().ok()?, denom.parse::<f64>().ok()?);
+
+    (numer > 0.0 && denom > 0.0).then(|| numer / denom)
+}
+
+pub fn parse_probe_output(json: &str) -> Result<MediaInfo, String> {
+    let output: ProbeOutput = serde_json::from_str(json)
+        .map_err(|e| format!("ffprobeの出力を解析できません: {}", e))?;
+
+    let stream = output
+        .streams
+        .into_iter()
+        .next()
+        .ok_or_else(|| "映像トラックがありません".to_string())?;
+
+    let (width, height) = match (stream.width, stream.height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => (w, h),
+        _ => return Err("映像のサイズを取得できません".to_string()),
+    };
+
+    // 縦向きで撮影された動画は表示時の向きに合わせる
+    let rotated = stream
+        .side_data_list
+        .iter()
+        .filter_map(|s| s.rotation)
+        .any(|r| r.rem_euclid(180) == 90);
+
+    let format = output.format;
+    // iPhone などは撮影地のタイムゾーン付きの日時を別のタグに記録する（creation_time は UTC）
+    let tag = |names: &[&str]| {
+        let tags = &format.as_ref()?.tags;
+        names.iter().find_map(|name| tags.get(*name)).cloned()
+    };
+    let creation_time = tag(&["com.apple.quicktime.creationdate", "creation_time"]);
+    let location = tag(&["com.apple.quicktime.location.ISO6709", "location"]);
+
+    Ok(MediaInfo {
+        media_type: MediaType::Video,
+        duration_seconds: format
+            .and_then(|f| f.duration)
+            .and_then(|d| d.parse().ok())
+            .unwrap_or(0.0),
+        codec: stream.codec_name.unwrap_or_else(|| "unknown".to_string()),
+        frame_rate: stream.avg_frame_rate.as_deref().and_then(parse_frame_rate),
+        width: if rotated { height } else { width },
+        height: if rotated { width } else { height },
+        creation_time,
+        location,
+    })
+}
+
+// ffmpeg / ffprobe はファイルを読むため、一時ファイルに書き出してから処理する
+pub struct TempMediaFile {
+    path: PathBuf,
+}
+
+impl TempMediaFile {
+    pub async fn create(name: &str, data: &[u8]) -> Result<Self, String> {
+        let path = std::env::temp_dir().join(format!("{}-{}", uuid::Uuid::new_v4(), name));
+
+        tokio::fs::write(&path, data)
+            .await
+            .map_err(|e| format!("一時ファイルの作成に失敗: {}", e))?;
+
+        Ok(TempMediaFile { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempMediaFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("{}を実行できません: {}", program, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}が失敗しました: {}", program, stderr.trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+pub async fn probe_video(path: &Path) -> Result<MediaInfo, String> {
+    let path = path.to_string_lossy();
+    let stdout = run("ffprobe", &[
+        "-v", "error",
+        "-select_streams", "v:0",
+        "-show_entries", "stream=codec_name,width,height,avg_frame_rate:stream_side_data=rotation:format=duration:format_tags",
+        "-of", "json",
+        &path,
+    ])
+    .await?;
+
+    parse_probe_output(&String::from_utf8_lossy(&stdout))
+}
+
+// 代表フレームをPNGで取り出す
+pub async fn extract_poster_frame(path: &Path, duration_seconds: f64) -> Result<Vec<u8>, String> {
+    let offset = POSTER_OFFSET_SECONDS.min(duration_seconds / 2.0).max(0.0);
+    let path = path.to_string_lossy();
+
+    run("ffmpeg", &[
+        "-v", "error",
+        "-ss", &format!("{:.3}", offset),
+        "-i", &path,
+        "-frames:v", "1",
+        "-f", "image2",
+        "-c:v", "png",
+        "pipe:1",
+    ])
+    .await
+}
+
+// 一覧でのホバー再生用に、先頭数秒を音声なしの小さなMP4にする
+pub async fn render_preview(path: &Path) -> Result<Vec<u8>, String> {
+    let output = TempMediaFile::create("preview.mp4", &[]).await?;
+    let input = path.to_string_lossy();
+    let output_path = output.path().to_string_lossy();
+
+    run("ffmpeg", &[
+        "-v", "error",
+        "-y",
+        "-i", &input,
+        "-t", &PREVIEW_SECONDS.to_string(),
+        "-an",
+        "-map_metadata", "-1",
+        "-vf", &format!("scale=-2:'min({},ih)'", PREVIEW_HEIGHT),
+        "-c:v", "libx264",
+        "-preset", "veryfast",
+        "-pix_fmt", "yuv420p",
+        "-movflags", "+faststart",
+        &output_path,
+    ])
+    .await?;
+
+    tokio::fs::read(output.path())
+        .await
+        .map_err(|e| format!("プレビューの読み込みに失敗: {}", e))
+}
+
+// 元の動画と同じコンテナで書き出すための (ffmpeg のフォーマット名, Content-Type, 拡張子)
+fn video_container(data: &[u8]) -> Option<(&'static str, &'static str, &'static str)> {
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return Some(if &data[8..12] == b"qt  " {
+            ("mov", "video/quicktime", "mov")
+        } else {
+            ("mp4", "video/mp4", "mp4")
+        });
+    }
+
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        let header = &data[..data.len().min(64)];
+        return Some(if header.windows(4).any(|w| w == b"webm") {
+            ("webm", "video/webm", "webm")
+        } else {
+            ("matroska", "video/x-matroska", "mkv")
+        });
+    }
+
+    None
+}
+
+// "location" / "location-eng"（Android）、"com.apple.quicktime.location.ISO6709" など（iPhone）
+fn is_location_tag(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.contains("location") || key.contains("gps") || key.ends_with("xyz")
+}
+
+async fn probe_format_tags(path: &Path) -> Result<HashMap<String, String>, String> {
+    let path = path.to_string_lossy();
+    let stdout = run("ffprobe", &["-v", "error", "-show_entries", "format_tags", "-of", "json", &path]).await?;
+
+    let output: ProbeOutput = serde_json::from_slice(&stdout)
+        .map_err(|e| format!("ffprobeの出力を解析できません: {}", e))?;
+
+    Ok(output.format.map(|format| format.tags).unwrap_or_default())
+}
+
+// 再エンコードせずにコンテナを作り直し、メタデータを取り除いた動画を返す。変更が不要な場合は None
+// 映像・音声以外のストリーム（位置情報を含みうるタイムドメタデータなど）は残さない
+// 回転はストリームの side data としてそのまま引き継がれる
+pub async fn strip_video_metadata(
+    path: &Path,
+    data: &[u8],
+    mode: MetadataStripMode,
+) -> Result<Option<StrippedImage>, String> {
+    let removed_tags = match mode {
+        MetadataStripMode::None => return Ok(None),
+        MetadataStripMode::Location => {
+            let tags = probe_format_tags(path).await?;
+            let keys: Vec<String> = tags.into_keys().filter(|key| is_location_tag(key)).collect();
+            if keys.is_empty() {
+                return Ok(None);
+            }
+            keys
+        }
+        MetadataStripMode::All => Vec::new(),
+    };
+
+    let (format, content_type, extension) = video_container(data)
+        .ok_or_else(|| "動画のコンテナ形式を判定できません".to_string())?;
+
+    let output = TempMediaFile::create(&format!("stripped.{}", extension), &[]).await?;
+    let input = path.to_string_lossy().into_owned();
+    let output_path = output.path().to_string_lossy().into_owned();
+
+    let mut args: Vec<String> = ["-v", "error", "-y", "-i", input.as_str(), "-map", "0:v", "-map", "0:a?", "-c", "copy"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+
+    match mode {
+        MetadataStripMode::All => args.extend(["-map_metadata".to_string(), "-1".to_string()]),
+        // 値を空にしたタグは書き出されない
+        _ => {
+            for key in &removed_tags {
+                args.extend(["-metadata".to_string(), format!("{}=", key)]);
+            }
+        }
+    }
+
+    if matches!(format, "mp4" | "mov") {
+        // 位置情報以外の独自タグ（撮影日時など）も書き戻す
+        args.extend(["-movflags".to_string(), "+faststart+use_metadata_tags".to_string()]);
+    }
+
+    args.extend(["-f".to_string(), format.to_string(), output_path]);
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    run("ffmpeg", &args).await?;
+
+    let bytes = tokio::fs::read(output.path())
+        .await
+        .map_err(|e| format!("メタデータを削除した動画の読み込みに失敗: {}", e))?;
+
+    Ok(Some(StrippedImage { bytes, content_type }))
+}
+
+// 動画の場合は代表フレームを、それ以外はそのまま返す（静止画として扱う処理用）
+pub async fn still_image(name: &str, data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !is_video(&data) {
+        return Ok(data);
+    }
+
+    let source = TempMediaFile::create(name, &data).await?;
+    let info = probe_video(source.path()).await?;
+
+    extract_poster_frame(source.path(), info.duration_seconds).await
+}
+
+// "photo.mp4" → "photo_preview.mp4"
+pub fn preview_key(original_key: &str) -> String {
+    let stem = original_key.rsplit_once('.').map_or(original_key, |(stem, _)| stem);
+    format!("{}_preview.mp4", stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba, RgbaImage};
+
+    #[test]
+    fn test_is_video() {
+        let mut mp4 = vec![0, 0, 0, 0x18];
+        mp4.extend_from_slice(b"ftypisom\0\0\0\0");
+        let mut heic = vec![0, 0, 0, 0x18];
+        heic.extend_from_slice(b"ftypheic\0\0\0\0");
+
+        assert!(is_video(&mp4));
+        assert!(is_video(&[0x1A, 0x45, 0xDF, 0xA3, 0x01]));
+        assert!(!is_video(&heic));
+        assert!(!is_video(&[0xFF, 0xD8, 0xFF, 0xE0]));
+    }
+
+    #[test]
+    fn test_video_container() {
+        let mut mov = vec![0, 0, 0, 0x14];
+        mov.extend_from_slice(b"ftypqt  \0\0\0\0");
+        let mut mp4 = vec![0, 0, 0, 0x18];
+        mp4.extend_from_slice(b"ftypisom\0\0\0\0");
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+        webm.extend_from_slice(b"webm");
+
+        assert_eq!(video_container(&mov).map(|c| c.0), Some("mov"));
+        assert_eq!(video_container(&mp4).map(|c| c.0), Some("mp4"));
+        assert_eq!(video_container(&webm).map(|c| c.0), Some("webm"));
+        assert_eq!(video_container(&[0x1A, 0x45, 0xDF, 0xA3]).map(|c| c.0), Some("matroska"));
+        assert_eq!(video_container(&[0xFF, 0xD8, 0xFF, 0xE0]), None);
+    }
+
+    #[test]
+    fn test_is_location_tag() {
+        assert!(is_location_tag("location"));
+        assert!(is_location_tag("location-eng"));
+        assert!(is_location_tag("com.apple.quicktime.location.ISO6709"));
+        assert!(!is_location_tag("creation_time"));
+        assert!(!is_location_tag("com.apple.quicktime.make"));
+    }
+
+    #[test]
+    fn test_detect_animation() {
+        let mut data = Vec::new();
+        {
+            let mut encoder = GifEncoder::new(&mut data);
+            for color in [[255, 0, 0, 255], [0, 0, 255, 255]] {
+                let frame = Frame::from_parts(
+                    RgbaImage::from_pixel(8, 6, Rgba(color)),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(500, 1),
+                );
+                encoder.encode_frame(frame).unwrap();
+            }
+        }
+
+        let info = detect_animation(&data).unwrap().unwrap();
+        assert_eq!(info.media_type, MediaType::Animated);
+        assert_eq!((info.width, info.height), (8, 6));
+        assert!((info.duration_seconds - 1.0).abs() < 0.01);
+        assert!((info.frame_rate.unwrap() - 2.0).abs() < 0.01);
+        assert_eq!(info.codec, "gif");
+    }
+
+    #[test]
+    fn test_parse_probe_output() {
+        let json = r#"{
+            "streams": [{
+                "codec_name": "h264",
+                "width": 1920,
+                "height": 1080,
+                "avg_frame_rate": "30000/1001",
+                "side_data_list": [{ "rotation": -90 }]
+            }],
+            "format": {
+                "duration": "12.345000",
+                "tags": {
+                    "creation_time": "2024-05-01T10:20:30.000000Z",
+                    "com.apple.quicktime.creationdate": "2024-05-01T19:20:30+0900"
+                }
+            }
+        }"#;
+
+        let info = parse_probe_output(json).unwrap();
+        assert_eq!(info.codec, "h264");
+        assert_eq!((info.width, info.height), (1080, 1920));
+        assert!((info.frame_rate.unwrap() - 29.97).abs() < 0.01);
+        assert!((info.duration_seconds - 12.345).abs() < 0.001);
+        assert_eq!(info.creation_time.as_deref(), Some("2024-05-01T19:20:30+0900"));
+        assert_eq!(info.location, None);
+
+        assert!(parse_probe_output(r#"{ "streams": [] }"#).is_err());
+    }
+
+    #[test]
+    fn test_preview_key() {
+        assert_eq!(preview_key("abc.mov"), "abc_preview.mp4");
+    }
+}
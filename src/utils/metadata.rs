@@ -0,0 +1,369 @@
+use std::io::Cursor;
+use exif::{In, Tag, Value};
+use time::{Date, Month, PrimitiveDateTime, Time, UtcOffset};
+
+// 画像から取り出した撮影情報。EXIFに無い項目はXMPから補う
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ExtractedMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens_model: Option<String>,
+    pub focal_length: Option<f64>,
+    pub aperture: Option<f64>,
+    pub exposure_time: Option<String>,
+    pub iso: Option<i32>,
+    pub taken_at: Option<PrimitiveDateTime>,
+    pub timezone_offset_minutes: Option<i32>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+pub fn extract_metadata(data: &[u8]) -> ExtractedMetadata {
+    let mut metadata = ExtractedMetadata::default();
+
+    if let Ok(exif) = exif::Reader::new().read_from_container(&mut Cursor::new(data)) {
+        read_exif(&exif, &mut metadata);
+    }
+
+    if let Some(xmp) = find_xmp_packet(data) {
+        read_xmp(xmp, &mut metadata);
+    }
+
+    metadata
+}
+
+fn read_exif(exif: &exif::Exif, metadata: &mut ExtractedMetadata) {
+    let field = |tag: Tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+
+    metadata.camera_make = field(Tag::Make).and_then(ascii_value);
+    metadata.camera_model = field(Tag::Model).and_then(ascii_value);
+    metadata.lens_model = field(Tag::LensModel).and_then(ascii_value);
+    metadata.focal_length = field(Tag::FocalLength).and_then(|v| rational_value(v, 0));
+    metadata.aperture = field(Tag::FNumber).and_then(|v| rational_value(v, 0));
+    metadata.exposure_time = field(Tag::ExposureTime).and_then(exposure_time_value);
+    metadata.iso = field(Tag::PhotographicSensitivity)
+        .and_then(|v| v.get_uint(0))
+        .map(|iso| iso as i32);
+
+    let date_time = [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(date_tag, offset_tag)| {
+        let Some(Value::Ascii(date)) = field(date_tag) else {
+            return None;
+        };
+        let mut date_time = exif::DateTime::from_ascii(date.first()?).ok()?;
+
+        if let Some(Value::Ascii(offset)) = field(offset_tag) {
+            if let Some(offset) = offset.first() {
+                let _ = date_time.parse_offset(offset);
+            }
+        }
+
+        Some(date_time)
+    });
+
+    if let Some(date_time) = date_time {
+        metadata.taken_at = primitive_date_time(
+            date_time.year as i32,
+            date_time.month,
+            date_time.day,
+            date_time.hour,
+            date_time.minute,
+            date_time.second,
+        );
+        metadata.timezone_offset_minutes = date_time.offset.map(i32::from);
+    }
+
+    metadata.latitude = gps_coordinate(field(Tag::GPSLatitude), field(Tag::GPSLatitudeRef), b'S');
+    metadata.longitude = gps_coordinate(field(Tag::GPSLongitude), field(Tag::GPSLongitudeRef), b'W');
+    metadata.altitude = field(Tag::GPSAltitude)
+        .and_then(|v| rational_value(v, 0))
+        .map(|altitude| match field(Tag::GPSAltitudeRef).and_then(|v| v.get_uint(0)) {
+            Some(1) => -altitude,
+            _ => altitude,
+        });
+}
+
+fn ascii_value(value: &Value) -> Option<String> {
+    let Value::Ascii(values) = value else {
+        return None;
+    };
+
+    let text = String::from_utf8_lossy(values.first()?)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string();
+
+    (!text.is_empty()).then_some(text)
+}
+
+fn rational_value(value: &Value, index: usize) -> Option<f64> {
+    match value {
+        Value::Rational(values) => values.get(index).filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        Value::SRational(values) => values.get(index).filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        _ => None,
+    }
+}
+
+// 1秒未満は "1/250" の形式にする
+fn exposure_time_value(value: &Value) -> Option<String> {
+    let seconds = rational_value(value, 0)?;
+
+    if seconds <= 0.0 {
+        None
+    } else if seconds < 1.0 {
+        Some(format!("1/{}", (1.0 / seconds).round()))
+    } else {
+        Some(format!("{}", (seconds * 10.0).round() / 10.0))
+    }
+}
+
+fn gps_coordinate(value: Option<&Value>, reference: Option<&Value>, negative_ref: u8) -> Option<f64> {
+    let value = value?;
+    let degrees = rational_value(value, 0)?;
+    let minutes = rational_value(value, 1).unwrap_or(0.0);
+    let seconds = rational_value(value, 2).unwrap_or(0.0);
+
+    let coordinate = degrees + minutes / 60.0 + seconds / 3600.0;
+
+    let is_negative = matches!(
+        reference,
+        Some(Value::Ascii(refs)) if refs.first().and_then(|r| r.first()) == Some(&negative_ref)
+    );
+
+    Some(if is_negative { -coordinate } else { coordinate })
+}
+
+fn primitive_date_time(year: i32, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Option<PrimitiveDateTime> {
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+    let time = Time::from_hms(hour, minute, second).ok()?;
+
+    Some(PrimitiveDateTime::new(date, time))
+}
+
+fn find_xmp_packet(data: &[u8]) -> Option<&str> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+
+    let start = data.windows(START.len()).position(|w| w == START)?;
+    let end = data[start..].windows(END.len()).position(|w| w == END)? + start + END.len();
+
+    std::str::from_utf8(&data[start..end]).ok()
+}
+
+fn read_xmp(xmp: &str, metadata: &mut ExtractedMetadata) {
+    if metadata.camera_make.is_none() {
+        metadata.camera_make = xmp_value(xmp, "tiff:Make");
+    }
+    if metadata.camera_model.is_none() {
+        metadata.camera_model = xmp_value(xmp, "tiff:Model");
+    }
+    if metadata.lens_model.is_none() {
+        metadata.lens_model = xmp_value(xmp, "exifEX:LensModel").or_else(|| xmp_value(xmp, "aux:Lens"));
+    }
+    if metadata.taken_at.is_none() {
+        let date = xmp_value(xmp, "exif:DateTimeOriginal")
+            .or_else(|| xmp_value(xmp, "photoshop:DateCreated"))
+            .or_else(|| xmp_value(xmp, "xmp:CreateDate"));
+
+        if let Some((taken_at, offset)) = date.as_deref().and_then(parse_xmp_date) {
+            metadata.taken_at = Some(taken_at);
+            metadata.timezone_offset_minutes = offset;
+        }
+    }
+    if metadata.latitude.is_none() || metadata.longitude.is_none() {
+        let latitude = xmp_value(xmp, "exif:GPSLatitude").as_deref().and_then(parse_xmp_gps);
+        let longitude = xmp_value(xmp, "exif:GPSLongitude").as_deref().and_then(parse_xmp_gps);
+
+        if let (Some(latitude), Some(longitude)) = (latitude, longitude) {
+            metadata.latitude = Some(latitude);
+            metadata.longitude = Some(longitude);
+        }
+    }
+}
+
+// 属性形式（name="value"）と要素形式（<name>value</name>）の両方に対応
+fn xmp_value(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!("{}=\"", name);
+    if let Some(start) = xmp.find(&attribute).map(|i| i + attribute.len()) {
+        let end = xmp[start..].find('"')? + start;
+        return Some(xmp[start..end].trim().to_string()).filter(|v| !v.is_empty());
+    }
+
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = xmp.find(&open)? + open.len();
+    let end = xmp[start..].find(&close)? + start;
+
+    Some(xmp[start..end].trim().to_string()).filter(|v| !v.is_empty() && !v.starts_with('<'))
+}
+
+// 例: "2024-03-01T10:20:30+09:00" / "2024-03-01T10:20:30+0900" / "2024-03-01T10:20:30" / "2024-03-01T10:20"
+fn parse_xmp_date(value: &str) -> Option<(PrimitiveDateTime, Option<i32>)> {
+    let (date, rest) = value.split_once('T').unwrap_or((value, "00:00:00"));
+
+    let mut date_parts = date.split('-').map(|p| p.parse::<u16>().ok());
+    let year = date_parts.next()??;
+    let month = date_parts.next().unwrap_or(Some(1))?;
+    let day = date_parts.next().unwrap_or(Some(1))?;
+
+    let offset_start = rest.find(['+', '-', 'Z']);
+    let (time, offset) = match offset_start {
+        Some(i) => (&rest[..i], Some(&rest[i..])),
+        None => (rest, None),
+    };
+
+    let mut time_parts = time.split(':').map(|p| p.split('.').next().and_then(|p| p.parse::<u8>().ok()));
+    let hour = time_parts.next().flatten().unwrap_or(0);
+    let minute = time_parts.next().flatten().unwrap_or(0);
+    let second = time_parts.next().flatten().unwrap_or(0);
+
+    let offset_minutes = match offset {
+        Some("Z") => Some(0),
+        Some(offset) => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (h, m) = match offset[1..].split_once(':') {
+                Some(parts) => parts,
+                // "+0900" 形式
+                None if offset.len() == 5 => (&offset[1..3], &offset[3..]),
+                None => (&offset[1..], "0"),
+            };
+            Some(sign * (h.parse::<i32>().ok()? * 60 + m.parse::<i32>().ok()?))
+        }
+        None => None,
+    };
+
+    let taken_at = primitive_date_time(year as i32, month as u8, day as u8, hour, minute, second)?;
+
+    Some((taken_at, offset_minutes))
+}
+
+// 動画のコンテナに記録された撮影日時・撮影地で、EXIF・XMP に無い項目を補う
+pub fn apply_video_tags(metadata: &mut ExtractedMetadata, creation_time: Option<&str>, location: Option<&str>) {
+    if metadata.taken_at.is_none() {
+        if let Some((taken_at, offset)) = creation_time.and_then(parse_xmp_date) {
+            metadata.taken_at = Some(taken_at);
+            metadata.timezone_offset_minutes = offset;
+        }
+    }
+    if metadata.latitude.is_none() || metadata.longitude.is_none() {
+        if let Some((latitude, longitude, altitude)) = location.and_then(parse_iso6709) {
+            metadata.latitude = Some(latitude);
+            metadata.longitude = Some(longitude);
+            metadata.altitude = metadata.altitude.or(altitude);
+        }
+    }
+}
+
+// 例: "+35.6586+139.7454/" / "+35.6586+139.7454+010.000/"
+fn parse_iso6709(value: &str) -> Option<(f64, f64, Option<f64>)> {
+    let value = value.trim().trim_end_matches('/');
+    let starts: Vec<usize> = value.match_indices(['+', '-']).map(|(i, _)| i).collect();
+    let numbers: Vec<f64> = starts
+        .iter()
+        .zip(starts.iter().skip(1).copied().chain(std::iter::once(value.len())))
+        .map(|(&start, end)| value[start..end].parse::<f64>().ok())
+        .collect::<Option<_>>()?;
+
+    match numbers.as_slice() {
+        [latitude, longitude] => Some((*latitude, *longitude, None)),
+        [latitude, longitude, altitude] => Some((*latitude, *longitude, Some(*altitude))),
+        _ => None,
+    }
+}
+
+// 例: "35,40.5N" / "139,45,30E"
+fn parse_xmp_gps(value: &str) -> Option<f64> {
+    let direction = value.chars().last().filter(|c| c.is_ascii_alphabetic())?;
+    let numbers: Vec<f64> = value[..value.len() - 1]
+        .split(',')
+        .map(|p| p.trim().parse::<f64>().ok())
+        .collect::<Option<_>>()?;
+
+    let coordinate = match numbers.as_slice() {
+        [degrees] => *degrees,
+        [degrees, minutes] => degrees + minutes / 60.0,
+        [degrees, minutes, seconds] => degrees + minutes / 60.0 + seconds / 3600.0,
+        _ => return None,
+    };
+
+    Some(match direction {
+        'S' | 'W' => -coordinate,
+        _ => coordinate,
+    })
+}
+
+pub fn utc_offset(minutes: i32) -> Option<UtcOffset> {
+    UtcOffset::from_whole_seconds(minutes * 60).ok()
+}
+
+// 例: 540 → "+09:00"
+pub fn format_utc_offset(minutes: i32) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    format!("{}{:02}:{:02}", sign, minutes.abs() / 60, minutes.abs() % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xmp_date_with_offset() {
+        let (taken_at, offset) = parse_xmp_date("2024-03-01T10:20:30+09:00").unwrap();
+        assert_eq!(taken_at, primitive_date_time(2024, 3, 1, 10, 20, 30).unwrap());
+        assert_eq!(offset, Some(540));
+
+        let (_, offset) = parse_xmp_date("2024-03-01T10:20:30.25Z").unwrap();
+        assert_eq!(offset, Some(0));
+
+        let (_, offset) = parse_xmp_date("2024-03-01T10:20:30-0330").unwrap();
+        assert_eq!(offset, Some(-210));
+
+        let (taken_at, offset) = parse_xmp_date("2024-03-01T10:20").unwrap();
+        assert_eq!(taken_at, primitive_date_time(2024, 3, 1, 10, 20, 0).unwrap());
+        assert_eq!(offset, None);
+    }
+
+    #[test]
+    fn test_read_xmp_fills_missing_fields() {
+        let xmp = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:Description
+            tiff:Make="FUJIFILM"
+            exif:GPSLatitude="35,0.6N"
+            exif:GPSLongitude="135,46.2W">
+            <tiff:Model>X-T5</tiff:Model>
+            <exif:DateTimeOriginal>2023-11-03T08:00:00-05:00</exif:DateTimeOriginal>
+        </rdf:Description></x:xmpmeta>"#;
+
+        let metadata = extract_metadata(xmp.as_bytes());
+
+        assert_eq!(metadata.camera_make.as_deref(), Some("FUJIFILM"));
+        assert_eq!(metadata.camera_model.as_deref(), Some("X-T5"));
+        assert_eq!(metadata.timezone_offset_minutes, Some(-300));
+        assert!((metadata.latitude.unwrap() - 35.01).abs() < 1e-9);
+        assert!((metadata.longitude.unwrap() + 135.77).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_apply_video_tags() {
+        let mut metadata = ExtractedMetadata::default();
+        apply_video_tags(&mut metadata, Some("2024-05-01T19:20:30+0900"), Some("+35.6586-139.7454+010.000/"));
+
+        assert_eq!(metadata.taken_at, primitive_date_time(2024, 5, 1, 19, 20, 30));
+        assert_eq!(metadata.timezone_offset_minutes, Some(540));
+        assert_eq!(metadata.latitude, Some(35.6586));
+        assert_eq!(metadata.longitude, Some(-139.7454));
+        assert_eq!(metadata.altitude, Some(10.0));
+
+        assert_eq!(parse_iso6709("+35.6586"), None);
+    }
+
+    #[test]
+    fn test_format_utc_offset() {
+        assert_eq!(format_utc_offset(540), "+09:00");
+        assert_eq!(format_utc_offset(-210), "-03:30");
+    }
+}
@@ -0,0 +1,379 @@
+use std::io::Cursor;
+use exif::experimental::Writer;
+use exif::{Context, In, Tag};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat};
+
+use crate::models::privacy::MetadataStripMode;
+use crate::utils::image_processing::decode_image;
+
+const JPEG_QUALITY: u8 = 92;
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+
+const MARKER_SOI: u8 = 0xD8;
+const MARKER_SOS: u8 = 0xDA;
+const MARKER_APP1: u8 = 0xE1;
+const MARKER_APP13: u8 = 0xED;
+const MARKER_COM: u8 = 0xFE;
+
+// VP8X チャンクのフラグ
+const VP8X_FLAG_EXIF: u8 = 0x08;
+const VP8X_FLAG_XMP: u8 = 0x04;
+const VP8X_FLAG_ANIMATION: u8 = 0x02;
+
+pub struct StrippedImage {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+// メタデータを削除した画像を返す。変更が不要な場合は None
+// 向き情報（Orientation）が付いている場合は画素に反映してから削除するため、削除後も正しい向きで表示される
+pub fn strip_metadata(data: &[u8], mode: MetadataStripMode) -> Result<Option<StrippedImage>, String> {
+    if mode == MetadataStripMode::None {
+        return Ok(None);
+    }
+
+    let format = image::guess_format(data).map_err(|e| format!("画像形式の判定に失敗: {}", e))?;
+
+    let bytes = match format {
+        ImageFormat::Jpeg => strip_jpeg(data, mode)?,
+        // アニメーションWebPは再エンコードするとフレームが失われるため、メタデータのチャンクだけを取り除く
+        ImageFormat::WebP if is_animated_webp(data) => strip_webp_chunks(data, mode)?,
+        // JPEG以外はデコードし直してメタデータを持たない画像として書き出す
+        _ => {
+            let img = decode_image(data)?;
+            let mut buf = Cursor::new(Vec::new());
+            img.write_to(&mut buf, format)
+                .map_err(|e| format!("画像の再エンコードに失敗: {}", e))?;
+            buf.into_inner()
+        }
+    };
+
+    if bytes == data {
+        return Ok(None);
+    }
+
+    Ok(Some(StrippedImage {
+        bytes,
+        content_type: format.to_mime_type(),
+    }))
+}
+
+fn strip_jpeg(data: &[u8], mode: MetadataStripMode) -> Result<Vec<u8>, String> {
+    let exif = exif::Reader::new().read_from_container(&mut Cursor::new(data)).ok();
+
+    let orientation = exif
+        .as_ref()
+        .and_then(|e| e.get_field(Tag::Orientation, In::PRIMARY))
+        .and_then(|f| f.value.get_uint(0))
+        .unwrap_or(1);
+
+    let needs_rotation = orientation != 1;
+
+    // 位置情報だけを消す場合は、GPS以外のEXIFを書き直して残す
+    let rebuilt_exif = match (mode, &exif) {
+        (MetadataStripMode::Location, Some(exif)) => rebuild_exif_without_location(exif, needs_rotation),
+        _ => None,
+    };
+
+    let source = if needs_rotation {
+        reencode_jpeg(&decode_image(data)?)?
+    } else {
+        data.to_vec()
+    };
+
+    let (segments, scan) = split_jpeg_segments(&source)
+        .ok_or_else(|| "JPEGの構造を解析できませんでした".to_string())?;
+
+    let mut out = Vec::with_capacity(source.len());
+    out.extend_from_slice(&[0xFF, MARKER_SOI]);
+
+    if let Some(exif) = rebuilt_exif {
+        // APP1 のサイズ上限（64KB）を超える場合は付けない
+        if let Ok(length) = u16::try_from(exif.len() + EXIF_HEADER.len() + 2) {
+            out.extend_from_slice(&[0xFF, MARKER_APP1]);
+            out.extend_from_slice(&length.to_be_bytes());
+            out.extend_from_slice(EXIF_HEADER);
+            out.extend_from_slice(&exif);
+        }
+    }
+
+    for (marker, segment) in segments {
+        // APP1: EXIF/XMP, APP13: IPTC（地名などを含む）, COM: コメント
+        let drop = match marker {
+            MARKER_APP1 | MARKER_APP13 => true,
+            MARKER_COM => mode == MetadataStripMode::All,
+            _ => false,
+        };
+
+        if !drop {
+            out.extend_from_slice(segment);
+        }
+    }
+
+    out.extend_from_slice(scan);
+
+    Ok(out)
+}
+
+fn reencode_jpeg(img: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY);
+
+    DynamicImage::ImageRgb8(img.to_rgb8())
+        .write_with_encoder(encoder)
+        .map_err(|e| format!("JPEGの再エンコードに失敗: {}", e))?;
+
+    Ok(buf)
+}
+
+fn rebuild_exif_without_location(exif: &exif::Exif, drop_orientation: bool) -> Option<Vec<u8>> {
+    let mut writer = Writer::new();
+    let mut has_fields = false;
+
+    for field in exif.fields() {
+        let keep = field.ifd_num == In::PRIMARY
+            && field.tag.context() != Context::Gps
+            // メーカーノートは独自形式で位置情報を含むことがあるため残さない
+            && field.tag != Tag::MakerNote
+            && !(drop_orientation && field.tag == Tag::Orientation);
+
+        if keep {
+            writer.push_field(field);
+            has_fields = true;
+        }
+    }
+
+    if !has_fields {
+        return None;
+    }
+
+    let mut buf = Cursor::new(Vec::new());
+    writer.write(&mut buf, exif.little_endian()).ok()?;
+
+    Some(buf.into_inner())
+}
+
+// RIFF のチャンク（FourCC, 中身）
+fn split_webp_chunks(data: &[u8]) -> Option<Vec<([u8; 4], &[u8])>> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+
+    while pos < data.len() {
+        let fourcc: [u8; 4] = data.get(pos..pos + 4)?.try_into().ok()?;
+        let size = u32::from_le_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        let payload = data.get(pos + 8..pos + 8 + size)?;
+        chunks.push((fourcc, payload));
+        // 奇数長のチャンクは1バイトの詰め物が続く
+        pos += 8 + size + (size & 1);
+    }
+
+    Some(chunks)
+}
+
+fn is_animated_webp(data: &[u8]) -> bool {
+    split_webp_chunks(data)
+        .and_then(|chunks| chunks.into_iter().find(|(fourcc, _)| fourcc == b"VP8X"))
+        .and_then(|(_, payload)| payload.first().copied())
+        .is_some_and(|flags| flags & VP8X_FLAG_ANIMATION != 0)
+}
+
+// XMP は位置情報を含みうるため常に取り除き、EXIF は位置情報だけを消す場合は GPS 以外を書き直して残す
+fn strip_webp_chunks(data: &[u8], mode: MetadataStripMode) -> Result<Vec<u8>, String> {
+    let chunks = split_webp_chunks(data).ok_or_else(|| "WebPの構造を解析できませんでした".to_string())?;
+
+    let exif = match mode {
+        MetadataStripMode::Location => chunks
+            .iter()
+            .find(|(fourcc, _)| fourcc == b"EXIF")
+            .and_then(|(_, payload)| {
+                let tiff = payload.strip_prefix(EXIF_HEADER).unwrap_or(payload);
+                exif::Reader::new().read_raw(tiff.to_vec()).ok()
+            })
+            .and_then(|exif| rebuild_exif_without_location(&exif, false)),
+        _ => None,
+    };
+
+    let mut body = b"WEBP".to_vec();
+    let mut push_chunk = |fourcc: &[u8], payload: &[u8]| {
+        body.extend_from_slice(fourcc);
+        body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        body.extend_from_slice(payload);
+        if payload.len() % 2 == 1 {
+            body.push(0);
+        }
+    };
+
+    for (fourcc, payload) in &chunks {
+        match fourcc {
+            b"VP8X" => {
+                let mut payload = payload.to_vec();
+                if let Some(flags) = payload.first_mut() {
+                    *flags &= !VP8X_FLAG_XMP;
+                    if exif.is_none() {
+                        *flags &= !VP8X_FLAG_EXIF;
+                    }
+                }
+                push_chunk(fourcc, &payload);
+            }
+            b"EXIF" => {
+                if let Some(exif) = &exif {
+                    push_chunk(fourcc, exif);
+                }
+            }
+            b"XMP " => {}
+            _ => push_chunk(fourcc, payload),
+        }
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend_from_slice(&body);
+
+    Ok(out)
+}
+
+// SOS より前のセグメント（マーカー, セグメント全体）と、SOS 以降の画像データ
+type JpegSegments<'a> = (Vec<(u8, &'a [u8])>, &'a [u8]);
+
+fn split_jpeg_segments(data: &[u8]) -> Option<JpegSegments<'_>> {
+    if data.len() < 4 || data[0] != 0xFF || data[1] != MARKER_SOI {
+        return None;
+    }
+
+    let mut segments = Vec::new();
+    let mut pos = 2;
+
+    loop {
+        if *data.get(pos)? != 0xFF {
+            return None;
+        }
+
+        let start = pos;
+        while *data.get(pos)? == 0xFF {
+            pos += 1;
+        }
+
+        let marker = *data.get(pos)?;
+        pos += 1;
+
+        if marker == MARKER_SOS {
+            return Some((segments, &data[start..]));
+        }
+
+        // 長さを持たないマーカー
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            segments.push((marker, &data[start..pos]));
+            continue;
+        }
+
+        let length = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+        let end = pos + length;
+        if length < 2 || end > data.len() {
+            return None;
+        }
+
+        segments.push((marker, &data[start..end]));
+        pos = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jpeg_with_segments(extra: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let encoder = JpegEncoder::new_with_quality(&mut buf, 80);
+        DynamicImage::new_rgb8(8, 8).write_with_encoder(encoder).unwrap();
+
+        let mut out = vec![0xFF, MARKER_SOI];
+        for (marker, payload) in extra {
+            out.extend_from_slice(&[0xFF, *marker]);
+            out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+            out.extend_from_slice(payload);
+        }
+        out.extend_from_slice(&buf[2..]);
+        out
+    }
+
+    fn markers(data: &[u8]) -> Vec<u8> {
+        split_jpeg_segments(data).unwrap().0.into_iter().map(|(m, _)| m).collect()
+    }
+
+    #[test]
+    fn test_strip_all_removes_metadata_segments() {
+        let xmp = b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta></x:xmpmeta>";
+        let data = jpeg_with_segments(&[(MARKER_APP1, xmp), (MARKER_APP13, b"Photoshop 3.0\0"), (MARKER_COM, b"hello")]);
+
+        let stripped = strip_metadata(&data, MetadataStripMode::All).unwrap().unwrap();
+        let remaining = markers(&stripped.bytes);
+
+        assert!(!remaining.contains(&MARKER_APP1));
+        assert!(!remaining.contains(&MARKER_APP13));
+        assert!(!remaining.contains(&MARKER_COM));
+        assert_eq!(stripped.content_type, "image/jpeg");
+        assert!(decode_image(&stripped.bytes).is_ok());
+    }
+
+    #[test]
+    fn test_strip_location_keeps_comments() {
+        let data = jpeg_with_segments(&[(MARKER_COM, b"hello")]);
+        assert!(strip_metadata(&data, MetadataStripMode::Location).unwrap().is_none());
+    }
+
+    fn animated_webp(extra: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WEBP".to_vec();
+        let mut flags = VP8X_FLAG_ANIMATION;
+        let mut chunks: Vec<(&[u8; 4], &[u8])> = vec![(b"ANIM", &[0; 6]), (b"ANMF", &[0; 16])];
+        for &(fourcc, payload) in extra {
+            flags |= match fourcc {
+                b"EXIF" => VP8X_FLAG_EXIF,
+                b"XMP " => VP8X_FLAG_XMP,
+                _ => 0,
+            };
+            chunks.push((fourcc, payload));
+        }
+
+        let vp8x = [flags, 0, 0, 0, 7, 0, 0, 7, 0, 0];
+        chunks.insert(0, (b"VP8X", &vp8x));
+        for (fourcc, payload) in chunks {
+            body.extend_from_slice(fourcc);
+            body.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            body.extend_from_slice(payload);
+            if payload.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    #[test]
+    fn test_strip_animated_webp_keeps_frames() {
+        let data = animated_webp(&[(b"EXIF", b"II*\0\x08\0\0\0\0\0"), (b"XMP ", b"<x:xmpmeta/>")]);
+        assert!(is_animated_webp(&data));
+
+        let stripped = strip_metadata(&data, MetadataStripMode::All).unwrap().unwrap();
+        let chunks = split_webp_chunks(&stripped.bytes).unwrap();
+        let fourccs: Vec<&[u8; 4]> = chunks.iter().map(|(fourcc, _)| fourcc).collect();
+
+        assert_eq!(fourccs, vec![b"VP8X", b"ANIM", b"ANMF"]);
+        assert_eq!(chunks[0].1[0], VP8X_FLAG_ANIMATION);
+        assert_eq!(stripped.content_type, "image/webp");
+        assert_eq!(u32::from_le_bytes(stripped.bytes[4..8].try_into().unwrap()) as usize, stripped.bytes.len() - 8);
+    }
+
+    #[test]
+    fn test_strip_none_is_noop() {
+        let data = jpeg_with_segments(&[(MARKER_COM, b"hello")]);
+        assert!(strip_metadata(&data, MetadataStripMode::None).unwrap().is_none());
+    }
+}
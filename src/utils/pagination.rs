@@ -0,0 +1,49 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+// キーセットページネーションのカーソル。前のページの最後の行の並び替えキーを持つ
+// 並び順が変わると位置の意味が変わるため、並び順もあわせて保存して照合する
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PageCursor {
+    pub sort: String,
+    pub descending: bool,
+    pub num_key: i64,
+    pub text_key: String,
+    pub id: i32,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        // 文字列と数値だけなのでシリアライズには失敗しない
+        let json = serde_json::to_vec(self).expect("カーソルのシリアライズに失敗");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(value: &str) -> Result<Self, String> {
+        let invalid = || "カーソルが不正です".to_string();
+        let json = URL_SAFE_NO_PAD.decode(value).map_err(|_| invalid())?;
+
+        serde_json::from_slice(&json).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = PageCursor {
+            sort: "name".to_string(),
+            descending: false,
+            num_key: 0,
+            text_key: "京都 2024".to_string(),
+            id: 42,
+        };
+
+        assert_eq!(PageCursor::decode(&cursor.encode()), Ok(cursor));
+        assert!(PageCursor::decode("not a cursor").is_err());
+        assert!(PageCursor::decode(&URL_SAFE_NO_PAD.encode("{}")).is_err());
+    }
+}
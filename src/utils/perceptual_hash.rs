@@ -0,0 +1,121 @@
+use std::f64::consts::PI;
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+const SAMPLE_SIZE: usize = 32;
+const HASH_SIZE: usize = 8;
+pub const BAND_COUNT: usize = 8;
+
+// pHash: 32x32 のグレースケール画像に DCT をかけ、低周波 8x8 成分が中央値より大きいかを 64bit で表す
+pub fn perceptual_hash(img: &DynamicImage) -> i64 {
+    let gray = img
+        .resize_exact(SAMPLE_SIZE as u32, SAMPLE_SIZE as u32, FilterType::Triangle)
+        .to_luma8();
+
+    let pixels: Vec<f64> = gray.pixels().map(|p| p[0] as f64).collect();
+
+    let cosines: Vec<Vec<f64>> = (0..HASH_SIZE)
+        .map(|u| {
+            (0..SAMPLE_SIZE)
+                .map(|x| ((2 * x + 1) as f64 * u as f64 * PI / (2 * SAMPLE_SIZE) as f64).cos())
+                .collect()
+        })
+        .collect();
+
+    let mut coefficients = Vec::with_capacity(HASH_SIZE * HASH_SIZE);
+    for v in 0..HASH_SIZE {
+        for u in 0..HASH_SIZE {
+            let mut sum = 0.0;
+            for y in 0..SAMPLE_SIZE {
+                for x in 0..SAMPLE_SIZE {
+                    sum += pixels[y * SAMPLE_SIZE + x] * cosines[u][x] * cosines[v][y];
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    // 直流成分（平均の明るさ）は中央値の計算から除く
+    let mut sorted: Vec<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let median = sorted[sorted.len() / 2];
+
+    coefficients
+        .iter()
+        .enumerate()
+        .fold(0u64, |hash, (i, &c)| if c > median { hash | (1 << i) } else { hash }) as i64
+}
+
+pub fn hamming_distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+// インデックス用に 8bit ずつ分割する
+pub fn hash_bands(hash: i64) -> [i16; BAND_COUNT] {
+    std::array::from_fn(|band| ((hash as u64 >> (band * 8)) & 0xFF) as i16)
+}
+
+// 距離が threshold 以下の写真を区間インデックスから探すための (区間, 値) の組
+// 距離が threshold 以下なら、少なくとも 1 区間は threshold / 区間数 bit 以内の違いに収まる
+pub fn band_probes(hash: i64, threshold: u32) -> (Vec<i16>, Vec<i16>) {
+    let radius = threshold / BAND_COUNT as u32;
+    let masks: Vec<i16> = (0..=0xFF_i16).filter(|mask| mask.count_ones() <= radius).collect();
+
+    hash_bands(hash)
+        .iter()
+        .enumerate()
+        .flat_map(|(band, &value)| masks.iter().map(move |mask| (band as i16, value ^ mask)))
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn pattern(width: u32, height: u32, offset: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let fx = x as f64 / width as f64;
+            let fy = y as f64 / height as f64;
+            let v = 100.0 + 60.0 * (fx * 7.0).sin() * (fy * 5.0).cos() + 40.0 * fx;
+            let v = (v as u8).saturating_add(offset);
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn test_similar_images_have_close_hashes() {
+        let original = perceptual_hash(&pattern(400, 300, 0));
+        let resized = perceptual_hash(&pattern(200, 150, 0));
+        let brighter = perceptual_hash(&pattern(400, 300, 20));
+        let flipped = perceptual_hash(&pattern(400, 300, 0).fliph());
+
+        assert!(hamming_distance(original, resized) <= 4);
+        assert!(hamming_distance(original, brighter) <= 4);
+        assert!(hamming_distance(original, flipped) > 10);
+    }
+
+    #[test]
+    fn test_hash_bands() {
+        let bands = hash_bands(0x0102_0304_0506_07FF_u64 as i64);
+        assert_eq!(bands, [0xFF, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01]);
+    }
+
+    #[test]
+    fn test_band_probes() {
+        let hash = 0x0102_0304_0506_07FF_u64 as i64;
+
+        let (bands, values) = band_probes(hash, 7);
+        assert_eq!(bands, (0..8).collect::<Vec<i16>>());
+        assert_eq!(values, hash_bands(hash).to_vec());
+
+        // 距離 12 なら 1 区間は 1bit 以内の違いに収まる
+        let (bands, values) = band_probes(hash, 12);
+        assert_eq!(bands.len(), 8 * 9);
+        let other = hash ^ 0x0101_0101_0101_0107_u64 as i64;
+        assert_eq!(hamming_distance(hash, other), 10);
+        let other_bands = hash_bands(other);
+        assert!(bands.iter().zip(&values).any(|(&band, &value)| other_bands[band as usize] == value));
+        assert!(!hash_bands(hash).iter().zip(&other_bands).any(|(a, b)| a == b));
+    }
+}
@@ -0,0 +1,97 @@
+use image::DynamicImage;
+
+use crate::models::edit::{EditOperation, FlipDirection};
+
+fn normalized_rotation(degrees: i32) -> i32 {
+    degrees.rem_euclid(360)
+}
+
+fn crop_rect(width: u32, height: u32, x: f64, y: f64, w: f64, h: f64) -> (u32, u32, u32, u32) {
+    let left = ((x * width as f64).round() as u32).min(width.saturating_sub(1));
+    let top = ((y * height as f64).round() as u32).min(height.saturating_sub(1));
+    let crop_w = ((w * width as f64).round() as u32).clamp(1, width - left);
+    let crop_h = ((h * height as f64).round() as u32).clamp(1, height - top);
+
+    (left, top, crop_w, crop_h)
+}
+
+// 編集を順番に適用する。元の画像は変更しない
+pub fn apply_edits(img: &DynamicImage, edits: &[EditOperation]) -> DynamicImage {
+    let mut current = img.clone();
+
+    for edit in edits {
+        current = match *edit {
+            EditOperation::Rotate { degrees } => match normalized_rotation(degrees) {
+                90 => current.rotate90(),
+                180 => current.rotate180(),
+                270 => current.rotate270(),
+                _ => current,
+            },
+            EditOperation::Flip { direction: FlipDirection::Horizontal } => current.fliph(),
+            EditOperation::Flip { direction: FlipDirection::Vertical } => current.flipv(),
+            EditOperation::Crop { x, y, width, height } => {
+                let (left, top, w, h) = crop_rect(current.width(), current.height(), x, y, width, height);
+                current.crop_imm(left, top, w, h)
+            }
+            EditOperation::Brightness { value } => current.brighten((value as f32 * 2.55).round() as i32),
+            EditOperation::Contrast { value } => current.adjust_contrast(value as f32),
+        };
+    }
+
+    current
+}
+
+// 画像をデコードせずに編集後のサイズを求める
+pub fn edited_dimensions(width: u32, height: u32, edits: &[EditOperation]) -> (u32, u32) {
+    edits.iter().fold((width, height), |(w, h), edit| match *edit {
+        EditOperation::Rotate { degrees } if normalized_rotation(degrees) % 180 == 90 => (h, w),
+        EditOperation::Crop { x, y, width, height } if w > 0 && h > 0 => {
+            let (_, _, crop_w, crop_h) = crop_rect(w, h, x, y, width, height);
+            (crop_w, crop_h)
+        }
+        _ => (w, h),
+    })
+}
+
+// DBに保存した JSON を読み込む。壊れたものは無視する
+pub fn parse_edits(rows: impl IntoIterator<Item = String>) -> Vec<EditOperation> {
+    rows.into_iter()
+        .filter_map(|operation| serde_json::from_str(&operation).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edited_dimensions_match_applied_image() {
+        let edits = vec![
+            EditOperation::Rotate { degrees: 90 },
+            EditOperation::Crop { x: 0.25, y: 0.0, width: 0.5, height: 0.5 },
+            EditOperation::Flip { direction: FlipDirection::Horizontal },
+            EditOperation::Rotate { degrees: -90 },
+        ];
+
+        let img = DynamicImage::new_rgb8(400, 300);
+        let edited = apply_edits(&img, &edits);
+
+        assert_eq!(edited_dimensions(400, 300, &edits), (edited.width(), edited.height()));
+        assert_eq!((edited.width(), edited.height()), (200, 150));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(EditOperation::Rotate { degrees: 45 }.validate().is_err());
+        assert!(EditOperation::Rotate { degrees: -90 }.validate().is_ok());
+        assert!(EditOperation::Crop { x: 0.5, y: 0.0, width: 0.6, height: 1.0 }.validate().is_err());
+        assert!(EditOperation::Brightness { value: 101 }.validate().is_err());
+    }
+
+    #[test]
+    fn test_operation_json() {
+        let json = r#"{"type":"flip","direction":"vertical"}"#;
+        let parsed = parse_edits(vec![json.to_string(), "broken".to_string()]);
+        assert_eq!(parsed, vec![EditOperation::Flip { direction: FlipDirection::Vertical }]);
+    }
+}
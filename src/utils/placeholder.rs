@@ -0,0 +1,118 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, RgbImage};
+
+use crate::utils::color::{extract_palette, PaletteColor};
+
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+const BLURHASH_SOURCE_SIZE: u32 = 64;
+const LQIP_SIZE: u32 = 16;
+const LQIP_QUALITY: u8 = 50;
+const COLOR_SOURCE_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholders {
+    pub blurhash: String,
+    // data URI 形式の極小JPEG
+    pub lqip: String,
+    pub dominant_color: String,
+    pub average_color: String,
+    pub palette: Vec<PaletteColor>,
+}
+
+pub fn compute_placeholders(img: &DynamicImage) -> Result<Placeholders, String> {
+    let small = img.thumbnail(BLURHASH_SOURCE_SIZE, BLURHASH_SOURCE_SIZE).to_rgba8();
+    let blurhash = blurhash::encode(
+        BLURHASH_COMPONENTS.0,
+        BLURHASH_COMPONENTS.1,
+        small.width(),
+        small.height(),
+        small.as_raw(),
+    )
+    .map_err(|e| format!("BlurHashの計算に失敗: {:?}", e))?;
+
+    let tiny = img.resize(LQIP_SIZE, LQIP_SIZE, FilterType::Triangle).to_rgb8();
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(tiny)
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, LQIP_QUALITY))
+        .map_err(|e| format!("LQIPの生成に失敗: {}", e))?;
+    let lqip = format!("data:image/jpeg;base64,{}", STANDARD.encode(jpeg));
+
+    let pixels = img.thumbnail(COLOR_SOURCE_SIZE, COLOR_SOURCE_SIZE).to_rgb8();
+
+    Ok(Placeholders {
+        blurhash,
+        lqip,
+        dominant_color: hex_color(dominant_color(&pixels)),
+        average_color: hex_color(average_color(&pixels)),
+        palette: extract_palette(&pixels),
+    })
+}
+
+pub fn hex_color([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn average_color(pixels: &RgbImage) -> [u8; 3] {
+    let count = (pixels.width() * pixels.height()).max(1) as u64;
+    let sum = pixels.pixels().fold([0u64; 3], |mut sum, p| {
+        for (s, &c) in sum.iter_mut().zip(p.0.iter()) {
+            *s += c as u64;
+        }
+        sum
+    });
+
+    sum.map(|s| (s / count) as u8)
+}
+
+// 各チャンネルを上位4ビットで量子化して最も多い色域を選び、その色域の平均色を返す
+fn dominant_color(pixels: &RgbImage) -> [u8; 3] {
+    let bucket_of = |p: &image::Rgb<u8>| ((p[0] >> 4) as usize) << 8 | ((p[1] >> 4) as usize) << 4 | (p[2] >> 4) as usize;
+
+    let mut counts = vec![0u32; 4096];
+    for p in pixels.pixels() {
+        counts[bucket_of(p)] += 1;
+    }
+
+    let Some((bucket, _)) = counts.iter().enumerate().max_by_key(|&(_, &count)| count) else {
+        return [0, 0, 0];
+    };
+
+    let mut sum = [0u64; 3];
+    let mut count = 0u64;
+    for p in pixels.pixels().filter(|p| bucket_of(p) == bucket) {
+        for (s, &c) in sum.iter_mut().zip(p.0.iter()) {
+            *s += c as u64;
+        }
+        count += 1;
+    }
+
+    sum.map(|s| (s / count.max(1)) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors() {
+        // 3/4 が青、1/4 が赤
+        let img = RgbImage::from_fn(40, 40, |x, _| if x < 30 { image::Rgb([0, 0, 255]) } else { image::Rgb([255, 0, 0]) });
+
+        assert_eq!(hex_color(dominant_color(&img)), "#0000ff");
+        assert_eq!(hex_color(average_color(&img)), "#3f00bf");
+    }
+
+    #[test]
+    fn test_compute_placeholders() {
+        let img = RgbImage::from_pixel(300, 200, image::Rgb([10, 200, 30]));
+
+        let placeholders = compute_placeholders(&DynamicImage::ImageRgb8(img)).unwrap();
+
+        assert_eq!(placeholders.dominant_color, "#0ac81e");
+        assert!(placeholders.lqip.starts_with("data:image/jpeg;base64,"));
+        assert!(!placeholders.blurhash.is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 use std::env;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::{Credentials, Region};
+use aws_sdk_s3::primitives::ByteStream;
 
 pub fn create_s3_client() -> (Client, String, String) {
     let access_key = env::var("AWS_ACCESS_KEY_ID").unwrap();
@@ -20,6 +21,194 @@ pub fn create_s3_client() -> (Client, String, String) {
     (client, bucket_name, region)
 }
 
+pub fn public_url(bucket: &str, region: &str, key: &str) -> String {
+    format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, key)
+}
+
+// image_path（公開URL）からS3のキーを取り出す
+pub fn object_key_from_url(image_url: &str) -> Result<&str, String> {
+    image_url
+        .rsplit('/')
+        .next()
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| format!("無効なURL形式: {}", image_url))
+}
+
+pub async fn download_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+) -> Result<Vec<u8>, String> {
+    let output = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| format!("S3取得失敗: {} ({:?})", key, e))?;
+
+    let data = output
+        .body
+        .collect()
+        .await
+        .map_err(|e| format!("S3読み込み失敗: {} ({:?})", key, e))?;
+
+    Ok(data.into_bytes().to_vec())
+}
+
+pub async fn upload_object(
+    client: &Client,
+    bucket: &str,
+    key: &str,
+    content_type: &str,
+    body: Vec<u8>,
+) -> Result<(), String> {
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type)
+        .body(ByteStream::from(body))
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("S3アップロード失敗: {} ({:?})", key, e))
+}
+
+// CopyObject の CopySource は "バケット/キー" をURLエンコードして指定する
+fn copy_source(bucket: &str, key: &str) -> String {
+    let mut encoded = format!("{}/", bucket);
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// ダウンロードせずにS3上で複製する
+pub async fn copy_object(
+    client: &Client,
+    bucket: &str,
+    source_key: &str,
+    target_key: &str,
+) -> Result<(), String> {
+    client
+        .copy_object()
+        .bucket(bucket)
+        .copy_source(copy_source(bucket, source_key))
+        .key(target_key)
+        .send()
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("S3コピー失敗: {} → {} ({:?})", source_key, target_key, e))
+}
+
+// "<uuid>-photo.jpg" → "<新しいuuid>-photo.jpg"
+pub fn copied_object_key(key: &str) -> String {
+    let filename = match (key.get(..36), key.get(36..37), key.get(37..)) {
+        (Some(prefix), Some("-"), Some(rest)) if uuid::Uuid::parse_str(prefix).is_ok() => rest,
+        _ => key,
+    };
+
+    format!("{}-{}", uuid::Uuid::new_v4(), filename)
+}
+
+pub async fn delete_objects_with_prefix(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<(), String> {
+    let mut continuation_token: Option<String> = None;
+
+    loop {
+        let listed = client
+            .list_objects_v2()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_continuation_token(continuation_token.take())
+            .send()
+            .await
+            .map_err(|e| format!("S3一覧取得失敗: {} ({:?})", prefix, e))?;
+
+        for object in listed.contents().unwrap_or_default() {
+            let Some(key) = object.key() else {
+                continue;
+            };
+
+            client
+                .delete_object()
+                .bucket(bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(|e| format!("S3削除失敗: {} ({:?})", key, e))?;
+        }
+
+        // 一度に返るのは最大1000件なので、残りがあれば続きから取得する
+        if !listed.is_truncated() {
+            return Ok(());
+        }
+
+        continuation_token = listed.next_continuation_token().map(str::to_string);
+        if continuation_token.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+// 開始から一定時間が経っても完了していないマルチパートアップロードを中止し、件数を返す
+pub async fn abort_stale_multipart_uploads(
+    client: &Client,
+    bucket: &str,
+    initiated_before_unix: i64,
+) -> Result<usize, String> {
+    let mut aborted = 0;
+    let mut key_marker: Option<String> = None;
+    let mut upload_id_marker: Option<String> = None;
+
+    loop {
+        let listed = client
+            .list_multipart_uploads()
+            .bucket(bucket)
+            .set_key_marker(key_marker.take())
+            .set_upload_id_marker(upload_id_marker.take())
+            .send()
+            .await
+            .map_err(|e| format!("マルチパートアップロードの一覧取得失敗: {:?}", e))?;
+
+        for upload in listed.uploads().unwrap_or_default() {
+            let stale = upload.initiated().is_some_and(|t| t.secs() < initiated_before_unix);
+            let (Some(key), Some(upload_id)) = (upload.key(), upload.upload_id()) else {
+                continue;
+            };
+
+            if !stale {
+                continue;
+            }
+
+            client
+                .abort_multipart_upload()
+                .bucket(bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+                .await
+                .map_err(|e| format!("マルチパートアップロードの中止失敗: {} ({:?})", key, e))?;
+
+            aborted += 1;
+        }
+
+        if !listed.is_truncated() {
+            return Ok(aborted);
+        }
+
+        key_marker = listed.next_key_marker().map(str::to_string);
+        upload_id_marker = listed.next_upload_id_marker().map(str::to_string);
+    }
+}
+
 pub async fn verify_s3_credentials() -> String {
     let (client, _, _) = create_s3_client();
 
@@ -37,3 +226,23 @@ pub async fn verify_s3_credentials() -> String {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_source() {
+        assert_eq!(copy_source("bucket", "a b/写真.jpg"), "bucket/a%20b/%E5%86%99%E7%9C%9F.jpg");
+    }
+
+    #[test]
+    fn test_copied_object_key() {
+        let copied = copied_object_key("67e55044-10b1-426f-9247-bb680e5fe0c8-photo.jpg");
+        assert!(copied.ends_with("-photo.jpg"));
+        assert_eq!(copied.len(), "67e55044-10b1-426f-9247-bb680e5fe0c8-photo.jpg".len());
+        assert!(!copied.starts_with("67e55044"));
+
+        assert!(copied_object_key("photo.jpg").ends_with("-photo.jpg"));
+    }
+}
@@ -0,0 +1,106 @@
+// 検索結果の抜粋。一致した部分を <mark> で囲み、それ以外は HTML エスケープする
+// 一致した箇所が無い場合は None を返す
+
+// 抜粋の最大文字数（前後の「…」を除く）
+pub const SNIPPET_LENGTH: usize = 80;
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+// 検索語（空白区切り）が出現する範囲を文字単位で返す。重なる範囲はまとめる
+fn match_ranges(text: &[char], query: &str) -> Vec<(usize, usize)> {
+    let lowered: Vec<char> = text.iter().map(|&c| lowercase(c)).collect();
+    let mut ranges = Vec::new();
+
+    for term in query.split_whitespace() {
+        let term: Vec<char> = term.chars().map(lowercase).collect();
+        if term.len() > lowered.len() {
+            continue;
+        }
+        for start in 0..=lowered.len() - term.len() {
+            if lowered[start..start + term.len()] == term[..] {
+                ranges.push((start, start + term.len()));
+            }
+        }
+    }
+
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn escape_html(text: &str, out: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+pub fn highlight(text: &str, query: &str, max_chars: usize) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let ranges = match_ranges(&chars, query);
+    let first = ranges.first()?;
+
+    // 最初に一致した箇所が抜粋の前のほうに来るように切り出す
+    let start = first.0.saturating_sub(max_chars / 4).min(chars.len().saturating_sub(max_chars));
+    let end = (start + max_chars).min(chars.len());
+
+    let mut snippet = String::new();
+    if start > 0 {
+        snippet.push('…');
+    }
+
+    let mut position = start;
+    for &(range_start, range_end) in ranges.iter().filter(|(s, e)| *e > start && *s < end) {
+        let (range_start, range_end) = (range_start.max(start), range_end.min(end));
+        escape_html(&chars[position..range_start].iter().collect::<String>(), &mut snippet);
+        snippet.push_str("<mark>");
+        escape_html(&chars[range_start..range_end].iter().collect::<String>(), &mut snippet);
+        snippet.push_str("</mark>");
+        position = range_end;
+    }
+    escape_html(&chars[position..end].iter().collect::<String>(), &mut snippet);
+
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(highlight("東京タワーの夜景", "夜景", 80).as_deref(), Some("東京タワーの<mark>夜景</mark>"));
+        assert_eq!(highlight("Cat & Dog", "cat dog", 80).as_deref(), Some("<mark>Cat</mark> &amp; <mark>Dog</mark>"));
+        assert_eq!(highlight("<b>cat</b>", "cat", 80).as_deref(), Some("&lt;b&gt;<mark>cat</mark>&lt;/b&gt;"));
+        assert_eq!(highlight("abcabc", "bc abc", 80).as_deref(), Some("<mark>abcabc</mark>"));
+        assert_eq!(highlight("夜景", "猫", 80), None);
+    }
+
+    #[test]
+    fn test_highlight_truncates() {
+        let text = format!("{}猫{}", "あ".repeat(20), "い".repeat(20));
+        assert_eq!(
+            highlight(&text, "猫", 8).as_deref(),
+            Some("…ああ<mark>猫</mark>いいいいい…")
+        );
+        assert_eq!(highlight("猫いいい", "猫", 2).as_deref(), Some("<mark>猫</mark>い…"));
+    }
+}
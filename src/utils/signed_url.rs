@@ -0,0 +1,48 @@
+use std::env;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn signing_key() -> Result<Vec<u8>, String> {
+    env::var("IMAGE_URL_SECRET")
+        .map(String::into_bytes)
+        .map_err(|_| "IMAGE_URL_SECRET が設定されていません".to_string())
+}
+
+fn mac_for(message: &str) -> Result<HmacSha256, String> {
+    let mut mac = HmacSha256::new_from_slice(&signing_key()?)
+        .map_err(|e| format!("署名鍵が不正です: {}", e))?;
+    mac.update(message.as_bytes());
+    Ok(mac)
+}
+
+pub fn sign(message: &str) -> Result<String, String> {
+    Ok(URL_SAFE_NO_PAD.encode(mac_for(message)?.finalize().into_bytes()))
+}
+
+// 比較は定数時間で行う
+pub fn verify(message: &str, signature: &str) -> Result<bool, String> {
+    let Ok(signature) = URL_SAFE_NO_PAD.decode(signature) else {
+        return Ok(false);
+    };
+
+    Ok(mac_for(message)?.verify_slice(&signature).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        env::set_var("IMAGE_URL_SECRET", "test-secret");
+
+        let signature = sign("1:2:3").unwrap();
+        assert!(verify("1:2:3", &signature).unwrap());
+        assert!(!verify("1:2:4", &signature).unwrap());
+        assert!(!verify("1:2:3", "not-base64!").unwrap());
+    }
+}
@@ -0,0 +1,369 @@
+use std::fmt;
+use serde::Serialize;
+
+// 展開後の条件の上限。(a OR b) AND (c OR d) ... のように組み合わせが増えすぎる式は受け付けない
+const MAX_CLAUSES: usize = 64;
+// 括弧と否定の入れ子の上限。深すぎる式で再帰がスタックを使い切らないようにする
+const MAX_DEPTH: usize = 32;
+
+// タグの検索式
+// 例: "(cat OR dog) AND -blurry"、"cat dog"（AND の省略）、"cat,dog"（OR）
+// 演算子は AND / OR / NOT（大文字小文字を問わない）と & / | , / - 。空白や記号を含むタグは "..." で囲む
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagQueryError {
+    // 0 から数えた文字の位置
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TagQueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}文字目: {}", self.position + 1, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '"' | '&' | '|' | ',')
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, TagQueryError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+
+        let token = match c {
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' => Token::And,
+            '|' | ',' => Token::Or,
+            // 直後に続く語や括弧を否定する（"blue-sky" のような語の途中の - はタグの一部）
+            '-' if chars.get(i + 1).is_some_and(|&next| !next.is_whitespace()) => Token::Not,
+            '"' => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .map(|offset| i + 1 + offset)
+                    .ok_or_else(|| TagQueryError {
+                        position: start,
+                        message: "「\"」が閉じられていません".to_string(),
+                    })?;
+                let tag: String = chars[i + 1..end].iter().collect();
+                if tag.trim().is_empty() {
+                    return Err(TagQueryError { position: start, message: "タグが空です".to_string() });
+                }
+                i = end + 1;
+                tokens.push((start, Token::Tag(tag.trim().to_string())));
+                continue;
+            }
+            _ => {
+                while i < chars.len() && !is_delimiter(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.to_ascii_uppercase().as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Tag(word),
+                };
+                tokens.push((start, token));
+                continue;
+            }
+        };
+
+        tokens.push((start, token));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    index: usize,
+    end: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.index).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error(&self, message: &str) -> TagQueryError {
+        TagQueryError { position: self.position(), message: message.to_string() }
+    }
+
+    // or := and (OR and)*
+    fn parse_or(&mut self) -> Result<TagExpr, TagQueryError> {
+        let mut terms = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.index += 1;
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { TagExpr::Or(terms) })
+    }
+
+    // and := unary ((AND)? unary)*
+    fn parse_and(&mut self) -> Result<TagExpr, TagQueryError> {
+        let mut terms = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.index += 1;
+                    terms.push(self.parse_unary()?);
+                }
+                Some(Token::Tag(_) | Token::Not | Token::LParen) => terms.push(self.parse_unary()?),
+                _ => break,
+            }
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { TagExpr::And(terms) })
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, TagQueryError>) -> Result<T, TagQueryError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(&format!("括弧や否定の入れ子が深すぎます（{}段まで）", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // unary := NOT unary | "(" or ")" | TAG
+    fn parse_unary(&mut self) -> Result<TagExpr, TagQueryError> {
+        let position = self.position();
+        match self.peek().cloned() {
+            Some(Token::Not) => {
+                let inner = self.nested(|parser| {
+                    parser.index += 1;
+                    parser.parse_unary()
+                })?;
+                Ok(TagExpr::Not(Box::new(inner)))
+            }
+            Some(Token::LParen) => {
+                let expr = self.nested(|parser| {
+                    parser.index += 1;
+                    parser.parse_or()
+                })?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(TagQueryError { position, message: "「(」が閉じられていません".to_string() });
+                }
+                self.index += 1;
+                Ok(expr)
+            }
+            Some(Token::Tag(tag)) => {
+                self.index += 1;
+                Ok(TagExpr::Tag(tag))
+            }
+            Some(Token::RParen) => Err(self.error("「)」に対応する「(」がありません")),
+            Some(Token::And | Token::Or) => Err(self.error("演算子の前にタグがありません")),
+            None => Err(self.error("式が途中で終わっています")),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<TagExpr, TagQueryError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Err(TagQueryError { position: 0, message: "検索条件が空です".to_string() });
+    }
+
+    let mut parser = Parser { tokens, index: 0, end: input.chars().count(), depth: 0 };
+    let expr = parser.parse_or()?;
+
+    if parser.peek().is_some() {
+        return Err(parser.error("「)」に対応する「(」がありません"));
+    }
+
+    Ok(expr)
+}
+
+// 「all のタグをすべて持ち、none のタグを1つも持たない」条件。いずれかの条件に一致すれば検索結果に含める
+// タグ名は大文字小文字を区別しないよう小文字にそろえる
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TagClause {
+    pub all: Vec<String>,
+    pub none: Vec<String>,
+}
+
+impl TagClause {
+    fn merge(&self, other: &TagClause) -> TagClause {
+        let mut merged = self.clone();
+        for tag in &other.all {
+            if !merged.all.contains(tag) {
+                merged.all.push(tag.clone());
+            }
+        }
+        for tag in &other.none {
+            if !merged.none.contains(tag) {
+                merged.none.push(tag.clone());
+            }
+        }
+        merged
+    }
+
+    // 同じタグを持ちかつ持たない条件には、どの写真も一致しない
+    fn is_satisfiable(&self) -> bool {
+        !self.all.iter().any(|tag| self.none.contains(tag))
+    }
+}
+
+fn too_complex() -> String {
+    format!("検索式が複雑すぎます（展開後の条件が{}を超えています）", MAX_CLAUSES)
+}
+
+// 否定を内側に移しながら、AND の OR（選言標準形）に展開する
+fn expand(expr: &TagExpr, negated: bool) -> Result<Vec<TagClause>, String> {
+    match (expr, negated) {
+        (TagExpr::Tag(tag), _) => {
+            let tag = tag.to_lowercase();
+            Ok(vec![if negated {
+                TagClause { all: Vec::new(), none: vec![tag] }
+            } else {
+                TagClause { all: vec![tag], none: Vec::new() }
+            }])
+        }
+        (TagExpr::Not(inner), _) => expand(inner, !negated),
+        (TagExpr::And(terms), false) | (TagExpr::Or(terms), true) => {
+            let mut clauses = vec![TagClause::default()];
+            for term in terms {
+                let term_clauses = expand(term, negated)?;
+                if clauses.len() * term_clauses.len() > MAX_CLAUSES {
+                    return Err(too_complex());
+                }
+                clauses = clauses
+                    .iter()
+                    .flat_map(|clause| term_clauses.iter().map(move |other| clause.merge(other)))
+                    .filter(TagClause::is_satisfiable)
+                    .collect();
+            }
+            Ok(clauses)
+        }
+        (TagExpr::Or(terms), false) | (TagExpr::And(terms), true) => {
+            let mut clauses = Vec::new();
+            for term in terms {
+                clauses.extend(expand(term, negated)?);
+                if clauses.len() > MAX_CLAUSES {
+                    return Err(too_complex());
+                }
+            }
+            Ok(clauses)
+        }
+    }
+}
+
+pub fn to_clauses(expr: &TagExpr) -> Result<Vec<TagClause>, String> {
+    expand(expr, false)
+}
+
+// 検索式を SQL に渡す JSON（TagClause の配列）にする。エラーはそのままレスポンスに使える文言で返す
+pub fn compile(input: &str) -> Result<String, String> {
+    let expr = parse(input).map_err(|e| format!("タグの検索式が正しくありません（{}）", e))?;
+    let clauses = to_clauses(&expr)?;
+    // 文字列の配列だけなのでシリアライズには失敗しない
+    Ok(serde_json::to_string(&clauses).expect("検索条件のシリアライズに失敗"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> TagExpr {
+        TagExpr::Tag(name.to_string())
+    }
+
+    fn clause(all: &[&str], none: &[&str]) -> TagClause {
+        TagClause {
+            all: all.iter().map(|s| s.to_string()).collect(),
+            none: none.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("(cat OR dog) AND -blurry").unwrap(),
+            TagExpr::And(vec![
+                TagExpr::Or(vec![tag("cat"), tag("dog")]),
+                TagExpr::Not(Box::new(tag("blurry"))),
+            ])
+        );
+        // AND は OR より優先され、省略できる
+        assert_eq!(
+            parse("a b or c").unwrap(),
+            TagExpr::Or(vec![TagExpr::And(vec![tag("a"), tag("b")]), tag("c")])
+        );
+        assert_eq!(parse("cat,dog").unwrap(), TagExpr::Or(vec![tag("cat"), tag("dog")]));
+        assert_eq!(parse("blue-sky").unwrap(), tag("blue-sky"));
+        assert_eq!(parse("\"new york\" 旅行").unwrap(), TagExpr::And(vec![tag("new york"), tag("旅行")]));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("").unwrap_err().position, 0);
+        assert_eq!(parse("(cat OR dog").unwrap_err().position, 0);
+        assert_eq!(parse("cat AND").unwrap_err().position, 7);
+        assert_eq!(parse("cat ) dog").unwrap_err().position, 4);
+        assert_eq!(parse("OR cat").unwrap_err().position, 0);
+        assert_eq!(parse("写真 \"abc").unwrap_err().position, 3);
+        assert_eq!(parse("cat AND").unwrap_err().to_string(), "8文字目: 式が途中で終わっています");
+    }
+
+    #[test]
+    fn test_parse_depth_limit() {
+        let nested = |depth: usize| format!("{}cat{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(parse(&nested(MAX_DEPTH)).unwrap(), tag("cat"));
+        assert_eq!(parse(&nested(MAX_DEPTH + 1)).unwrap_err().position, MAX_DEPTH);
+
+        // スタックを使い切るほど深い式もエラーとして返す
+        assert!(parse(&nested(100_000)).is_err());
+        assert!(parse(&format!("{}cat", "-".repeat(100_000))).is_err());
+        assert!(parse(&format!("{}cat", "NOT ".repeat(100_000))).is_err());
+    }
+
+    #[test]
+    fn test_to_clauses() {
+        let clauses = to_clauses(&parse("(Cat OR dog) AND -blurry").unwrap()).unwrap();
+        assert_eq!(clauses, vec![clause(&["cat"], &["blurry"]), clause(&["dog"], &["blurry"])]);
+
+        // ド・モルガンの法則で否定を展開する
+        let clauses = to_clauses(&parse("NOT (a AND b)").unwrap()).unwrap();
+        assert_eq!(clauses, vec![clause(&[], &["a"]), clause(&[], &["b"])]);
+
+        assert!(to_clauses(&parse("a -a").unwrap()).unwrap().is_empty());
+
+        let many = (0..7).map(|i| format!("(a{i} OR b{i})")).collect::<Vec<_>>().join(" ");
+        assert!(to_clauses(&parse(&many).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(30);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60 * 60);
+
+// キューに積む処理の定義。payload にはこの enum をそのまま JSON で保存する
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Job {
+    // アップロード・編集後の処理一式（メタデータ抽出・レンディション生成など）
+    ProcessPhoto { photo_id: i32 },
+    // 既存の写真に対する個別の再計算（バックフィル用）
+    ExtractMetadata { photo_id: i32 },
+    ComputePlaceholders { photo_id: i32 },
+    ComputePerceptualHash { photo_id: i32 },
+    // 削除した写真に対応するS3オブジェクトの後片付け
+    DeleteObjects {
+        image_paths: Vec<String>,
+        #[serde(default)]
+        prefixes: Vec<String>,
+    },
+}
+
+impl Job {
+    pub fn to_payload(&self) -> String {
+        // 数値と文字列だけで構成されるため失敗しない
+        serde_json::to_string(self).expect("ジョブをJSONに変換できません")
+    }
+
+    pub fn job_type(&self) -> JobType {
+        match self {
+            Job::ProcessPhoto { .. } => JobType::ProcessPhoto,
+            Job::ExtractMetadata { .. } => JobType::ExtractMetadata,
+            Job::ComputePlaceholders { .. } => JobType::ComputePlaceholders,
+            Job::ComputePerceptualHash { .. } => JobType::ComputePerceptualHash,
+            Job::DeleteObjects { .. } => JobType::DeleteObjects,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobType {
+    ProcessPhoto,
+    ExtractMetadata,
+    ComputePlaceholders,
+    ComputePerceptualHash,
+    DeleteObjects,
+}
+
+impl JobType {
+    pub const ALL: [JobType; 5] = [
+        JobType::ProcessPhoto,
+        JobType::ExtractMetadata,
+        JobType::ComputePlaceholders,
+        JobType::ComputePerceptualHash,
+        JobType::DeleteObjects,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobType::ProcessPhoto => "process_photo",
+            JobType::ExtractMetadata => "extract_metadata",
+            JobType::ComputePlaceholders => "compute_placeholders",
+            JobType::ComputePerceptualHash => "compute_perceptual_hash",
+            JobType::DeleteObjects => "delete_objects",
+        }
+    }
+
+    // 同時に実行する数。JOB_CONCURRENCY_PROCESS_PHOTO=4 のように種類ごとに変更できる
+    pub fn concurrency(&self) -> usize {
+        let default = match self {
+            JobType::ProcessPhoto => 2,
+            JobType::DeleteObjects => 4,
+            _ => 1,
+        };
+
+        env::var(format!("JOB_CONCURRENCY_{}", self.as_str().to_ascii_uppercase()))
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(default)
+    }
+
+    pub fn max_attempts(&self) -> i32 {
+        match self {
+            JobType::DeleteObjects => 10,
+            _ => 5,
+        }
+    }
+}
+
+// 失敗回数に応じて待ち時間を倍にしていく（上限あり）
+pub fn retry_delay(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+
+    RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(RETRY_MAX_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_payload_roundtrip() {
+        let job = Job::ProcessPhoto { photo_id: 42 };
+        let payload = serde_json::to_value(&job).unwrap();
+
+        assert_eq!(payload, serde_json::json!({ "type": "process_photo", "photo_id": 42 }));
+        assert_eq!(serde_json::from_value::<Job>(payload).unwrap(), job);
+
+        for job_type in JobType::ALL {
+            assert_eq!(JobType::parse(job_type.as_str()), Some(job_type));
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), Duration::from_secs(30));
+        assert_eq!(retry_delay(3), Duration::from_secs(120));
+        assert_eq!(retry_delay(100), RETRY_MAX_DELAY);
+    }
+}
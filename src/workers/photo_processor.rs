@@ -0,0 +1,599 @@
+use std::sync::Arc;
+use aws_sdk_s3::Client;
+use sqlx::PgPool;
+use time::UtcOffset;
+
+use crate::utils::image_processing::{
+    decode_image,
+    render_rendition,
+    rendition_key,
+    EncodedImage,
+    RenditionConfig,
+    RenditionFormat,
+};
+use crate::models::privacy::{MetadataStripMode, MetadataStripTarget};
+use crate::utils::metadata::{apply_video_tags, extract_metadata, utc_offset, ExtractedMetadata};
+use crate::utils::media::{
+    detect_animation,
+    extract_poster_frame,
+    is_video,
+    preview_key,
+    probe_video,
+    render_preview,
+    still_image,
+    strip_video_metadata,
+    MediaInfo,
+    MediaType,
+    TempMediaFile,
+};
+use crate::utils::metadata_strip::{strip_metadata, StrippedImage};
+use crate::utils::photo_edit::{apply_edits, parse_edits};
+use crate::utils::perceptual_hash::{hash_bands, perceptual_hash};
+use crate::utils::placeholder::{compute_placeholders, Placeholders};
+use crate::utils::s3::{download_object, object_key_from_url, public_url, upload_object};
+
+struct RenderedPhoto {
+    animation: Option<MediaInfo>,
+    metadata: Option<ExtractedMetadata>,
+    stripped: Option<StrippedImage>,
+    placeholders: Placeholders,
+    perceptual_hash: i64,
+    original_width: u32,
+    original_height: u32,
+    width: u32,
+    height: u32,
+    renditions: Vec<(u32, RenditionFormat, EncodedImage)>,
+}
+
+pub(crate) async fn process_photo(
+    pool: &PgPool,
+    client: &Client,
+    bucket_name: &str,
+    region: &str,
+    config: &Arc<RenditionConfig>,
+    photo_id: i32,
+) -> Result<(), String> {
+    let photo = sqlx::query!(
+        "
+        SELECT
+            image_path,
+            metadata_strip_mode,
+            metadata_strip_target,
+            metadata_stripped_at
+        FROM
+            photos
+        WHERE id = $1
+        ",
+        photo_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("写真取得失敗: {:?}", e))?;
+
+    // 処理待ちの間に削除された
+    let Some(photo) = photo else {
+        return Ok(());
+    };
+
+    let original_key = object_key_from_url(&photo.image_path)?.to_string();
+    let data = Arc::new(download_object(client, bucket_name, &original_key).await?);
+
+    // 動画は代表フレームを静止画として扱い、レンディション等の処理を共通化する
+    let video = if is_video(&data) {
+        let source = TempMediaFile::create(&original_key, &data).await?;
+        let info = probe_video(source.path()).await?;
+        let poster = extract_poster_frame(source.path(), info.duration_seconds).await?;
+        Some((source, info, poster))
+    } else {
+        None
+    };
+
+    // 元画像から削除済みの場合、抽出済みのメタデータを上書きしないよう再抽出しない
+    // 動画は再エンコードせずにコンテナを作り直して削除する（メタデータは削除する前に抽出する）
+    let already_stripped = photo.metadata_stripped_at.is_some();
+    let strip_mode = match MetadataStripTarget::parse(&photo.metadata_strip_target) {
+        Some(MetadataStripTarget::Original) if !already_stripped => {
+            MetadataStripMode::parse(&photo.metadata_strip_mode).unwrap_or(MetadataStripMode::None)
+        }
+        // レンディションは常にメタデータを含まない形で書き出すため、元画像の加工は不要
+        _ => MetadataStripMode::None,
+    };
+
+    let operations = sqlx::query_scalar!(
+        "SELECT operation FROM photo_edits WHERE photo_id = $1 ORDER BY position",
+        photo_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("編集内容の取得失敗: {:?}", e))?;
+
+    let edits = parse_edits(operations);
+
+    let config = Arc::clone(config);
+    let source = Arc::clone(&data);
+    let poster = video.as_ref().map(|(_, _, poster)| poster.clone());
+    let image_strip_mode = if video.is_some() { MetadataStripMode::None } else { strip_mode };
+    let video_tags = video.as_ref().map(|(_, info, _)| (info.creation_time.clone(), info.location.clone()));
+    let rendered = tokio::task::spawn_blocking(move || -> Result<RenderedPhoto, String> {
+        let animation = match poster {
+            Some(_) => None,
+            None => detect_animation(&source)?,
+        };
+        // アニメーションGIFは再エンコードするとフレームが失われるため加工しない（アニメーションWebPはチャンク単位で削除する）
+        let strip_mode = match &animation {
+            Some(info) if info.codec == "gif" => MetadataStripMode::None,
+            _ => image_strip_mode,
+        };
+
+        // 削除する前に抽出してDBにだけ残す
+        let metadata = (!already_stripped).then(|| {
+            let mut metadata = extract_metadata(&source);
+            if let Some((creation_time, location)) = &video_tags {
+                apply_video_tags(&mut metadata, creation_time.as_deref(), location.as_deref());
+            }
+            metadata
+        });
+        let stripped = strip_metadata(&source, strip_mode)?;
+        let original = decode_image(poster.as_deref().unwrap_or(&source))?;
+        // 重複検出は編集前の画像で行う
+        let perceptual_hash = perceptual_hash(&original);
+        // レンディションには編集を反映する（元画像は変更しない）
+        let img = apply_edits(&original, &edits);
+        let placeholders = compute_placeholders(&img)?;
+
+        let mut renditions = Vec::new();
+        for &size in &config.sizes {
+            for &format in &config.formats {
+                renditions.push((size, format, render_rendition(&img, size, format)?));
+            }
+        }
+
+        Ok(RenderedPhoto {
+            animation,
+            metadata,
+            stripped,
+            placeholders,
+            perceptual_hash,
+            original_width: original.width(),
+            original_height: original.height(),
+            width: img.width(),
+            height: img.height(),
+            renditions,
+        })
+    })
+    .await
+    .map_err(|e| format!("画像処理タスクの実行に失敗: {:?}", e))??;
+
+    if let Some(metadata) = &rendered.metadata {
+        save_metadata(pool, photo_id, metadata).await?;
+    }
+
+    let stripped = match &video {
+        Some((source, _, _)) => strip_video_metadata(source.path(), &data, strip_mode).await?,
+        None => rendered.stripped,
+    };
+
+    if let Some(stripped) = stripped {
+        let size_in_bytes = stripped.bytes.len() as i64;
+
+        upload_object(client, bucket_name, &original_key, stripped.content_type, stripped.bytes).await?;
+
+        sqlx::query!(
+            "UPDATE photos SET size_in_bytes = $1, metadata_stripped_at = now() WHERE id = $2",
+            size_in_bytes,
+            photo_id,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("メタデータ削除状態の更新失敗: {:?}", e))?;
+    }
+
+    sqlx::query!(
+        "
+        UPDATE photos
+        SET width = $1,
+            height = $2,
+            original_width = $3,
+            original_height = $4
+        WHERE id = $5
+        ",
+        rendered.width as i32,
+        rendered.height as i32,
+        rendered.original_width as i32,
+        rendered.original_height as i32,
+        photo_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| format!("画像サイズの更新失敗: {:?}", e))?;
+
+    save_placeholders(pool, photo_id, &rendered.placeholders).await?;
+    save_perceptual_hash(pool, photo_id, rendered.perceptual_hash).await?;
+
+    let (video_source, media) = match (video, rendered.animation) {
+        (Some((source, info, _)), _) => (Some(source), Some(info)),
+        (None, animation) => (None, animation),
+    };
+
+    // プレビューは無くても表示できるため、生成に失敗しても処理を続ける
+    let preview_path = match &media {
+        Some(_) => {
+            let source = match video_source {
+                Some(source) => Ok(source),
+                None => TempMediaFile::create(&original_key, &data).await,
+            };
+
+            let preview = match source {
+                Ok(source) => render_preview(source.path()).await,
+                Err(e) => Err(e),
+            };
+
+            match preview {
+                Ok(bytes) => {
+                    let key = preview_key(&original_key);
+                    upload_object(client, bucket_name, &key, "video/mp4", bytes).await?;
+                    Some(public_url(bucket_name, region, &key))
+                }
+                Err(e) => {
+                    eprintln!("写真 {} のプレビュー生成に失敗: {}", photo_id, e);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    sqlx::query!(
+        "
+        UPDATE photos
+        SET media_type = $1,
+            duration_seconds = $2,
+            codec = $3,
+            frame_rate = $4,
+            preview_path = $5
+        WHERE id = $6
+        ",
+        media.as_ref().map_or(MediaType::Image, |m| m.media_type).as_str(),
+        media.as_ref().map(|m| m.duration_seconds),
+        media.as_ref().map(|m| m.codec.as_str()),
+        media.as_ref().and_then(|m| m.frame_rate),
+        preview_path,
+        photo_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| format!("メディア情報の更新失敗: {:?}", e))?;
+
+    for (size, format, image) in rendered.renditions {
+        let key = rendition_key(&original_key, size, format);
+        let size_in_bytes = image.bytes.len() as i64;
+
+        upload_object(client, bucket_name, &key, format.content_type(), image.bytes).await?;
+
+        sqlx::query!(
+            "
+            INSERT INTO photo_renditions
+                (photo_id, size, format, image_path, width, height, size_in_bytes)
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (photo_id, size, format) DO UPDATE
+            SET image_path = EXCLUDED.image_path,
+                width = EXCLUDED.width,
+                height = EXCLUDED.height,
+                size_in_bytes = EXCLUDED.size_in_bytes,
+                created_at = now()
+            ",
+            photo_id,
+            size as i32,
+            format.as_str(),
+            public_url(bucket_name, region, &key),
+            image.width as i32,
+            image.height as i32,
+            size_in_bytes,
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| format!("レンディションの登録失敗: {:?}", e))?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn extract_photo_metadata(
+    pool: &PgPool,
+    client: &Client,
+    bucket_name: &str,
+    photo_id: i32,
+) -> Result<(), String> {
+    let photo = sqlx::query!(
+        "SELECT image_path, metadata_stripped_at FROM photos WHERE id = $1",
+        photo_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("写真取得失敗: {:?}", e))?;
+
+    // 元画像からメタデータを削除済みの場合は抽出できるものが残っていない
+    let Some(photo) = photo.filter(|p| p.metadata_stripped_at.is_none()) else {
+        return Ok(());
+    };
+
+    let key = object_key_from_url(&photo.image_path)?;
+    let data = download_object(client, bucket_name, key).await?;
+
+    let video_info = if is_video(&data) {
+        let source = TempMediaFile::create(key, &data).await?;
+        Some(probe_video(source.path()).await?)
+    } else {
+        None
+    };
+
+    let mut metadata = tokio::task::spawn_blocking(move || extract_metadata(&data))
+        .await
+        .map_err(|e| format!("メタデータ抽出タスクの実行に失敗: {:?}", e))?;
+
+    if let Some(info) = video_info {
+        apply_video_tags(&mut metadata, info.creation_time.as_deref(), info.location.as_deref());
+    }
+
+    save_metadata(pool, photo_id, &metadata).await
+}
+
+// 何も取れなかった場合も行を作り、抽出済みであることを記録する
+async fn save_metadata(
+    pool: &PgPool,
+    photo_id: i32,
+    metadata: &ExtractedMetadata,
+) -> Result<(), String> {
+    let offset = metadata
+        .timezone_offset_minutes
+        .and_then(utc_offset)
+        .unwrap_or(UtcOffset::UTC);
+    let taken_at = metadata.taken_at.map(|t| t.assume_offset(offset));
+
+    sqlx::query!(
+        "
+        INSERT INTO photo_metadata
+            (
+                photo_id,
+                camera_make,
+                camera_model,
+                lens_model,
+                focal_length,
+                aperture,
+                exposure_time,
+                iso,
+                taken_at,
+                timezone_offset_minutes,
+                latitude,
+                longitude,
+                altitude
+            )
+        VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        ON CONFLICT (photo_id) DO UPDATE
+        SET camera_make = EXCLUDED.camera_make,
+            camera_model = EXCLUDED.camera_model,
+            lens_model = EXCLUDED.lens_model,
+            focal_length = EXCLUDED.focal_length,
+            aperture = EXCLUDED.aperture,
+            exposure_time = EXCLUDED.exposure_time,
+            iso = EXCLUDED.iso,
+            taken_at = EXCLUDED.taken_at,
+            timezone_offset_minutes = EXCLUDED.timezone_offset_minutes,
+            latitude = EXCLUDED.latitude,
+            longitude = EXCLUDED.longitude,
+            altitude = EXCLUDED.altitude,
+            extracted_at = now()
+        ",
+        photo_id,
+        metadata.camera_make,
+        metadata.camera_model,
+        metadata.lens_model,
+        metadata.focal_length,
+        metadata.aperture,
+        metadata.exposure_time,
+        metadata.iso,
+        taken_at,
+        metadata.timezone_offset_minutes,
+        metadata.latitude,
+        metadata.longitude,
+        metadata.altitude,
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())
+    .map_err(|e| format!("メタデータの保存失敗: {:?}", e))
+}
+
+pub(crate) async fn compute_photo_placeholders(
+    pool: &PgPool,
+    client: &Client,
+    bucket_name: &str,
+    photo_id: i32,
+) -> Result<(), String> {
+    let image_path = sqlx::query_scalar!(
+        "SELECT image_path FROM photos WHERE id = $1",
+        photo_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("写真取得失敗: {:?}", e))?;
+
+    let Some(image_path) = image_path else {
+        return Ok(());
+    };
+
+    let operations = sqlx::query_scalar!(
+        "SELECT operation FROM photo_edits WHERE photo_id = $1 ORDER BY position",
+        photo_id
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("編集内容の取得失敗: {:?}", e))?;
+
+    let edits = parse_edits(operations);
+    let key = object_key_from_url(&image_path)?;
+    let data = still_image(key, download_object(client, bucket_name, key).await?).await?;
+
+    let placeholders = tokio::task::spawn_blocking(move || {
+        compute_placeholders(&apply_edits(&decode_image(&data)?, &edits))
+    })
+    .await
+    .map_err(|e| format!("プレースホルダー計算タスクの実行に失敗: {:?}", e))??;
+
+    save_placeholders(pool, photo_id, &placeholders).await
+}
+
+async fn save_placeholders(
+    pool: &PgPool,
+    photo_id: i32,
+    placeholders: &Placeholders,
+) -> Result<(), String> {
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("トランザクション開始失敗: {:?}", e))?;
+
+    sqlx::query!(
+        "
+        UPDATE photos
+        SET blurhash = $1,
+            lqip = $2,
+            dominant_color = $3,
+            average_color = $4
+        WHERE id = $5
+        ",
+        placeholders.blurhash,
+        placeholders.lqip,
+        placeholders.dominant_color,
+        placeholders.average_color,
+        photo_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("プレースホルダーの保存失敗: {:?}", e))?;
+
+    sqlx::query!(
+        "DELETE FROM photo_colors WHERE photo_id = $1",
+        photo_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("パレットの保存失敗: {:?}", e))?;
+
+    let palette = &placeholders.palette;
+    let positions: Vec<i16> = (0..palette.len() as i16).collect();
+    let colors: Vec<String> = palette.iter().map(|c| c.color.clone()).collect();
+    let l: Vec<f32> = palette.iter().map(|c| c.lab.l).collect();
+    let a: Vec<f32> = palette.iter().map(|c| c.lab.a).collect();
+    let b: Vec<f32> = palette.iter().map(|c| c.lab.b).collect();
+    let weights: Vec<f32> = palette.iter().map(|c| c.weight).collect();
+
+    // 写真が削除済みの場合は何も挿入しない
+    sqlx::query!(
+        "
+        INSERT INTO photo_colors (photo_id, position, color, l, a, b, weight)
+        SELECT $1, t.*
+        FROM UNNEST($2::SMALLINT[], $3::TEXT[], $4::REAL[], $5::REAL[], $6::REAL[], $7::REAL[])
+            AS t(position, color, l, a, b, weight)
+        WHERE EXISTS (SELECT 1 FROM photos WHERE id = $1)
+        ",
+        photo_id,
+        &positions,
+        &colors,
+        &l,
+        &a,
+        &b,
+        &weights,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("パレットの保存失敗: {:?}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("トランザクションコミット失敗: {:?}", e))
+}
+
+pub(crate) async fn compute_photo_perceptual_hash(
+    pool: &PgPool,
+    client: &Client,
+    bucket_name: &str,
+    photo_id: i32,
+) -> Result<(), String> {
+    let image_path = sqlx::query_scalar!(
+        "SELECT image_path FROM photos WHERE id = $1",
+        photo_id
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("写真取得失敗: {:?}", e))?;
+
+    let Some(image_path) = image_path else {
+        return Ok(());
+    };
+
+    let key = object_key_from_url(&image_path)?;
+    let data = still_image(key, download_object(client, bucket_name, key).await?).await?;
+
+    let hash = tokio::task::spawn_blocking(move || decode_image(&data).map(|img| perceptual_hash(&img)))
+        .await
+        .map_err(|e| format!("知覚ハッシュ計算タスクの実行に失敗: {:?}", e))??;
+
+    save_perceptual_hash(pool, photo_id, hash).await
+}
+
+async fn save_perceptual_hash(
+    pool: &PgPool,
+    photo_id: i32,
+    hash: i64,
+) -> Result<(), String> {
+    let bands: Vec<i16> = (0..hash_bands(hash).len() as i16).collect();
+    let values: Vec<i16> = hash_bands(hash).to_vec();
+
+    let mut tx = pool
+        .begin()
+        .await
+        .map_err(|e| format!("トランザクション開始失敗: {:?}", e))?;
+
+    let user_id = sqlx::query_scalar!(
+        "UPDATE photos SET phash = $1 WHERE id = $2 RETURNING user_id",
+        hash,
+        photo_id,
+    )
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| format!("知覚ハッシュの保存失敗: {:?}", e))?;
+
+    let Some(user_id) = user_id else {
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "DELETE FROM photo_hash_bands WHERE photo_id = $1",
+        photo_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("知覚ハッシュの保存失敗: {:?}", e))?;
+
+    sqlx::query!(
+        "
+        INSERT INTO photo_hash_bands (photo_id, user_id, band, value)
+        SELECT $1, $2, band, value
+        FROM UNNEST($3::SMALLINT[], $4::SMALLINT[]) AS t(band, value)
+        ",
+        photo_id,
+        user_id,
+        &bands,
+        &values,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| format!("知覚ハッシュの保存失敗: {:?}", e))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("トランザクションコミット失敗: {:?}", e))
+}
@@ -0,0 +1,201 @@
+use std::time::Duration;
+use sqlx::{PgExecutor, PgPool};
+
+use crate::workers::job::{retry_delay, Job, JobType};
+
+// 実行中のジョブは一定間隔で locked_at を更新する
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+// locked_at が更新されないジョブは、ワーカーが落ちたものとみなして再取得する。ハートビートの遅れを見込んで間隔より十分長くする
+const STALE_LOCK_SECONDS: f64 = 5.0 * 60.0;
+
+pub struct ClaimedJob {
+    pub id: i64,
+    pub payload: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+}
+
+// 呼び出し側のトランザクション内で登録できるよう executor を受け取る
+pub async fn enqueue<'e, E: PgExecutor<'e>>(executor: E, job: &Job) -> Result<i64, sqlx::Error> {
+    let payload = job.to_payload();
+    let job_type = job.job_type();
+
+    sqlx::query_scalar!(
+        "
+        INSERT INTO jobs (job_type, payload, max_attempts)
+        VALUES ($1, $2::TEXT::JSONB, $3)
+        RETURNING id
+        ",
+        job_type.as_str(),
+        payload,
+        job_type.max_attempts(),
+    )
+    .fetch_one(executor)
+    .await
+}
+
+pub async fn enqueue_many<'e, E: PgExecutor<'e>>(executor: E, jobs: &[Job]) -> Result<u64, sqlx::Error> {
+    let mut job_types = Vec::with_capacity(jobs.len());
+    let mut payloads = Vec::with_capacity(jobs.len());
+    let mut max_attempts = Vec::with_capacity(jobs.len());
+
+    for job in jobs {
+        payloads.push(job.to_payload());
+        job_types.push(job.job_type().as_str().to_string());
+        max_attempts.push(job.job_type().max_attempts());
+    }
+
+    sqlx::query!(
+        "
+        INSERT INTO jobs (job_type, payload, max_attempts)
+        SELECT job_type, payload::JSONB, max_attempts
+        FROM UNNEST($1::TEXT[], $2::TEXT[], $3::INTEGER[]) AS t(job_type, payload, max_attempts)
+        ",
+        &job_types,
+        &payloads,
+        &max_attempts,
+    )
+    .execute(executor)
+    .await
+    .map(|result| result.rows_affected())
+}
+
+// 実行可能なジョブを1件取得して実行中にする。複数のワーカーが同時に取得しても重複しない
+pub async fn claim(pool: &PgPool, job_type: JobType) -> Result<Option<ClaimedJob>, String> {
+    sqlx::query_as!(
+        ClaimedJob,
+        r#"
+        UPDATE jobs
+        SET status = 'running',
+            attempts = attempts + 1,
+            locked_at = now(),
+            updated_at = now()
+        WHERE id = (
+            SELECT id
+            FROM jobs
+            WHERE job_type = $1
+            AND (
+                (status = 'pending' AND run_at <= now()) OR
+                (status = 'running' AND locked_at < now() - make_interval(secs => $2))
+            )
+            ORDER BY run_at, id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING
+            id,
+            payload::TEXT AS "payload!",
+            attempts,
+            max_attempts
+        "#,
+        job_type.as_str(),
+        STALE_LOCK_SECONDS,
+    )
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| format!("ジョブの取得失敗: {:?}", e))
+}
+
+// 実行中であることを記録する。再取得されて別のワーカーが実行している場合は false
+pub async fn heartbeat(pool: &PgPool, job_id: i64, attempts: i32) -> Result<bool, String> {
+    sqlx::query!(
+        "
+        UPDATE jobs
+        SET locked_at = now()
+        WHERE id = $1 AND status = 'running' AND attempts = $2
+        ",
+        job_id,
+        attempts,
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("ジョブの実行中の記録失敗: {:?}", e))
+}
+
+// 完了・失敗の登録は、取得したときの実行のままの場合だけ行う
+// ロックが古くなって別のワーカーに再取得されていれば何もせず false を返す
+pub async fn complete(pool: &PgPool, job: &ClaimedJob) -> Result<bool, String> {
+    sqlx::query!(
+        "
+        UPDATE jobs
+        SET status = 'completed',
+            locked_at = NULL,
+            completed_at = now(),
+            updated_at = now()
+        WHERE id = $1 AND status = 'running' AND attempts = $2
+        ",
+        job.id,
+        job.attempts,
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("ジョブの完了登録失敗: {:?}", e))
+}
+
+// 再試行の上限に達していなければ待ち時間を空けて再実行し、達していれば dead にする
+pub async fn fail(pool: &PgPool, job: &ClaimedJob, error: &str) -> Result<bool, String> {
+    let dead = job.attempts >= job.max_attempts;
+    let delay = retry_delay(job.attempts).as_secs_f64();
+
+    sqlx::query!(
+        "
+        UPDATE jobs
+        SET status = CASE WHEN $2 THEN 'dead' ELSE 'pending' END,
+            run_at = CASE WHEN $2 THEN run_at ELSE now() + make_interval(secs => $3) END,
+            locked_at = NULL,
+            last_error = $4,
+            updated_at = now()
+        WHERE id = $1 AND status = 'running' AND attempts = $5
+        ",
+        job.id,
+        dead,
+        delay,
+        error,
+        job.attempts,
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("ジョブの失敗登録失敗: {:?}", e))
+}
+
+// 内容を読み取れないジョブは再試行しても成功しないため、すぐに dead にする
+pub async fn bury(pool: &PgPool, job: &ClaimedJob, error: &str) -> Result<bool, String> {
+    sqlx::query!(
+        "
+        UPDATE jobs
+        SET status = 'dead',
+            locked_at = NULL,
+            last_error = $2,
+            updated_at = now()
+        WHERE id = $1 AND status = 'running' AND attempts = $3
+        ",
+        job.id,
+        error,
+        job.attempts,
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+    .map_err(|e| format!("ジョブの失敗登録失敗: {:?}", e))
+}
+
+// dead になったジョブを最初からやり直す。対象が無ければ false
+pub async fn retry_dead(pool: &PgPool, job_id: i64) -> Result<bool, sqlx::Error> {
+    sqlx::query!(
+        "
+        UPDATE jobs
+        SET status = 'pending',
+            attempts = 0,
+            run_at = now(),
+            updated_at = now()
+        WHERE id = $1 AND status = 'dead'
+        ",
+        job_id,
+    )
+    .execute(pool)
+    .await
+    .map(|result| result.rows_affected() > 0)
+}
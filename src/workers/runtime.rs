@@ -0,0 +1,155 @@
+use std::sync::Arc;
+use std::time::Duration;
+use aws_sdk_s3::Client;
+use sqlx::PgPool;
+use tokio::sync::Semaphore;
+
+use crate::handlers::s3_handler::delete_image_from_s3;
+use crate::utils::image_processing::RenditionConfig;
+use crate::utils::s3::{create_s3_client, delete_objects_with_prefix};
+use crate::workers::job::{Job, JobType};
+use crate::workers::photo_processor::{
+    compute_photo_perceptual_hash,
+    compute_photo_placeholders,
+    extract_photo_metadata,
+    process_photo,
+};
+use crate::workers::queue::{self, ClaimedJob};
+
+// 取得できるジョブが無いときの待ち時間
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+struct WorkerContext {
+    pool: PgPool,
+    client: Client,
+    bucket_name: String,
+    region: String,
+    rendition_config: Arc<RenditionConfig>,
+}
+
+// ジョブの種類ごとに取得ループを起動する。同時実行数は種類ごとのセマフォで制限する
+pub fn spawn_job_workers(pool: PgPool) {
+    let (client, bucket_name, region) = create_s3_client();
+    let context = Arc::new(WorkerContext {
+        pool,
+        client,
+        bucket_name,
+        region,
+        rendition_config: Arc::new(RenditionConfig::from_env()),
+    });
+
+    for job_type in JobType::ALL {
+        tokio::spawn(run_job_type(Arc::clone(&context), job_type));
+    }
+}
+
+async fn run_job_type(context: Arc<WorkerContext>, job_type: JobType) {
+    let semaphore = Arc::new(Semaphore::new(job_type.concurrency()));
+
+    loop {
+        let permit = match Arc::clone(&semaphore).acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+
+        match queue::claim(&context.pool, job_type).await {
+            Ok(Some(job)) => {
+                let context = Arc::clone(&context);
+                tokio::spawn(async move {
+                    execute(&context, job).await;
+                    drop(permit);
+                });
+            }
+            Ok(None) => {
+                drop(permit);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                drop(permit);
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+async fn execute(context: &WorkerContext, claimed: ClaimedJob) {
+    let job = match serde_json::from_str::<Job>(&claimed.payload) {
+        Ok(job) => job,
+        Err(e) => {
+            let error = format!("ジョブの内容を読み取れません: {}", e);
+            eprintln!("ジョブ {}: {}", claimed.id, error);
+            report(&claimed, queue::bury(&context.pool, &claimed, &error).await);
+            return;
+        }
+    };
+
+    // 長時間かかるジョブが実行中に再取得されないよう、終わるまで locked_at を更新し続ける
+    let heartbeat = tokio::spawn(keep_alive(context.pool.clone(), claimed.id, claimed.attempts));
+    let outcome = run(context, &job).await;
+    heartbeat.abort();
+
+    let result = match outcome {
+        Ok(()) => queue::complete(&context.pool, &claimed).await,
+        Err(error) => {
+            eprintln!("ジョブ {} ({}) の実行に失敗 ({}/{}回目): {}", claimed.id, job.job_type().as_str(), claimed.attempts, claimed.max_attempts, error);
+            queue::fail(&context.pool, &claimed, &error).await
+        }
+    };
+
+    report(&claimed, result);
+}
+
+fn report(claimed: &ClaimedJob, result: Result<bool, String>) {
+    match result {
+        Ok(true) => {}
+        Ok(false) => eprintln!("ジョブ {} ({}回目) は別のワーカーに再取得されていたため、結果を記録しませんでした", claimed.id, claimed.attempts),
+        Err(e) => eprintln!("{}", e),
+    }
+}
+
+async fn keep_alive(pool: PgPool, job_id: i64, attempts: i32) {
+    let mut interval = tokio::time::interval(queue::HEARTBEAT_INTERVAL);
+    // 最初の tick はすぐに完了する（取得時に locked_at を設定済み）
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        match queue::heartbeat(&pool, job_id, attempts).await {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!("ジョブ {} は別のワーカーに再取得されました", job_id);
+                return;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+async fn run(context: &WorkerContext, job: &Job) -> Result<(), String> {
+    let WorkerContext { pool, client, bucket_name, region, rendition_config } = context;
+
+    match job {
+        Job::ProcessPhoto { photo_id } => {
+            process_photo(pool, client, bucket_name, region, rendition_config, *photo_id).await
+        }
+        Job::ExtractMetadata { photo_id } => {
+            extract_photo_metadata(pool, client, bucket_name, *photo_id).await
+        }
+        Job::ComputePlaceholders { photo_id } => {
+            compute_photo_placeholders(pool, client, bucket_name, *photo_id).await
+        }
+        Job::ComputePerceptualHash { photo_id } => {
+            compute_photo_perceptual_hash(pool, client, bucket_name, *photo_id).await
+        }
+        Job::DeleteObjects { image_paths, prefixes } => {
+            for image_path in image_paths {
+                delete_image_from_s3(client, bucket_name, image_path).await?;
+            }
+            for prefix in prefixes {
+                delete_objects_with_prefix(client, bucket_name, prefix).await?;
+            }
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use aws_sdk_s3::Client;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::handlers::folder_handler::lock_folder_tree;
+use crate::handlers::trash_handler::{purge_trash_entries, trash_retention_days};
+use crate::utils::cron::CronSchedule;
+use crate::utils::s3::{abort_stale_multipart_uploads, create_s3_client};
+
+const TICK_INTERVAL: Duration = Duration::from_secs(20);
+// pg_try_advisory_lock(クラス, タスク) のクラス部分。他の用途のロックと衝突しないよう固定値にする
+const ADVISORY_LOCK_CLASS: i32 = 0x5343_4844;
+const DEFAULT_STALE_UPLOAD_HOURS: i64 = 24;
+const DEFAULT_JOB_RETENTION_DAYS: i32 = 7;
+const RUN_HISTORY_RETENTION_DAYS: i32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduledTask {
+    // 完了しないまま残ったS3のマルチパートアップロードを中止する
+    AbortStaleUploads,
+    // 完了済みのジョブと古い実行履歴を削除する
+    PruneJobs,
+    // 保持期間を過ぎたゴミ箱の写真・フォルダーを完全に削除する
+    PurgeTrash,
+}
+
+impl ScheduledTask {
+    pub const ALL: [ScheduledTask; 3] = [
+        ScheduledTask::AbortStaleUploads,
+        ScheduledTask::PruneJobs,
+        ScheduledTask::PurgeTrash,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == value)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScheduledTask::AbortStaleUploads => "abort_stale_uploads",
+            ScheduledTask::PruneJobs => "prune_jobs",
+            ScheduledTask::PurgeTrash => "purge_trash",
+        }
+    }
+
+    fn default_schedule(&self) -> &'static str {
+        match self {
+            ScheduledTask::AbortStaleUploads => "15 * * * *",
+            ScheduledTask::PruneJobs => "30 3 * * *",
+            ScheduledTask::PurgeTrash => "0 4 * * *",
+        }
+    }
+
+    // SCHEDULE_PRUNE_JOBS="0 4 * * *" のように変更できる。"off" の場合は実行しない
+    pub fn schedule_expression(&self) -> Option<String> {
+        let expression = env::var(format!("SCHEDULE_{}", self.name().to_ascii_uppercase()))
+            .unwrap_or_else(|_| self.default_schedule().to_string());
+
+        (expression.trim() != "off").then_some(expression)
+    }
+
+    fn lock_key(&self) -> i32 {
+        Self::ALL.iter().position(|t| t == self).unwrap_or_default() as i32
+    }
+}
+
+struct SchedulerContext {
+    pool: PgPool,
+    client: Client,
+    bucket_name: String,
+}
+
+pub fn spawn_scheduler(pool: PgPool) {
+    let (client, bucket_name, _) = create_s3_client();
+    let context = Arc::new(SchedulerContext { pool, client, bucket_name });
+
+    let now = OffsetDateTime::now_utc();
+    let mut entries = Vec::new();
+
+    for task in ScheduledTask::ALL {
+        let Some(expression) = task.schedule_expression() else {
+            continue;
+        };
+
+        match CronSchedule::parse(&expression) {
+            Ok(schedule) => {
+                if let Some(next_run) = schedule.next_after(now) {
+                    entries.push((task, schedule, next_run));
+                }
+            }
+            Err(e) => eprintln!("定期タスク {} のスケジュールが不正です: {}", task.name(), e),
+        }
+    }
+
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(TICK_INTERVAL).await;
+            let now = OffsetDateTime::now_utc();
+
+            entries.retain_mut(|(task, schedule, next_run)| {
+                if *next_run > now {
+                    return true;
+                }
+
+                let scheduled_for = *next_run;
+                let task = *task;
+                let context = Arc::clone(&context);
+                tokio::spawn(async move {
+                    if let Err(e) = run_once(&context, task, scheduled_for).await {
+                        eprintln!("定期タスク {} の実行に失敗: {}", task.name(), e);
+                    }
+                });
+
+                match schedule.next_after(now) {
+                    Some(next) => {
+                        *next_run = next;
+                        true
+                    }
+                    None => false,
+                }
+            });
+        }
+    });
+}
+
+// 複数のサーバーで同時に動いていても、予定時刻ごとに1回だけ実行する
+async fn run_once(context: &SchedulerContext, task: ScheduledTask, scheduled_for: OffsetDateTime) -> Result<(), String> {
+    let mut conn = context
+        .pool
+        .acquire()
+        .await
+        .map_err(|e| format!("DB接続の取得失敗: {:?}", e))?;
+
+    // 前回の実行が長引いている場合などは、ロックを取れないので今回は見送る
+    let locked = sqlx::query_scalar!(
+        "SELECT pg_try_advisory_lock($1, $2)",
+        ADVISORY_LOCK_CLASS,
+        task.lock_key(),
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| format!("ロックの取得失敗: {:?}", e))?;
+
+    if locked != Some(true) {
+        return Ok(());
+    }
+
+    let result = record_and_run(context, task, scheduled_for).await;
+
+    let unlocked = sqlx::query_scalar!(
+        "SELECT pg_advisory_unlock($1, $2)",
+        ADVISORY_LOCK_CLASS,
+        task.lock_key(),
+    )
+    .fetch_one(&mut *conn)
+    .await;
+
+    if let Err(e) = unlocked {
+        // ロックは接続に紐づくため、解放できない接続はプールに戻さない
+        conn.detach();
+        eprintln!("ロックの解放失敗: {:?}", e);
+    }
+
+    result
+}
+
+async fn record_and_run(context: &SchedulerContext, task: ScheduledTask, scheduled_for: OffsetDateTime) -> Result<(), String> {
+    let run_id = sqlx::query_scalar!(
+        "
+        INSERT INTO scheduled_task_runs (task_name, scheduled_for)
+        VALUES ($1, $2)
+        ON CONFLICT (task_name, scheduled_for) DO NOTHING
+        RETURNING id
+        ",
+        task.name(),
+        scheduled_for,
+    )
+    .fetch_optional(&context.pool)
+    .await
+    .map_err(|e| format!("実行履歴の登録失敗: {:?}", e))?;
+
+    // 他のサーバーで実行済み
+    let Some(run_id) = run_id else {
+        return Ok(());
+    };
+
+    let result = run_task(context, task).await;
+    let (status, detail) = match &result {
+        Ok(detail) => ("succeeded", detail.clone()),
+        Err(e) => ("failed", e.clone()),
+    };
+
+    sqlx::query!(
+        "
+        UPDATE scheduled_task_runs
+        SET status = $1,
+            detail = $2,
+            finished_at = now()
+        WHERE id = $3
+        ",
+        status,
+        detail,
+        run_id,
+    )
+    .execute(&context.pool)
+    .await
+    .map_err(|e| format!("実行履歴の更新失敗: {:?}", e))?;
+
+    result.map(|_| ())
+}
+
+// 成功時は実行結果の概要を返す
+async fn run_task(context: &SchedulerContext, task: ScheduledTask) -> Result<String, String> {
+    match task {
+        ScheduledTask::AbortStaleUploads => {
+            let hours = env::var("STALE_UPLOAD_HOURS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .filter(|&h| h > 0)
+                .unwrap_or(DEFAULT_STALE_UPLOAD_HOURS);
+            let initiated_before = OffsetDateTime::now_utc().unix_timestamp() - hours * 60 * 60;
+
+            let aborted = abort_stale_multipart_uploads(&context.client, &context.bucket_name, initiated_before).await?;
+
+            Ok(format!("{}件のアップロードを中止しました", aborted))
+        }
+        ScheduledTask::PruneJobs => {
+            let retention_days = env::var("JOB_RETENTION_DAYS")
+                .ok()
+                .and_then(|v| v.parse::<i32>().ok())
+                .filter(|&d| d > 0)
+                .unwrap_or(DEFAULT_JOB_RETENTION_DAYS);
+
+            // dead のジョブは調査・再実行のため残す
+            let jobs = sqlx::query!(
+                "
+                DELETE FROM jobs
+                WHERE status = 'completed'
+                AND completed_at < now() - make_interval(days => $1)
+                ",
+                retention_days,
+            )
+            .execute(&context.pool)
+            .await
+            .map_err(|e| format!("ジョブの削除失敗: {:?}", e))?;
+
+            let runs = sqlx::query!(
+                "
+                DELETE FROM scheduled_task_runs
+                WHERE started_at < now() - make_interval(days => $1)
+                ",
+                RUN_HISTORY_RETENTION_DAYS,
+            )
+            .execute(&context.pool)
+            .await
+            .map_err(|e| format!("実行履歴の削除失敗: {:?}", e))?;
+
+            Ok(format!(
+                "完了済みジョブ{}件、実行履歴{}件を削除しました",
+                jobs.rows_affected(),
+                runs.rows_affected(),
+            ))
+        }
+        ScheduledTask::PurgeTrash => {
+            let expired = sqlx::query!(
+                r#"
+                SELECT user_id, ARRAY_AGG(id) AS "entry_ids!"
+                FROM trash_entries
+                WHERE deleted_at < now() - make_interval(days => $1)
+                GROUP BY user_id
+                "#,
+                trash_retention_days(),
+            )
+            .fetch_all(&context.pool)
+            .await
+            .map_err(|e| format!("ゴミ箱の取得失敗: {:?}", e))?;
+
+            let mut folders = 0;
+            let mut photos = 0;
+
+            // フォルダー構成のロックはユーザーごとなので、ユーザーごとにコミットする
+            for row in expired {
+                let mut tx = context
+                    .pool
+                    .begin()
+                    .await
+                    .map_err(|e| format!("トランザクション開始エラー: {:?}", e))?;
+
+                lock_folder_tree(&mut tx, row.user_id)
+                    .await
+                    .map_err(|e| format!("フォルダーのロック失敗: {:?}", e))?;
+
+                let (deleted_folders, deleted_photos) = purge_trash_entries(&mut tx, row.user_id, &row.entry_ids)
+                    .await
+                    .map_err(|e| format!("ゴミ箱の削除失敗: {:?}", e))?;
+
+                tx.commit()
+                    .await
+                    .map_err(|e| format!("トランザクションコミット失敗: {:?}", e))?;
+
+                folders += deleted_folders;
+                photos += deleted_photos;
+            }
+
+            Ok(format!("ゴミ箱からフォルダー{}件、写真{}件を削除しました", folders, photos))
+        }
+    }
+}